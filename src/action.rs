@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    axial::Axial,
+    cities_knights::{ProgressCard, Track},
+    edge::PathCoords,
+    resource::{Commodity, Resource, ResourceGroup},
+};
+
+/// Everything a player can do, taken by whoever `Phase::player` says is acting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    /// Free during initial placement, otherwise paid for.
+    BuildSettlement(Axial),
+    BuildCity(Axial),
+    /// Free during initial placement and road building, otherwise paid for.
+    BuildRoad(PathCoords),
+    /// Seafarers ship, built like a road but along the sea.
+    BuildShip(PathCoords),
+    /// Moves the ship at the open end of a shipping route, once per turn.
+    MoveShip {
+        from: PathCoords,
+        to: PathCoords,
+    },
+    /// Cities & Knights: a new basic knight next to one of the player's roads.
+    BuildKnight(Axial),
+    /// Pays a wheat so the knight defends against the barbarians.
+    ActivateKnight(Axial),
+    PromoteKnight(Axial),
+    /// Moves an active knight along the player's roads, which deactivates it.
+    MoveKnight {
+        from: Axial,
+        to: Axial,
+    },
+    /// Deactivates a knight beside the robber to move the robber.
+    ChaseRobber(Axial),
+    /// Raises a city improvement track by one level.
+    ImproveCity(Track),
+    /// `resource` is named for the cards that use one and `None` for the rest.
+    PlayProgressCard {
+        card: ProgressCard,
+        resource: Option<Resource>,
+    },
+    RollDice,
+    /// The cards given back to the bank after a 7.
+    Discard(ResourceGroup),
+    /// Moves the robber and steals a random card from `victim`, who must have a building
+    /// on the hex whenever anyone with cards does.
+    MoveRobber {
+        pos: Axial,
+        victim: Option<usize>,
+    },
+    /// Moves the pirate instead of the robber, stealing from a player with a ship beside
+    /// the sea hex.
+    MovePirate {
+        pos: Axial,
+        victim: Option<usize>,
+    },
+    BuyDevelopmentCard,
+    PlayKnight,
+    PlayRoadBuilding,
+    PlayYearOfPlenty,
+    PlayMonopoly(Resource),
+    /// One of the two resources picked with year of plenty.
+    TakeResource(Resource),
+    /// Trades the player's harbor rate of `give` for one `get` from the bank.
+    BankTrade {
+        give: Resource,
+        get: Resource,
+    },
+    /// Cities & Knights: trades commodities for a resource from the bank.
+    CommodityTrade {
+        give: Commodity,
+        get: Resource,
+    },
+    OfferTrade {
+        to: usize,
+        give: ResourceGroup,
+        get: ResourceGroup,
+    },
+    AcceptTrade,
+    RejectTrade,
+    EndTurn,
+}
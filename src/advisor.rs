@@ -0,0 +1,294 @@
+use std::collections::HashSet;
+
+use crate::{
+    axial::Axial,
+    board::Board,
+    edge::{PathCoords, PathType},
+    error::RuleError,
+    harbor::Harbor,
+    phase::Phase,
+    production::Production,
+    resource::Resource,
+    vertex::BuildType,
+};
+
+/// Bonus per resource the player doesn't produce yet, in expected cards per roll.
+const NEW_RESOURCE_BONUS: f64 = 0.05;
+/// Bonus for producing both halves of ore and wheat, or brick and wood.
+const PAIR_BONUS: f64 = 0.03;
+const GENERIC_HARBOR_BONUS: f64 = 0.02;
+/// Share of the player's production of its resource a 2:1 harbor adds.
+const HARBOR_SHARE: f64 = 0.5;
+/// Share of the best spot a road opens up that it adds.
+const ROAD_SHARE: f64 = 0.25;
+
+/// A settlement and road to place, with the reasons for its score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub settlement: Axial,
+    pub road: PathCoords,
+    pub score: f64,
+    /// Expected cards per roll from the settlement.
+    pub production: f64,
+    /// Resources the settlement adds to what the player already produces.
+    pub new_resources: Vec<Resource>,
+    /// The harbor at the settlement, if any.
+    pub harbor: Option<Harbor>,
+    /// Expected cards per roll of the best free spot the road leads towards.
+    pub next_spot: f64,
+}
+
+impl Board {
+    /// Every settlement and road `phase`'s player may place next in the initial placement,
+    /// best first. Settlements score by their expected production, the resources they add
+    /// to the player's other settlement and the harbor they reach, and roads by the best
+    /// spot they lead towards. Once the settlement is down only its roads are suggested.
+    pub fn opening_suggestions(&self, phase: Phase) -> Result<Vec<Suggestion>, RuleError> {
+        let Phase::InitialPlacement {
+            player,
+            placing_road,
+            ..
+        } = phase
+        else {
+            return Err(RuleError::WrongPhase);
+        };
+        let settlements: Vec<Axial> = if placing_road {
+            self.vertices
+                .values()
+                .filter(|v| v.owner == Some(player) && v.build_type == BuildType::Settlement)
+                .filter(|v| {
+                    self.get_adjacent_paths(v.pos)
+                        .iter()
+                        .all(|p| self.edges[p].owner != Some(player))
+                })
+                .map(|v| v.pos)
+                .collect()
+        } else {
+            let mut spots = self.get_valid_build_spots(BuildType::Settlement, player, false);
+            spots.retain(|&v| self.is_on_land(v));
+            spots
+        };
+        let settled = self.settled(player, placing_road);
+        let produced: HashSet<Resource> = settled
+            .iter()
+            .flat_map(|&pos| self.producing(pos))
+            .collect();
+        let mut elsewhere: Production = [0.0; 5];
+        for &pos in &settled {
+            for (total, cards) in elsewhere
+                .iter_mut()
+                .zip(self.expected_vertex_production(pos))
+            {
+                *total += cards;
+            }
+        }
+
+        let mut suggestions: Vec<Suggestion> = settlements
+            .into_iter()
+            .flat_map(|settlement| {
+                self.settlement_suggestions(player, settlement, &produced, &elsewhere)
+            })
+            .collect();
+        suggestions.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.settlement.cmp(&b.settlement))
+                .then_with(|| a.road.cmp(&b.road))
+        });
+        Ok(suggestions)
+    }
+
+    /// The buildings `player` collects from already, leaving out the settlement still
+    /// waiting for its road when `placing_road`.
+    fn settled(&self, player: usize, placing_road: bool) -> Vec<Axial> {
+        self.vertices
+            .values()
+            .filter(|v| v.owner == Some(player) && v.build_type.is_building())
+            .filter(|v| {
+                !placing_road
+                    || self
+                        .get_adjacent_paths(v.pos)
+                        .iter()
+                        .any(|p| self.edges[p].owner == Some(player))
+            })
+            .map(|v| v.pos)
+            .collect()
+    }
+
+    fn settlement_suggestions(
+        &self,
+        player: usize,
+        settlement: Axial,
+        produced: &HashSet<Resource>,
+        elsewhere: &Production,
+    ) -> Vec<Suggestion> {
+        let production: Production = self.expected_vertex_production(settlement);
+        let total: f64 = production.iter().sum();
+        let new_resources: Vec<Resource> = self
+            .producing(settlement)
+            .into_iter()
+            .filter(|r| !produced.contains(r))
+            .collect();
+        let all: HashSet<Resource> = produced
+            .iter()
+            .copied()
+            .chain(new_resources.clone())
+            .collect();
+        let pairs = [
+            (Resource::Ore, Resource::Wheat),
+            (Resource::Brick, Resource::Wood),
+        ]
+        .iter()
+        .filter(|(a, b)| all.contains(a) && all.contains(b))
+        .count();
+        let harbor = self.harbor_at(settlement);
+        let harbor_bonus = match harbor.map(|h| h.resource) {
+            None => 0.0,
+            Some(None) => GENERIC_HARBOR_BONUS,
+            Some(Some(r)) => HARBOR_SHARE * (production[r.index()] + elsewhere[r.index()]),
+        };
+        let base = total
+            + NEW_RESOURCE_BONUS * new_resources.len() as f64
+            + PAIR_BONUS * pairs as f64
+            + harbor_bonus;
+
+        self.get_adjacent_paths(settlement)
+            .into_iter()
+            .filter(|road| {
+                self.validate_path(player, road.clone(), PathType::Road, false)
+                    .is_ok()
+            })
+            .map(|road| {
+                let next_spot = self.next_spot(player, settlement, &road);
+                Suggestion {
+                    settlement,
+                    road,
+                    score: base + ROAD_SHARE * next_spot,
+                    production: total,
+                    new_resources: new_resources.clone(),
+                    harbor,
+                    next_spot,
+                }
+            })
+            .collect()
+    }
+
+    /// The expected production of the best spot one road beyond `road`, which can't be
+    /// taken while `settlement` is next to it.
+    fn next_spot(&self, player: usize, settlement: Axial, road: &PathCoords) -> f64 {
+        let [a, b] = road.vertices();
+        let end = if a == settlement { b } else { a };
+        self.get_adjacent_vertices(end)
+            .into_iter()
+            .map(|v| v.pos)
+            .filter(|&v| v != settlement)
+            .filter(|&v| self.validate_settlement(player, v, false).is_ok())
+            .filter(|&v| {
+                !self
+                    .get_adjacent_vertices(v)
+                    .iter()
+                    .any(|n| n.pos == settlement)
+            })
+            .map(|v| self.expected_vertex_production(v).iter().sum())
+            .fold(0.0, f64::max)
+    }
+
+    /// The best harbor on an edge ending at `pos`.
+    fn harbor_at(&self, pos: Axial) -> Option<Harbor> {
+        self.harbors
+            .iter()
+            .filter(|(coords, _)| coords.contains(pos))
+            .map(|(_, &harbor)| harbor)
+            .min_by_key(|h| h.ratio())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{action::Action, game::Game, player::Player};
+
+    use super::*;
+
+    #[test]
+    fn test_suggestions_are_legal_and_ranked() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 0).unwrap();
+        let suggestions = game.board.opening_suggestions(game.phase()).unwrap();
+        let spots = game
+            .board
+            .get_valid_build_spots(BuildType::Settlement, 0, false);
+        let roads: usize = spots
+            .iter()
+            .map(|&v| game.board.get_adjacent_paths(v).len())
+            .sum();
+        assert_eq!(suggestions.len(), roads);
+        assert!(suggestions.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let best = suggestions[0].clone();
+        assert_eq!(best.new_resources, game.board.producing(best.settlement));
+
+        game.apply(Action::BuildSettlement(best.settlement))
+            .unwrap();
+        let roads = game.board.opening_suggestions(game.phase()).unwrap();
+        assert!(roads.iter().all(|s| s.settlement == best.settlement));
+        assert_eq!(roads[0].road, best.road);
+        game.apply(Action::BuildRoad(best.road)).unwrap();
+    }
+
+    #[test]
+    fn test_second_settlement_values_new_resources() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 0).unwrap();
+        let mut first = None;
+        while game.current_player() != 0 || first.is_none() {
+            let best = game.board.opening_suggestions(game.phase()).unwrap()[0].clone();
+            game.apply(Action::BuildSettlement(best.settlement))
+                .unwrap();
+            game.apply(Action::BuildRoad(best.road.clone())).unwrap();
+            first.get_or_insert(best);
+        }
+        let first = first.unwrap();
+
+        let second = game.board.opening_suggestions(game.phase()).unwrap();
+        let owned = game.board.producing(first.settlement);
+        for s in &second {
+            assert!(s.new_resources.iter().all(|r| !owned.contains(r)));
+        }
+        assert!(!second[0].new_resources.is_empty());
+    }
+
+    #[test]
+    fn test_placed_settlement_keeps_its_score() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 0).unwrap();
+        let suggestions = game.board.opening_suggestions(game.phase()).unwrap();
+        let harbor_spot = suggestions
+            .iter()
+            .find(|s| {
+                s.harbor
+                    .and_then(|h| h.resource)
+                    .is_some_and(|r| game.board.producing(s.settlement).contains(&r))
+            })
+            .expect("seed 0 has a 2:1 harbor next to its resource")
+            .settlement;
+        let scores = |suggestions: &[Suggestion]| -> Vec<(PathCoords, f64)> {
+            suggestions
+                .iter()
+                .filter(|s| s.settlement == harbor_spot)
+                .map(|s| (s.road.clone(), s.score))
+                .collect()
+        };
+        let before = scores(&suggestions);
+
+        game.apply(Action::BuildSettlement(harbor_spot)).unwrap();
+        let roads = game.board.opening_suggestions(game.phase()).unwrap();
+        assert_eq!(scores(&roads), before);
+    }
+
+    #[test]
+    fn test_only_during_initial_placement() {
+        let game = Game::with_seed(Player::init_players(3), Board::new(), 0).unwrap();
+        let board = game.board();
+        assert_eq!(
+            board.opening_suggestions(Phase::START_TURNS),
+            Err(RuleError::WrongPhase)
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    action::Action,
+    error::RuleError,
+    game::{Game, TradeOffer},
+    phase::{Phase, TurnPhase},
+    resource::ResourceGroup,
+    rules::selections,
+    view::PlayerView,
+};
+
+/// A player that picks its own actions, seeing only what `PlayerView` shows it.
+pub trait Agent {
+    /// Picks one of `legal`, which is never empty.
+    fn choose_action(&mut self, view: &PlayerView, legal: &[Action]) -> Action;
+
+    /// Picks one of `legal`, knowing what `belief` does. Agents that simulate ahead play
+    /// on its samples. By default `choose_action` decides from its view.
+    fn choose_action_in(&mut self, belief: &Belief, legal: &[Action]) -> Action {
+        self.choose_action(&belief.view(), legal)
+    }
+
+    /// Whether to accept `offer`, which was made to this agent. By default
+    /// `choose_action` decides between accepting, when the agent can afford it, and
+    /// rejecting.
+    fn respond_to_trade(&mut self, view: &PlayerView, offer: &TradeOffer) -> bool {
+        let affordable = view
+            .hand
+            .as_ref()
+            .is_some_and(|hand| hand.resources.can_afford(&offer.get));
+        let legal = if affordable {
+            vec![Action::AcceptTrade, Action::RejectTrade]
+        } else {
+            vec![Action::RejectTrade]
+        };
+        self.choose_action(view, &legal) == Action::AcceptTrade
+    }
+
+    /// The `count` cards to give back after a 7. By default `choose_action` picks one of
+    /// every way of discarding them.
+    fn discard(&mut self, view: &PlayerView, count: i32) -> ResourceGroup {
+        let hand = view.hand.as_ref().map(|h| h.resources).unwrap_or_default();
+        let legal: Vec<Action> = selections(&hand, count)
+            .into_iter()
+            .map(Action::Discard)
+            .collect();
+        match self.choose_action(view, &legal) {
+            Action::Discard(group) => group,
+            _ => ResourceGroup::empty(),
+        }
+    }
+}
+
+/// What the acting player knows of a game. Agents never see the game itself, only its
+/// view and determinizations dealing the cards the player can't see again.
+pub struct Belief<'a> {
+    game: &'a Game,
+}
+impl<'a> Belief<'a> {
+    pub(crate) fn new(game: &'a Game) -> Self {
+        Belief { game }
+    }
+
+    pub fn player(&self) -> usize {
+        self.game.phase().player()
+    }
+
+    pub fn view(&self) -> PlayerView<'a> {
+        self.game.view_for(self.player())
+    }
+
+    /// A game the player can't tell from the real one, see `Game::determinize`.
+    pub fn sample(&self, rng: &mut impl Rng) -> Game {
+        self.game.determinize(self.player(), rng)
+    }
+}
+
+/// Picks uniformly among the legal actions.
+pub struct RandomAgent {
+    rng: ChaCha8Rng,
+}
+impl RandomAgent {
+    pub fn new(seed: u64) -> Self {
+        RandomAgent {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+impl Agent for RandomAgent {
+    fn choose_action(&mut self, _view: &PlayerView, legal: &[Action]) -> Action {
+        legal
+            .choose(&mut self.rng)
+            .expect("there is always a legal action")
+            .clone()
+    }
+}
+
+/// Lets `agents[i]` play for player `i` until someone wins or `max_actions` have been
+/// applied, returning the winner. An agent answering with an illegal action stops the
+/// game with its error.
+pub fn play_game(
+    game: &mut Game,
+    agents: &mut [Box<dyn Agent>],
+    max_actions: usize,
+) -> Result<Option<usize>, RuleError> {
+    for _ in 0..max_actions {
+        if game.winner().is_some() {
+            break;
+        }
+        let player = game.phase().player();
+        let Some(action) = next_action(game, agents[player].as_mut()) else {
+            break;
+        };
+        game.apply(action)?;
+    }
+    Ok(game.winner())
+}
+
+/// What `agent` does as the player acting in `game`, or `None` if it can't do anything.
+pub(crate) fn next_action(game: &Game, agent: &mut dyn Agent) -> Option<Action> {
+    let player = game.phase().player();
+    let view = game.view_for(player);
+    let action = match game.phase() {
+        Phase::Turn {
+            turn_phase: TurnPhase::TradeOffer(_),
+            ..
+        } => {
+            let offer = game.trade().expect("a trade is on offer");
+            if agent.respond_to_trade(&view, offer) {
+                Action::AcceptTrade
+            } else {
+                Action::RejectTrade
+            }
+        }
+        Phase::Turn {
+            turn_phase: TurnPhase::Discard(_),
+            ..
+        } => {
+            let count = game.players()[player].hand_size() / 2;
+            Action::Discard(agent.discard(&view, count))
+        }
+        _ => {
+            let legal = game.legal_actions();
+            if legal.is_empty() {
+                return None;
+            }
+            agent.choose_action_in(&Belief::new(game), &legal)
+        }
+    };
+    Some(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{board::Board, phase::DevelopmentPhase, player::Player};
+
+    use super::*;
+
+    fn random_agents(n: usize) -> Vec<Box<dyn Agent>> {
+        (0..n)
+            .map(|i| Box::new(RandomAgent::new(i as u64)) as Box<dyn Agent>)
+            .collect()
+    }
+
+    #[test]
+    fn test_random_agents_finish_a_game() {
+        let mut game = Game::with_seed(Player::init_players(4), Board::new(), 2).unwrap();
+        let winner = play_game(&mut game, &mut random_agents(4), 20_000).unwrap();
+        assert!(winner.is_some());
+        assert_eq!(winner, game.winner());
+    }
+
+    /// Rejects every trade and discards as little ore as it can.
+    struct Stubborn;
+    impl Agent for Stubborn {
+        fn choose_action(&mut self, _view: &PlayerView, legal: &[Action]) -> Action {
+            legal[0].clone()
+        }
+
+        fn respond_to_trade(&mut self, _view: &PlayerView, _offer: &TradeOffer) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_hooks_answer_trades_and_discards() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 0).unwrap();
+        game.phase = Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::Free,
+            development_phase: DevelopmentPhase::Ready,
+        };
+        game.players[0].resources = ResourceGroup::new(1, 0, 0, 0, 0);
+        game.players[1].resources = ResourceGroup::new(6, 0, 0, 0, 4);
+        game.apply(Action::OfferTrade {
+            to: 1,
+            give: ResourceGroup::new(1, 0, 0, 0, 0),
+            get: ResourceGroup::new(0, 0, 0, 0, 1),
+        })
+        .unwrap();
+        let mut agents: Vec<Box<dyn Agent>> =
+            vec![Box::new(RandomAgent::new(0)), Box::new(Stubborn)];
+        play_game(&mut game, &mut agents, 1).unwrap();
+        assert_eq!(game.trade(), None);
+        assert_eq!(
+            game.players()[1].resources,
+            ResourceGroup::new(6, 0, 0, 0, 4)
+        );
+
+        game.phase = Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::Discard(1),
+            development_phase: DevelopmentPhase::Ready,
+        };
+        play_game(&mut game, &mut agents, 1).unwrap();
+        // the first way of discarding five cards gives up as little ore as possible
+        assert_eq!(
+            game.players()[1].resources,
+            ResourceGroup::new(5, 0, 0, 0, 0)
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    agent::{play_game, Agent, RandomAgent},
+    game::{Game, MAX_PLAYERS, MIN_PLAYERS},
+    heuristic::HeuristicAgent,
+    mcts::{Budget, MctsAgent, MctsConfig},
+    player::Player,
+};
+
+/// An agent a tournament can seat, written `random`, `heuristic`, `mcts` or
+/// `mcts:<iterations>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentKind {
+    Random,
+    Heuristic,
+    Mcts { iterations: usize },
+}
+impl AgentKind {
+    pub fn build(self, seed: u64) -> Box<dyn Agent> {
+        match self {
+            AgentKind::Random => Box::new(RandomAgent::new(seed)),
+            AgentKind::Heuristic => Box::new(HeuristicAgent::new()),
+            AgentKind::Mcts { iterations } => Box::new(MctsAgent::new(
+                MctsConfig {
+                    budget: Budget::Iterations(iterations),
+                    ..MctsConfig::default()
+                },
+                seed,
+            )),
+        }
+    }
+}
+impl FromStr for AgentKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "random" => Ok(AgentKind::Random),
+            None if s == "heuristic" => Ok(AgentKind::Heuristic),
+            None if s == "mcts" => Ok(AgentKind::Mcts { iterations: 1000 }),
+            Some(("mcts", iterations)) => Ok(AgentKind::Mcts {
+                iterations: iterations
+                    .parse()
+                    .with_context(|| format!("Parsing mcts iterations {:?}", iterations))?,
+            }),
+            _ => bail!("Unknown agent {:?}", s),
+        }
+    }
+}
+impl fmt::Display for AgentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentKind::Random => write!(f, "random"),
+            AgentKind::Heuristic => write!(f, "heuristic"),
+            AgentKind::Mcts { iterations } => write!(f, "mcts:{}", iterations),
+        }
+    }
+}
+
+/// What to play: `games` games between `agents`, the seats rotating every game so each
+/// agent goes first equally often.
+#[derive(Debug, Clone)]
+pub struct ArenaConfig {
+    pub agents: Vec<AgentKind>,
+    pub games: usize,
+    /// Game `i` is played on a board and with dice from `seed + i`.
+    pub seed: u64,
+    pub threads: usize,
+    /// Games that haven't been won after this many actions are drawn.
+    pub max_actions: usize,
+}
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        ArenaConfig {
+            agents: vec![AgentKind::Heuristic, AgentKind::Random, AgentKind::Random],
+            games: 100,
+            seed: 0,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            max_actions: 20_000,
+        }
+    }
+}
+
+/// How one game of a tournament went. `winner` is an index into `ArenaConfig::agents`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameResult {
+    pub seed: u64,
+    pub winner: Option<usize>,
+    pub actions: usize,
+    pub rounds: u32,
+}
+
+/// Plays game `index` of the tournament.
+pub fn play_one(config: &ArenaConfig, index: usize) -> Result<GameResult> {
+    let n = config.agents.len();
+    let seed = config.seed + index as u64;
+    // seat `s` is played by agent `(s + index) % n`
+    let slot = |seat: usize| (seat + index) % n;
+    let mut agents: Vec<Box<dyn Agent>> = (0..n)
+        .map(|seat| config.agents[slot(seat)].build(seed.wrapping_add(seat as u64)))
+        .collect();
+    let mut game = Game::with_random_board(Player::init_players(n), seed)?;
+    let winner = play_game(&mut game, &mut agents, config.max_actions)
+        .with_context(|| format!("Playing game {} with seed {}", index, seed))?;
+    Ok(GameResult {
+        seed,
+        winner: winner.map(slot),
+        actions: game.log().len(),
+        rounds: game.round(),
+    })
+}
+
+/// Plays every game of the tournament across `config.threads` threads.
+pub fn run(config: &ArenaConfig) -> Result<Report> {
+    if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&config.agents.len()) {
+        bail!(
+            "A tournament needs {} to {} agents, not {}",
+            MIN_PLAYERS,
+            MAX_PLAYERS,
+            config.agents.len()
+        );
+    }
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(config.games));
+    thread::scope(|scope| -> Result<()> {
+        let workers: Vec<_> = (0..config.threads.max(1))
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= config.games {
+                            return Ok(());
+                        }
+                        let result = play_one(config, index)?;
+                        results.lock().expect("no worker panicked").push(result);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().expect("arena workers don't panic")?;
+        }
+        Ok(())
+    })?;
+    let mut results = results.into_inner().expect("no worker panicked");
+    results.sort_by_key(|r| r.seed);
+    Ok(Report {
+        agents: config.agents.clone(),
+        results,
+    })
+}
+
+/// The 95% Wilson score interval for `wins` out of `games`.
+pub fn wilson_interval(wins: usize, games: usize) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    const Z: f64 = 1.96;
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let centre = p + Z * Z / (2.0 * n);
+    let spread = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt();
+    let scale = 1.0 + Z * Z / n;
+    (
+        ((centre - spread) / scale).max(0.0),
+        ((centre + spread) / scale).min(1.0),
+    )
+}
+
+/// The results of a tournament.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub agents: Vec<AgentKind>,
+    pub results: Vec<GameResult>,
+}
+impl Report {
+    pub fn wins(&self, agent: usize) -> usize {
+        self.results
+            .iter()
+            .filter(|r| r.winner == Some(agent))
+            .count()
+    }
+
+    pub fn draws(&self) -> usize {
+        self.results.iter().filter(|r| r.winner.is_none()).count()
+    }
+
+    pub fn win_rate(&self, agent: usize) -> f64 {
+        self.wins(agent) as f64 / self.results.len().max(1) as f64
+    }
+
+    pub fn confidence_interval(&self, agent: usize) -> (f64, f64) {
+        wilson_interval(self.wins(agent), self.results.len())
+    }
+
+    /// The mean number of rounds of the games that were won.
+    pub fn average_rounds(&self) -> f64 {
+        let won: Vec<&GameResult> = self.results.iter().filter(|r| r.winner.is_some()).collect();
+        won.iter().map(|r| r.rounds as f64).sum::<f64>() / won.len().max(1) as f64
+    }
+
+    pub fn average_actions(&self) -> f64 {
+        self.results.iter().map(|r| r.actions as f64).sum::<f64>()
+            / self.results.len().max(1) as f64
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} games, {} drawn, {:.1} rounds and {:.0} actions on average",
+            self.results.len(),
+            self.draws(),
+            self.average_rounds(),
+            self.average_actions()
+        )?;
+        for (i, agent) in self.agents.iter().enumerate() {
+            let (low, high) = self.confidence_interval(i);
+            writeln!(
+                f,
+                "{:>2} {:<12} {:>5} wins {:>6.1}%  95% CI {:.1}%-{:.1}%",
+                i,
+                agent.to_string(),
+                self.wins(i),
+                100.0 * self.win_rate(i),
+                100.0 * low,
+                100.0 * high
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_agents() {
+        assert_eq!("random".parse::<AgentKind>().unwrap(), AgentKind::Random);
+        assert_eq!(
+            "mcts:50".parse::<AgentKind>().unwrap(),
+            AgentKind::Mcts { iterations: 50 }
+        );
+        assert!("mcts:lots".parse::<AgentKind>().is_err());
+        assert!("alphazero".parse::<AgentKind>().is_err());
+        assert_eq!(AgentKind::Mcts { iterations: 50 }.to_string(), "mcts:50");
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        let (low, high) = wilson_interval(50, 100);
+        assert!((low - 0.404).abs() < 0.001, "{low}");
+        assert!((high - 0.596).abs() < 0.001, "{high}");
+        assert_eq!(wilson_interval(0, 10).0, 0.0);
+        assert_eq!(wilson_interval(0, 0), (0.0, 1.0));
+    }
+
+    #[test]
+    fn test_tournament() {
+        let config = ArenaConfig {
+            agents: vec![AgentKind::Heuristic; 3],
+            games: 4,
+            seed: 10,
+            threads: 2,
+            max_actions: 5_000,
+        };
+        let report = run(&config).unwrap();
+        assert!(run(&ArenaConfig {
+            agents: vec![AgentKind::Random; 7],
+            ..config.clone()
+        })
+        .is_err());
+        let seeds: Vec<u64> = report.results.iter().map(|r| r.seed).collect();
+        assert_eq!(seeds, vec![10, 11, 12, 13]);
+        assert_eq!(
+            report.wins(0) + report.wins(1) + report.wins(2) + report.draws(),
+            4
+        );
+        assert_eq!(report.results[1], play_one(&config, 1).unwrap());
+        assert!(report.average_rounds() > 0.0);
+    }
+}
@@ -1,7 +1,8 @@
-use std::{cmp::Ordering, ops::Add};
+use std::ops::Add;
 
 use bevy_math::{Vec2, Vec3};
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+use serde::{Deserialize, Serialize};
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Axial {
     q: i32,
     r: i32,
@@ -11,10 +12,18 @@ pub static SCALE_X: f32 = 60.;
 pub static SCALE_Y: f32 = 60.;
 
 impl Axial {
-    pub fn new(q: i32, r: i32) -> Self {
+    pub const fn new(q: i32, r: i32) -> Self {
         Axial { q, r }
     }
 
+    pub fn q(&self) -> i32 {
+        self.q
+    }
+
+    pub fn r(&self) -> i32 {
+        self.r
+    }
+
     pub fn to_cartesian(&self) -> Vec3 {
         let l = Vec2::new(
             f32::cos(std::f32::consts::PI / 6.0),
@@ -0,0 +1,49 @@
+//! Plays agents against each other and reports how they did, e.g.
+//!
+//! ```text
+//! catanrs-arena --games 200 --threads 8 mcts:500 heuristic heuristic random
+//! ```
+
+use anyhow::{bail, Context, Result};
+use catanrs::{
+    arena::{run, ArenaConfig},
+    game::{MAX_PLAYERS, MIN_PLAYERS},
+};
+
+const USAGE: &str = "usage: catanrs-arena [--games N] [--seed S] [--threads T] \
+                     [--max-actions M] AGENT AGENT AGENT...\n\
+                     agents: random, heuristic, mcts, mcts:<iterations>";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<ArenaConfig> {
+    let mut config = ArenaConfig {
+        agents: Vec::new(),
+        ..ArenaConfig::default()
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .with_context(|| format!("{} needs a value", name))
+        };
+        match arg.as_str() {
+            "--games" => config.games = value(&arg)?.parse().context("Parsing --games")?,
+            "--seed" => config.seed = value(&arg)?.parse().context("Parsing --seed")?,
+            "--threads" => config.threads = value(&arg)?.parse().context("Parsing --threads")?,
+            "--max-actions" => {
+                config.max_actions = value(&arg)?.parse().context("Parsing --max-actions")?
+            }
+            "-h" | "--help" => bail!(USAGE),
+            _ => config.agents.push(arg.parse()?),
+        }
+    }
+    if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&config.agents.len()) {
+        bail!("Need {} to {} agents\n{}", MIN_PLAYERS, MAX_PLAYERS, USAGE);
+    }
+    Ok(config)
+}
+
+fn main() -> Result<()> {
+    let config = parse_args(std::env::args().skip(1))?;
+    let report = run(&config)?;
+    print!("{}", report);
+    Ok(())
+}
@@ -1,14 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::{anyhow, Error, Result};
 use lazy_static::lazy_static;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     edge::PathType,
+    error::RuleError,
     harbor::Harbor,
     hex::Hex,
+    layout::{BASE_MAP, EXTENSION_MAP, NEW_SHORES_MAP},
     player::Player,
     resource::{Resource, ResourceGroup},
+    save::entries,
+    terrain::Terrain,
     vertex::BuildType,
 };
 
@@ -19,7 +24,7 @@ use super::{
 };
 
 lazy_static! {
-    static ref OFFSETS: [Axial; 6] = [
+    pub(crate) static ref OFFSETS: [Axial; 6] = [
         Axial::new(1, 0),
         Axial::new(0, 1),
         Axial::new(-1, 1),
@@ -28,58 +33,100 @@ lazy_static! {
         Axial::new(1, -1),
     ];
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Board {
+    #[serde(with = "entries")]
     pub hexes: HashMap<Axial, Hex>,
+    #[serde(with = "entries")]
     pub edges: HashMap<PathCoords, Edge>,
+    #[serde(with = "entries")]
     pub vertices: HashMap<Axial, Vertex>,
+    #[serde(with = "entries")]
     pub harbors: HashMap<PathCoords, Harbor>,
     pub robber: Axial,
+    /// The Seafarers pirate, always on a sea hex.
+    pub pirate: Option<Axial>,
+}
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Board {
     pub fn new() -> Self {
-        let mut hex_data: HashMap<Axial, (Resource, i32)> = HashMap::new();
-        hex_data.insert(Axial::new(4, -2), (Resource::Ore, 10));
-        hex_data.insert(Axial::new(3, 0), (Resource::Sheep, 2));
-        hex_data.insert(Axial::new(2, 2), (Resource::Wood, 9));
-
-        hex_data.insert(Axial::new(3, -3), (Resource::Wheat, 12));
-        hex_data.insert(Axial::new(2, -1), (Resource::Brick, 6));
-        hex_data.insert(Axial::new(1, 1), (Resource::Sheep, 4));
-        hex_data.insert(Axial::new(0, 3), (Resource::Brick, 10));
-
-        hex_data.insert(Axial::new(2, -4), (Resource::Wheat, 9));
-        hex_data.insert(Axial::new(1, -2), (Resource::Wood, 11));
-        hex_data.insert(Axial::new(0, 0), (Resource::None, 0));
-        hex_data.insert(Axial::new(-1, 2), (Resource::Wood, 3));
-        hex_data.insert(Axial::new(-2, 4), (Resource::Ore, 8));
-
-        hex_data.insert(Axial::new(0, -3), (Resource::Wood, 8));
-        hex_data.insert(Axial::new(-1, -1), (Resource::Ore, 3));
-        hex_data.insert(Axial::new(-2, 1), (Resource::Wheat, 4));
-        hex_data.insert(Axial::new(-3, 3), (Resource::Sheep, 5));
-
-        hex_data.insert(Axial::new(-2, -2), (Resource::Brick, 5));
-        hex_data.insert(Axial::new(-3, 0), (Resource::Wheat, 6));
-        hex_data.insert(Axial::new(-4, 2), (Resource::Sheep, 11));
-
-        let mut hexes: HashMap<Axial, Hex> = HashMap::new();
-        for (pos, ru) in hex_data.into_iter() {
+        BASE_MAP.parse().expect("the base map is valid")
+    }
+
+    /// The board of the 5-6 player extension.
+    pub fn extension() -> Self {
+        EXTENSION_MAP.parse().expect("the extension map is valid")
+    }
+
+    /// The Seafarers scenario board with a home island and two islands to discover.
+    pub fn new_shores() -> Self {
+        NEW_SHORES_MAP.parse().expect("the new shores map is valid")
+    }
+
+    /// A base game board with its terrain, numbers and harbors shuffled.
+    pub fn generate(rng: &mut impl Rng) -> Self {
+        Board::generate_from(&Board::new(), rng)
+    }
+
+    /// A board with the same hexes as `template` and its terrain, numbers and harbors
+    /// shuffled.
+    pub fn generate_from(template: &Board, rng: &mut impl Rng) -> Self {
+        let mut positions: Vec<Axial> = template.hexes.keys().cloned().collect();
+        positions.sort();
+        let mut tiles: Vec<Terrain> = template.hexes.values().map(|h| h.terrain).collect();
+        tiles.sort();
+        tiles.shuffle(rng);
+        let mut numbers: Vec<i32> = template
+            .hexes
+            .values()
+            .map(|h| h.number)
+            .filter(|&n| n != 0)
+            .collect();
+        numbers.sort();
+        numbers.shuffle(rng);
+
+        let mut hexes = HashMap::new();
+        for (pos, terrain) in positions.into_iter().zip(tiles) {
+            let number = if terrain.has_number() {
+                numbers.pop().unwrap_or(0)
+            } else {
+                0
+            };
             hexes.insert(
                 pos,
                 Hex {
                     pos,
-                    number: ru.1,
-                    resource_type: ru.0,
+                    number,
+                    terrain,
                 },
             );
         }
+        let mut board = Board::from_hexes(hexes);
+
+        let mut harbor_edges: Vec<PathCoords> = template.harbors.keys().cloned().collect();
+        harbor_edges.sort();
+        let mut harbors: Vec<Harbor> = harbor_edges.iter().map(|c| template.harbors[c]).collect();
+        harbors.shuffle(rng);
+        board.harbors = harbor_edges.into_iter().zip(harbors).collect();
+        board
+    }
+
+    /// Builds a board with a vertex on every corner of `hexes`, an edge between every pair
+    /// of adjacent vertices and the robber on the desert, or the first one when there are
+    /// several.
+    pub fn from_hexes(hexes: HashMap<Axial, Hex>) -> Self {
         let mut vertices: HashMap<Axial, Vertex> = HashMap::new();
-        let mut robber = Axial::new(0, 0);
+        let robber = hexes
+            .values()
+            .filter(|hex| hex.terrain == Terrain::Desert)
+            .map(|hex| hex.pos)
+            .min()
+            .unwrap_or(Axial::new(0, 0));
         for hex in hexes.values() {
-            if hex.number == 0 {
-                robber = hex.pos;
-            }
             for &offset in OFFSETS.iter() {
                 let a: Axial = hex.pos + offset;
                 vertices.insert(a, Vertex::new(a, BuildType::None));
@@ -99,14 +146,14 @@ impl Board {
             //
         }
 
-        let board = Board {
+        Board {
             hexes,
             edges,
             vertices,
             harbors: HashMap::new(),
             robber,
-        };
-        board
+            pirate: None,
+        }
     }
     pub fn get_adjacent_vertices(&self, a: Axial) -> Vec<&Vertex> {
         OFFSETS
@@ -123,7 +170,7 @@ impl Board {
         build_type: BuildType,
         //makes sure there is a road connecting
         ensure_connected: bool,
-    ) -> Result<()> {
+    ) -> Result<(), RuleError> {
         self.validate_build(build_type, player, pos, ensure_connected)?;
 
         if let Some(v) = self.vertices.get_mut(&pos) {
@@ -131,7 +178,7 @@ impl Board {
             v.owner = Some(player);
             return Ok(());
         }
-        return Err(anyhow!("Invalid Build"));
+        Err(RuleError::NoSuchVertex(pos))
     }
 
     fn validate_build(
@@ -140,13 +187,13 @@ impl Board {
         player: usize,
         pos: Axial,
         ensure_connected: bool,
-    ) -> Result<(), Error> {
+    ) -> Result<(), RuleError> {
         match build_type {
             BuildType::City => self.validate_city(player, pos),
             BuildType::Settlement => self.validate_settlement(player, pos, ensure_connected),
-            BuildType::None => todo!(),
-        }?;
-        Ok(())
+            BuildType::Knight { .. } => self.validate_knight(player, pos),
+            BuildType::None => Err(RuleError::NothingToBuild),
+        }
     }
     pub fn get_valid_build_spots(
         &self,
@@ -154,100 +201,267 @@ impl Board {
         player: usize,
         ensure_connected: bool,
     ) -> Vec<Axial> {
-        self.vertices
+        let mut spots: Vec<Axial> = self
+            .vertices
             .keys()
             .filter(|&v| {
                 self.validate_build(build_type, player, *v, ensure_connected)
                     .is_ok()
             })
             .cloned()
+            .collect();
+        spots.sort();
+        spots
+    }
+    pub fn get_valid_path_spots(
+        &self,
+        player: usize,
+        path_type: PathType,
+        ensure_connected: bool,
+    ) -> Vec<PathCoords> {
+        let mut spots: Vec<PathCoords> = self
+            .edges
+            .keys()
+            .filter(|&coords| {
+                self.validate_path(player, coords.clone(), path_type, ensure_connected)
+                    .is_ok()
+            })
+            .cloned()
+            .collect();
+        spots.sort();
+        spots
+    }
+    pub fn get_adjacent_hexes(&self, a: Axial) -> Vec<&Hex> {
+        OFFSETS
+            .iter()
+            .map(|&offset| a + offset)
+            .filter_map(|adjacent| self.hexes.get(&adjacent))
+            .collect()
+    }
+    /// The edges that end at vertex `a`.
+    pub fn get_adjacent_paths(&self, a: Axial) -> Vec<PathCoords> {
+        OFFSETS
+            .iter()
+            .map(|&offset| PathCoords::new(a, a + offset))
+            .filter(|coords| self.edges.contains_key(coords))
             .collect()
     }
 
+    /// The cards each player collects for `roll`. Gold hexes are left out since their
+    /// owners choose what they take; see `gold_for_roll`.
     pub fn yield_for_roll(&self, roll: i32) -> HashMap<usize, ResourceGroup> {
         let mut yields = HashMap::new();
         for hex in self.hexes.values() {
-            if hex.number == roll && self.robber != hex.pos {
-                for v in self.get_adjacent_vertices(hex.pos) {
-                    if let Some(o) = v.owner {
-                        let group = yields.entry(o).or_insert(ResourceGroup::empty());
-                        group.add_resource(hex.resource_type, 1);
-                    }
-                }
+            let Some(resource) = hex.terrain.resource() else {
+                continue;
+            };
+            for (owner, amount) in self.produced_by(hex, roll) {
+                let group = yields.entry(owner).or_insert(ResourceGroup::empty());
+                group.add_resource(resource, amount);
             }
         }
         yields
     }
 
+    /// How many resources of their choice each player takes from gold fields for `roll`.
+    pub fn gold_for_roll(&self, roll: i32) -> HashMap<usize, i32> {
+        let mut owed = HashMap::new();
+        for hex in self.hexes.values() {
+            if hex.terrain != Terrain::Gold {
+                continue;
+            }
+            for (owner, amount) in self.produced_by(hex, roll) {
+                *owed.entry(owner).or_insert(0) += amount;
+            }
+        }
+        owed
+    }
+
+    /// The owner and card count of each building `hex` pays for `roll`.
+    fn produced_by(&self, hex: &Hex, roll: i32) -> Vec<(usize, i32)> {
+        if hex.number != roll || self.robber == hex.pos {
+            return Vec::new();
+        }
+        self.get_adjacent_vertices(hex.pos)
+            .into_iter()
+            .filter(|v| v.build_type.is_building())
+            .filter_map(|v| {
+                let amount = if v.build_type == BuildType::City {
+                    2
+                } else {
+                    1
+                };
+                v.owner.map(|o| (o, amount))
+            })
+            .collect()
+    }
+
     pub fn place_path(
         &mut self,
         player: &Player,
         coords: PathCoords,
         path_type: PathType,
-    ) -> Result<()> {
-        self.validate_path(player, coords.clone())?;
+        //makes sure the path continues one of the player's roads or buildings
+        ensure_connected: bool,
+    ) -> Result<(), RuleError> {
+        self.validate_path(player.id, coords.clone(), path_type, ensure_connected)?;
         if let Some(v) = self.edges.get_mut(&coords) {
             v.path_type = path_type;
             v.owner = Some(player.id);
             return Ok(());
         }
-        Err(anyhow!("Invalid Build"))
+        Err(RuleError::NoSuchEdge(coords))
     }
 
-    fn validate_path(&self, player: &Player, coords: PathCoords) -> Result<()> {
-        if !self.is_valid_path_coords(&coords) {
-            return Err(anyhow!("Settlement spot is not valid"));
+    pub fn validate_path(
+        &self,
+        player: usize,
+        coords: PathCoords,
+        path_type: PathType,
+        ensure_connected: bool,
+    ) -> Result<(), RuleError> {
+        match self.edges.get(&coords) {
+            None => return Err(RuleError::NoSuchEdge(coords)),
+            Some(edge) if edge.owner.is_some() => return Err(RuleError::EdgeOccupied(coords)),
+            Some(_) => {}
+        }
+        if !self.can_carry(&coords, path_type) {
+            return Err(RuleError::UnsuitableEdge { coords, path_type });
+        }
+        if ensure_connected && !self.is_path_connected(player, &coords, path_type, None) {
+            return Err(RuleError::NotConnected);
         }
-        //TODO ensure connected
         Ok(())
     }
 
-    fn is_valid_path_coords(&self, coords: &PathCoords) -> bool {
-        if let Some(v) = self.edges.get(&coords) {
-            if v.owner.is_some() {
-                return false;
-            }
-            // for neighbour in self.get_adjacent_vertices(v.) {
-            //     if neighbour.owner.is_some() {
-            //         return false;
-            //     }
-            // }
-            true
-        } else {
-            false
-        }
+    /// Whether a new `path_type` on `coords` would continue one of `player`'s buildings or
+    /// paths of the same type, leaving out the path on `ignoring`. Roads and ships only
+    /// join up at the player's own buildings.
+    pub(crate) fn is_path_connected(
+        &self,
+        player: usize,
+        coords: &PathCoords,
+        path_type: PathType,
+        ignoring: Option<&PathCoords>,
+    ) -> bool {
+        coords
+            .vertices()
+            .into_iter()
+            .any(|end| match self.vertices.get(&end) {
+                Some(v) if v.owner == Some(player) => true,
+                // roads can't continue through another player's building
+                Some(v) if v.owner.is_some() => false,
+                _ => self.get_adjacent_paths(end).iter().any(|p| {
+                    p != coords
+                        && Some(p) != ignoring
+                        && self.edges[p].owner == Some(player)
+                        && self.edges[p].path_type == path_type
+                }),
+            })
     }
+
     pub fn validate_settlement(
         &self,
         player: usize,
         pos: Axial,
         ensure_connected: bool,
-    ) -> Result<()> {
+    ) -> Result<(), RuleError> {
         if let Some(v) = self.vertices.get(&pos) {
+            if !self.is_on_land(pos) {
+                return Err(RuleError::NotOnLand(pos));
+            }
             if v.owner.is_some() {
-                return Err(anyhow!("Vertex is already owned"));
+                return Err(RuleError::VertexOccupied(pos));
             }
             for neighbour in self.get_adjacent_vertices(v.pos) {
-                if neighbour.owner.is_some() {
-                    return Err(anyhow!("Neighbour is occupied {:?}", neighbour));
+                if neighbour.build_type.is_building() {
+                    return Err(RuleError::NeighbourOccupied(neighbour.pos));
                 }
             }
+            if ensure_connected
+                && !self
+                    .get_adjacent_paths(pos)
+                    .iter()
+                    .any(|p| self.edges[p].owner == Some(player))
+            {
+                return Err(RuleError::NotConnected);
+            }
             Ok(())
         } else {
-            return Err(anyhow!("Vertex does not exist"));
+            Err(RuleError::NoSuchVertex(pos))
         }
     }
-    pub fn validate_city(&self, player: usize, pos: Axial) -> Result<()> {
+    pub fn validate_city(&self, player: usize, pos: Axial) -> Result<(), RuleError> {
         if let Some(v) = self.vertices.get(&pos) {
-            if !v.owner.is_some_and(|owner| owner == player) {
-                Err(anyhow!("Vertex is not owned by player"))
+            if v.owner != Some(player) {
+                Err(RuleError::NotOwner(pos))
             } else if v.build_type != BuildType::Settlement {
-                Err(anyhow!("There is no settlement on the vertex"))
+                Err(RuleError::NoSettlement(pos))
             } else {
                 Ok(())
             }
         } else {
-            Err(anyhow!("Vertex does not exist"))
+            Err(RuleError::NoSuchVertex(pos))
+        }
+    }
+
+    /// The best rate `player` can trade `resource` to the bank at, using any harbor they
+    /// have built on.
+    pub fn trade_ratio(&self, player: usize, resource: Resource) -> i32 {
+        self.harbors
+            .iter()
+            .filter(|(_, h)| h.resource.is_none() || h.resource == Some(resource))
+            .filter(|(coords, _)| {
+                coords.vertices().iter().any(|v| {
+                    self.vertices
+                        .get(v)
+                        .is_some_and(|v| v.owner == Some(player) && v.build_type.is_building())
+                })
+            })
+            .map(|(_, h)| h.ratio())
+            .fold(4, i32::min)
+    }
+
+    /// The number of roads and ships in the longest continuous route owned by `player`,
+    /// which can't pass through another player's building. A route only switches between
+    /// roads and ships at one of the player's own buildings.
+    pub fn longest_road(&self, player: usize) -> usize {
+        let mut used = HashSet::new();
+        self.edges
+            .values()
+            .filter(|e| e.owner == Some(player))
+            .flat_map(|e| e.path_coords.vertices())
+            .map(|start| self.extend_road(player, start, None, &mut used))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn extend_road(
+        &self,
+        player: usize,
+        at: Axial,
+        arrived_by: Option<PathType>,
+        used: &mut HashSet<PathCoords>,
+    ) -> usize {
+        let owner = self.vertices.get(&at).and_then(|v| v.owner);
+        if owner.is_some_and(|o| o != player) && !used.is_empty() {
+            return 0;
+        }
+        let mut longest = 0;
+        for coords in self.get_adjacent_paths(at) {
+            let edge = &self.edges[&coords];
+            if used.contains(&coords) || edge.owner != Some(player) {
+                continue;
+            }
+            if arrived_by.is_some_and(|t| t != edge.path_type) && owner != Some(player) {
+                continue;
+            }
+            let [a, b] = coords.vertices();
+            let next = if a == at { b } else { a };
+            used.insert(coords.clone());
+            longest = longest.max(1 + self.extend_road(player, next, Some(edge.path_type), used));
+            used.remove(&coords);
         }
+        longest
     }
 }
@@ -0,0 +1,1358 @@
+//! The Cities & Knights expansion: commodities from cities, city improvements, knights,
+//! the barbarians and progress cards.
+//!
+//! Knights stand on vertices as `BuildType::Knight` and commodities are counted in
+//! `ResourceGroup`, so the rest of the engine handles them like any other piece or card.
+//! A knight chasing the robber hands over to the usual `TurnPhase::MoveThief`. Knights
+//! don't displace each other, and the progress cards not listed in `ProgressCard` are
+//! left out.
+
+use std::collections::{HashMap, HashSet};
+
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    axial::Axial,
+    board::Board,
+    edge::PathType,
+    error::RuleError,
+    game::Game,
+    phase::TurnPhase,
+    player::{Player, MAX_SETTLEMENTS},
+    resource::{Commodity, Resource, ResourceGroup},
+    rules::Rules,
+    terrain::Terrain,
+    vertex::BuildType,
+};
+
+/// Victory points needed to win a Cities & Knights game.
+pub const VICTORY_POINTS: u32 = 13;
+/// The highest level of a city improvement track.
+pub const MAX_IMPROVEMENT: u8 = 5;
+/// The level that first earns a metropolis, which can only be taken away at level 5.
+pub const METROPOLIS_LEVEL: u8 = 4;
+pub const METROPOLIS_POINTS: u32 = 2;
+pub const MAX_KNIGHT_LEVEL: u8 = 3;
+/// Each player has two knights of each level.
+pub const KNIGHTS_PER_LEVEL: usize = 2;
+/// Promoting a knight to mighty needs this level of politics.
+pub const MIGHTY_POLITICS_LEVEL: u8 = 3;
+/// Steps the barbarian ship sails before it lands.
+pub const BARBARIAN_DISTANCE: u8 = 7;
+/// Defender of Catan points there are to award.
+pub const DEFENDER_CARDS: u32 = 6;
+/// The bank's supply of each commodity.
+pub const COMMODITY_SUPPLY: i32 = 12;
+/// Commodities trade with the bank at this rate until the trading house is built.
+pub const COMMODITY_RATIO: i32 = 4;
+/// The level of the trade track that builds the trading house, after which commodities
+/// trade 2:1.
+pub const TRADING_HOUSE_LEVEL: u8 = 3;
+/// The rate for the resource named with a merchant fleet, for the rest of the turn.
+pub const MERCHANT_FLEET_RATIO: i32 = 2;
+
+/// The three city improvement tracks, each paid for with one commodity.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Track {
+    Science,
+    Trade,
+    Politics,
+}
+impl Track {
+    pub const ALL: [Track; 3] = [Track::Science, Track::Trade, Track::Politics];
+
+    pub fn commodity(self) -> Commodity {
+        match self {
+            Track::Science => Commodity::Paper,
+            Track::Trade => Commodity::Cloth,
+            Track::Politics => Commodity::Coin,
+        }
+    }
+
+    /// Raising a track to `level` costs that many of its commodity.
+    pub fn improvement_cost(self, level: u8) -> ResourceGroup {
+        ResourceGroup::of_commodity(self.commodity(), level as i32)
+    }
+}
+
+/// A face of the event die rolled alongside the number dice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    /// The barbarian ship sails one step closer.
+    Barbarians,
+    /// Players far enough along the track may draw one of its progress cards.
+    CityGate(Track),
+}
+const EVENT_DIE: [Event; 6] = [
+    Event::Barbarians,
+    Event::Barbarians,
+    Event::Barbarians,
+    Event::CityGate(Track::Science),
+    Event::CityGate(Track::Trade),
+    Event::CityGate(Track::Politics),
+];
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProgressCard {
+    /// Two wheat for each fields hex next to the player's buildings.
+    Irrigation,
+    /// Two ore for each mountains hex next to the player's buildings.
+    Mining,
+    /// A victory point, revealed as soon as it is drawn.
+    Printer,
+    /// Every other player gives up to two of the named resource.
+    ResourceMonopoly,
+    /// The named resource trades 2:1 with the bank for the rest of the turn.
+    MerchantFleet,
+    /// Takes two random cards from the player furthest ahead, if anyone is.
+    MasterMerchant,
+    /// Activates all of the player's knights for free.
+    Warlord,
+    /// Every player with more points gives the player two cards.
+    Wedding,
+    /// A victory point, revealed as soon as it is drawn.
+    Constitution,
+}
+impl ProgressCard {
+    pub fn track(self) -> Track {
+        match self {
+            ProgressCard::Irrigation | ProgressCard::Mining | ProgressCard::Printer => {
+                Track::Science
+            }
+            ProgressCard::ResourceMonopoly
+            | ProgressCard::MerchantFleet
+            | ProgressCard::MasterMerchant => Track::Trade,
+            ProgressCard::Warlord | ProgressCard::Wedding | ProgressCard::Constitution => {
+                Track::Politics
+            }
+        }
+    }
+
+    pub fn is_victory_point(self) -> bool {
+        matches!(self, ProgressCard::Printer | ProgressCard::Constitution)
+    }
+
+    /// Whether playing the card names a resource.
+    pub fn names_resource(self) -> bool {
+        matches!(
+            self,
+            ProgressCard::ResourceMonopoly | ProgressCard::MerchantFleet
+        )
+    }
+
+    /// The unshuffled deck for `track`.
+    fn deck(track: Track) -> Vec<ProgressCard> {
+        let counts: &[(ProgressCard, usize)] = match track {
+            Track::Science => &[
+                (ProgressCard::Irrigation, 2),
+                (ProgressCard::Mining, 2),
+                (ProgressCard::Printer, 1),
+            ],
+            Track::Trade => &[
+                (ProgressCard::ResourceMonopoly, 4),
+                (ProgressCard::MerchantFleet, 2),
+                (ProgressCard::MasterMerchant, 2),
+            ],
+            Track::Politics => &[
+                (ProgressCard::Warlord, 2),
+                (ProgressCard::Wedding, 2),
+                (ProgressCard::Constitution, 1),
+            ],
+        };
+        counts
+            .iter()
+            .flat_map(|&(card, count)| std::iter::repeat_n(card, count))
+            .collect()
+    }
+}
+
+/// Everything Cities & Knights adds to a game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CitiesKnights {
+    /// Each player's level on every track, in the order of `Track::ALL`.
+    pub improvements: Vec<[u8; 3]>,
+    /// The city holding the metropolis of each track, if anyone has earned it.
+    pub metropolises: [Option<Axial>; 3],
+    /// How far the barbarian ship has sailed towards Catan.
+    pub barbarians: u8,
+    /// The robber stays put until the barbarians have landed once.
+    pub barbarians_attacked: bool,
+    /// Face down progress cards of each track, drawn from the back.
+    pub progress_decks: [Vec<ProgressCard>; 3],
+    /// Each player's unplayed progress cards.
+    pub progress_cards: Vec<Vec<ProgressCard>>,
+    /// Points from revealed progress cards and from defending Catan.
+    pub progress_points: Vec<u32>,
+    /// Defender of Catan points not yet awarded.
+    pub defender_cards: u32,
+    pub last_event: Option<Event>,
+    /// The resource the player whose turn it is named with a merchant fleet.
+    pub merchant_fleet: Option<Resource>,
+}
+impl CitiesKnights {
+    pub fn new(players: usize, rng: &mut impl Rng) -> Self {
+        let progress_decks = Track::ALL.map(|track| {
+            let mut deck = ProgressCard::deck(track);
+            deck.shuffle(rng);
+            deck
+        });
+        CitiesKnights {
+            improvements: vec![[0; 3]; players],
+            metropolises: [None; 3],
+            barbarians: 0,
+            barbarians_attacked: false,
+            progress_decks,
+            progress_cards: vec![Vec::new(); players],
+            progress_points: vec![0; players],
+            defender_cards: DEFENDER_CARDS,
+            last_event: None,
+            merchant_fleet: None,
+        }
+    }
+
+    pub fn level(&self, player: usize, track: Track) -> u8 {
+        self.improvements[player][track as usize]
+    }
+}
+
+impl Board {
+    /// Checks that `player` can put a new knight on `pos`, an empty vertex on land next to
+    /// one of their roads.
+    pub fn validate_knight(&self, player: usize, pos: Axial) -> Result<(), RuleError> {
+        let Some(v) = self.vertices.get(&pos) else {
+            return Err(RuleError::NoSuchVertex(pos));
+        };
+        if !self.is_on_land(pos) {
+            return Err(RuleError::NotOnLand(pos));
+        }
+        if v.owner.is_some() {
+            return Err(RuleError::VertexOccupied(pos));
+        }
+        let connected = self
+            .get_adjacent_paths(pos)
+            .iter()
+            .any(|p| self.edges[p].owner == Some(player));
+        if !connected {
+            return Err(RuleError::NotConnected);
+        }
+        Ok(())
+    }
+
+    /// The level and whether it is active of every knight `player` has, by position.
+    pub fn knights(&self, player: usize) -> Vec<(Axial, u8, bool)> {
+        let mut knights: Vec<(Axial, u8, bool)> = self
+            .vertices
+            .values()
+            .filter(|v| v.owner == Some(player))
+            .filter_map(|v| match v.build_type {
+                BuildType::Knight { level, active } => Some((v.pos, level, active)),
+                _ => None,
+            })
+            .collect();
+        knights.sort();
+        knights
+    }
+
+    /// The empty vertices on land a knight of `player` on `from` can move to along their
+    /// roads, passing their own pieces but nobody else's.
+    pub fn knight_moves(&self, player: usize, from: Axial) -> Vec<Axial> {
+        let mut seen = HashSet::from([from]);
+        let mut frontier = vec![from];
+        let mut moves = Vec::new();
+        while let Some(pos) = frontier.pop() {
+            for coords in self.get_adjacent_paths(pos) {
+                let edge = &self.edges[&coords];
+                if edge.owner != Some(player) || edge.path_type != PathType::Road {
+                    continue;
+                }
+                let [a, b] = coords.vertices();
+                let next = if a == pos { b } else { a };
+                if !seen.insert(next) {
+                    continue;
+                }
+                match self.vertices[&next].owner {
+                    None if self.is_on_land(next) => {
+                        moves.push(next);
+                        frontier.push(next);
+                    }
+                    None => {}
+                    Some(owner) if owner == player => frontier.push(next),
+                    Some(_) => {}
+                }
+            }
+        }
+        moves.sort();
+        moves
+    }
+
+    /// What cities collect for `roll` on top of `yield_for_roll`: one of their two
+    /// resources from forest, pasture and mountains becomes a commodity instead.
+    pub fn commodity_yield(&self, roll: i32) -> HashMap<usize, ResourceGroup> {
+        let mut yields = HashMap::new();
+        for hex in self.hexes.values() {
+            let Some(resource) = hex.terrain.resource() else {
+                continue;
+            };
+            let Some(commodity) = Commodity::from_resource(resource) else {
+                continue;
+            };
+            if hex.number != roll || self.robber == hex.pos {
+                continue;
+            }
+            for v in self.get_adjacent_vertices(hex.pos) {
+                if let Some(o) = v.owner.filter(|_| v.build_type == BuildType::City) {
+                    let group: &mut ResourceGroup = yields.entry(o).or_default();
+                    *group +=
+                        ResourceGroup::of_commodity(commodity, 1) - ResourceGroup::of(resource, 1);
+                }
+            }
+        }
+        yields
+    }
+}
+
+impl Rules {
+    /// The default rules, played to `VICTORY_POINTS`.
+    pub fn cities_and_knights() -> Self {
+        Rules {
+            victory_points: VICTORY_POINTS,
+            ..Rules::default()
+        }
+    }
+}
+
+impl Game {
+    /// A Cities & Knights game with progress cards in place of development cards. Start
+    /// from `Rules::cities_and_knights` for the usual target.
+    pub fn with_cities_and_knights(
+        players: Vec<Player>,
+        board: Board,
+        rules: Rules,
+        seed: u64,
+    ) -> Result<Self, RuleError> {
+        let mut game = Game::with_rules(players, board, rules, seed)?;
+        let cities_knights = CitiesKnights::new(game.players.len(), &mut game.rng);
+        game.cities_knights = Some(cities_knights);
+        for commodity in Commodity::ALL {
+            game.bank[commodity] = COMMODITY_SUPPLY;
+        }
+        Ok(game)
+    }
+
+    pub fn cities_knights(&self) -> Option<&CitiesKnights> {
+        self.cities_knights.as_ref()
+    }
+
+    pub fn is_cities_and_knights(&self) -> bool {
+        self.cities_knights.is_some()
+    }
+
+    /// Total level of `player`'s active knights.
+    pub fn knight_strength(&self, player: usize) -> u32 {
+        self.board
+            .knights(player)
+            .iter()
+            .filter(|&&(_, _, active)| active)
+            .map(|&(_, level, _)| level as u32)
+            .sum()
+    }
+
+    /// The barbarians are as strong as the number of cities in Catan.
+    pub fn barbarian_strength(&self) -> u32 {
+        self.board
+            .vertices
+            .values()
+            .filter(|v| v.build_type == BuildType::City)
+            .count() as u32
+    }
+
+    /// Points from metropolises, progress cards and defending Catan.
+    pub(crate) fn cities_knights_points(&self, player: usize) -> u32 {
+        let Some(ck) = &self.cities_knights else {
+            return 0;
+        };
+        let metropolises = ck
+            .metropolises
+            .iter()
+            .flatten()
+            .filter(|pos| self.board.vertices[*pos].owner == Some(player))
+            .count() as u32;
+        metropolises * METROPOLIS_POINTS + ck.progress_points[player]
+    }
+
+    /// Until the barbarians first land a 7 only makes players discard.
+    pub(crate) fn robber_asleep(&self) -> bool {
+        self.cities_knights
+            .as_ref()
+            .is_some_and(|ck| !ck.barbarians_attacked)
+    }
+
+    fn require_cities_knights(&self) -> Result<&CitiesKnights, RuleError> {
+        self.cities_knights.as_ref().ok_or(RuleError::NotInGame)
+    }
+
+    /// The knight `player` has on `pos`.
+    fn own_knight(&self, player: usize, pos: Axial) -> Result<(u8, bool), RuleError> {
+        match self.board.vertices.get(&pos) {
+            Some(v) if v.owner == Some(player) => match v.build_type {
+                BuildType::Knight { level, active } => Ok((level, active)),
+                _ => Err(RuleError::NoKnight(pos)),
+            },
+            _ => Err(RuleError::NoKnight(pos)),
+        }
+    }
+
+    fn knights_at_level(&self, player: usize, level: u8) -> usize {
+        self.board
+            .knights(player)
+            .iter()
+            .filter(|&&(_, l, _)| l == level)
+            .count()
+    }
+
+    pub(crate) fn validate_build_knight(&self, player: usize, pos: Axial) -> Result<(), RuleError> {
+        self.require_cities_knights()?;
+        if self.knights_at_level(player, 1) >= KNIGHTS_PER_LEVEL {
+            return Err(RuleError::NoKnightsLeft { level: 1 });
+        }
+        let cost = BuildType::Knight {
+            level: 1,
+            active: false,
+        }
+        .cost();
+        if !self.players[player].resources.can_afford(&cost) {
+            return Err(RuleError::InsufficientResources);
+        }
+        self.board.validate_knight(player, pos)
+    }
+
+    pub(crate) fn validate_activate_knight(
+        &self,
+        player: usize,
+        pos: Axial,
+    ) -> Result<(), RuleError> {
+        self.require_cities_knights()?;
+        let (_, active) = self.own_knight(player, pos)?;
+        if active {
+            return Err(RuleError::KnightAlreadyActive(pos));
+        }
+        if self.players[player].resources.get(Resource::Wheat) < 1 {
+            return Err(RuleError::InsufficientResources);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_promote_knight(
+        &self,
+        player: usize,
+        pos: Axial,
+    ) -> Result<(), RuleError> {
+        let ck = self.require_cities_knights()?;
+        let (level, active) = self.own_knight(player, pos)?;
+        let mighty = level + 1 == MAX_KNIGHT_LEVEL;
+        if level >= MAX_KNIGHT_LEVEL
+            || (mighty && ck.level(player, Track::Politics) < MIGHTY_POLITICS_LEVEL)
+        {
+            return Err(RuleError::CantPromote(pos));
+        }
+        if self.knights_at_level(player, level + 1) >= KNIGHTS_PER_LEVEL {
+            return Err(RuleError::NoKnightsLeft { level: level + 1 });
+        }
+        let cost = BuildType::Knight { level, active }.cost();
+        if !self.players[player].resources.can_afford(&cost) {
+            return Err(RuleError::InsufficientResources);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_move_knight(
+        &self,
+        player: usize,
+        from: Axial,
+        to: Axial,
+    ) -> Result<(), RuleError> {
+        self.require_cities_knights()?;
+        let (_, active) = self.own_knight(player, from)?;
+        if !active {
+            return Err(RuleError::KnightInactive(from));
+        }
+        if !self.board.knight_moves(player, from).contains(&to) {
+            return Err(RuleError::KnightCantReach(to));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_chase_robber(&self, player: usize, pos: Axial) -> Result<(), RuleError> {
+        self.require_cities_knights()?;
+        let (_, active) = self.own_knight(player, pos)?;
+        if !active {
+            return Err(RuleError::KnightInactive(pos));
+        }
+        if self.robber_asleep() {
+            return Err(RuleError::RobberAsleep);
+        }
+        let beside_robber = self
+            .board
+            .get_adjacent_vertices(self.board.robber)
+            .iter()
+            .any(|v| v.pos == pos);
+        if !beside_robber {
+            return Err(RuleError::NotBesideRobber(pos));
+        }
+        Ok(())
+    }
+
+    /// How many of a commodity `player` gives the bank for one resource.
+    pub fn commodity_ratio(&self, player: usize) -> i32 {
+        match &self.cities_knights {
+            Some(ck) if ck.level(player, Track::Trade) >= TRADING_HOUSE_LEVEL => 2,
+            _ => COMMODITY_RATIO,
+        }
+    }
+
+    pub(crate) fn validate_commodity_trade(
+        &self,
+        player: usize,
+        give: Commodity,
+        get: Resource,
+    ) -> Result<(), RuleError> {
+        self.require_cities_knights()?;
+        if !self.rules.bank_trades {
+            return Err(RuleError::TradeNotAllowed);
+        }
+        if self.players[player].resources[give] < self.commodity_ratio(player) {
+            return Err(RuleError::InsufficientResources);
+        }
+        if self.bank.get(get) < 1 {
+            return Err(RuleError::BankEmpty(get));
+        }
+        Ok(())
+    }
+
+    /// How many of `resource` `player` gives the bank for one card: their harbor rate,
+    /// or better for the resource named with a merchant fleet.
+    pub fn trade_ratio(&self, player: usize, resource: Resource) -> i32 {
+        let ratio = self.board.trade_ratio(player, resource);
+        match &self.cities_knights {
+            Some(ck) if ck.merchant_fleet == Some(resource) => ratio.min(MERCHANT_FLEET_RATIO),
+            _ => ratio,
+        }
+    }
+
+    pub(crate) fn validate_improve_city(
+        &self,
+        player: usize,
+        track: Track,
+    ) -> Result<(), RuleError> {
+        let ck = self.require_cities_knights()?;
+        let has_city = self.players[player]
+            .buildings
+            .iter()
+            .any(|pos| self.board.vertices[pos].build_type == BuildType::City);
+        if !has_city {
+            return Err(RuleError::NoCity);
+        }
+        let level = ck.level(player, track);
+        if level >= MAX_IMPROVEMENT {
+            return Err(RuleError::TrackComplete(track));
+        }
+        let cost = track.improvement_cost(level + 1);
+        if !self.players[player].resources.can_afford(&cost) {
+            return Err(RuleError::InsufficientResources);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_progress_card(
+        &self,
+        player: usize,
+        card: ProgressCard,
+        resource: Option<Resource>,
+    ) -> Result<(), RuleError> {
+        let ck = self.require_cities_knights()?;
+        if !ck.progress_cards[player].contains(&card) {
+            return Err(RuleError::NoProgressCard(card));
+        }
+        if card.names_resource() != resource.is_some() {
+            return Err(RuleError::InvalidChoice);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn build_knight(&mut self, player: usize, pos: Axial) {
+        let knight = BuildType::Knight {
+            level: 1,
+            active: false,
+        };
+        self.pay(player, &knight.cost());
+        self.board
+            .place_building(player, pos, knight, true)
+            .expect("validated knight");
+    }
+
+    pub(crate) fn activate_knight(&mut self, player: usize, pos: Axial) {
+        self.pay(player, &ResourceGroup::of(Resource::Wheat, 1));
+        self.set_knight_active(pos, true);
+    }
+
+    pub(crate) fn promote_knight(&mut self, player: usize, pos: Axial) {
+        let vertex = self.board.vertices.get_mut(&pos).expect("validated knight");
+        let cost = vertex.build_type.cost();
+        if let BuildType::Knight { level, .. } = &mut vertex.build_type {
+            *level += 1;
+        }
+        self.pay(player, &cost);
+    }
+
+    /// Moves the knight on `from` to `to`, where it has to be activated again.
+    pub(crate) fn move_knight(&mut self, player: usize, from: Axial, to: Axial) {
+        let (level, _) = self.own_knight(player, from).expect("validated knight");
+        let vertex = self
+            .board
+            .vertices
+            .get_mut(&from)
+            .expect("validated knight");
+        vertex.build_type = BuildType::None;
+        vertex.owner = None;
+        let vertex = self.board.vertices.get_mut(&to).expect("validated move");
+        vertex.build_type = BuildType::Knight {
+            level,
+            active: false,
+        };
+        vertex.owner = Some(player);
+    }
+
+    /// The knight on `pos` chases the robber away, which the player then moves like
+    /// after a 7.
+    pub(crate) fn chase_robber(&mut self, pos: Axial) {
+        self.set_knight_active(pos, false);
+        self.set_turn_phase(TurnPhase::MoveThief);
+    }
+
+    pub(crate) fn commodity_trade(&mut self, player: usize, give: Commodity, get: Resource) {
+        let given = ResourceGroup::of_commodity(give, self.commodity_ratio(player));
+        let got = ResourceGroup::of(get, 1);
+        self.players[player].resources += got - given;
+        self.bank += given - got;
+    }
+
+    fn set_knight_active(&mut self, pos: Axial, to: bool) {
+        if let Some(vertex) = self.board.vertices.get_mut(&pos) {
+            if let BuildType::Knight { active, .. } = &mut vertex.build_type {
+                *active = to;
+            }
+        }
+    }
+
+    pub(crate) fn improve_city(&mut self, player: usize, track: Track) {
+        let Some(ck) = self.cities_knights.as_mut() else {
+            return;
+        };
+        let level = &mut ck.improvements[player][track as usize];
+        *level += 1;
+        let level = *level;
+        self.pay(player, &track.improvement_cost(level));
+        self.update_metropolis(player, track);
+    }
+
+    /// Gives `player` the metropolis of `track` if they are the first to reach
+    /// `METROPOLIS_LEVEL`, or the first to complete it while its holder hasn't. It goes on
+    /// one of their cities that isn't a metropolis already.
+    fn update_metropolis(&mut self, player: usize, track: Track) {
+        let Some(ck) = &self.cities_knights else {
+            return;
+        };
+        let level = ck.level(player, track);
+        let holder =
+            ck.metropolises[track as usize].and_then(|pos| self.board.vertices[&pos].owner);
+        let earned = match holder {
+            _ if level < METROPOLIS_LEVEL => false,
+            None => true,
+            Some(holder) => {
+                holder != player
+                    && level == MAX_IMPROVEMENT
+                    && ck.level(holder, track) < MAX_IMPROVEMENT
+            }
+        };
+        if !earned {
+            return;
+        }
+        let city = self.players[player].buildings.iter().copied().find(|pos| {
+            self.board.vertices[pos].build_type == BuildType::City
+                && !ck.metropolises.contains(&Some(*pos))
+        });
+        if let (Some(city), Some(ck)) = (city, self.cities_knights.as_mut()) {
+            ck.metropolises[track as usize] = Some(city);
+        }
+    }
+
+    /// Rolls the event die after the number dice and resolves it, `red` being the red
+    /// number die.
+    pub(crate) fn roll_event(&mut self, red: i32) {
+        if self.cities_knights.is_none() {
+            return;
+        }
+        let event = EVENT_DIE[self.rng.gen_range(0..EVENT_DIE.len())];
+        let active = self.phase.player();
+        let n = self.players.len();
+        let Some(ck) = self.cities_knights.as_mut() else {
+            return;
+        };
+        ck.last_event = Some(event);
+        match event {
+            Event::Barbarians => {
+                ck.barbarians += 1;
+                if ck.barbarians >= BARBARIAN_DISTANCE {
+                    self.barbarian_attack();
+                }
+            }
+            Event::CityGate(track) => {
+                for player in (0..n).map(|i| (active + i) % n) {
+                    let level = ck.level(player, track) as i32;
+                    if level > 0 && red <= level + 1 {
+                        Self::draw_progress_card(ck, player, track);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_progress_card(ck: &mut CitiesKnights, player: usize, track: Track) {
+        let Some(card) = ck.progress_decks[track as usize].pop() else {
+            return;
+        };
+        if card.is_victory_point() {
+            ck.progress_points[player] += 1;
+        } else {
+            ck.progress_cards[player].push(card);
+        }
+    }
+
+    /// The barbarians land. If the active knights are at least as strong, the strongest
+    /// defender earns a point while there are any left, or every tied defender draws a
+    /// progress card. Otherwise the weakest defenders with a city that isn't a metropolis
+    /// lose one, which becomes a settlement if they have one left. Every knight is
+    /// deactivated afterwards.
+    fn barbarian_attack(&mut self) {
+        let n = self.players.len();
+        let defence: Vec<u32> = (0..n).map(|p| self.knight_strength(p)).collect();
+        let attack = self.barbarian_strength();
+        let settlements: Vec<usize> = (0..n)
+            .map(|p| self.pieces(p, BuildType::Settlement))
+            .collect();
+        let Some(ck) = self.cities_knights.as_mut() else {
+            return;
+        };
+        if defence.iter().sum::<u32>() >= attack {
+            let best = defence.iter().copied().max().unwrap_or(0);
+            let defenders: Vec<usize> =
+                (0..n).filter(|&p| best > 0 && defence[p] == best).collect();
+            match defenders.as_slice() {
+                [defender] if ck.defender_cards > 0 => {
+                    ck.defender_cards -= 1;
+                    ck.progress_points[*defender] += 1;
+                }
+                [_] => {}
+                _ => {
+                    for &defender in defenders.iter() {
+                        if let Some(&track) = Track::ALL
+                            .iter()
+                            .find(|t| !ck.progress_decks[**t as usize].is_empty())
+                        {
+                            Self::draw_progress_card(ck, defender, track);
+                        }
+                    }
+                }
+            }
+        } else {
+            let plain_cities: Vec<Option<Axial>> = (0..n)
+                .map(|p| {
+                    self.players[p].buildings.iter().copied().find(|pos| {
+                        self.board.vertices[pos].build_type == BuildType::City
+                            && !ck.metropolises.contains(&Some(*pos))
+                    })
+                })
+                .collect();
+            let weakest = (0..n)
+                .filter(|&p| plain_cities[p].is_some())
+                .map(|p| defence[p])
+                .min();
+            for p in 0..n {
+                let Some(city) = plain_cities[p].filter(|_| Some(defence[p]) == weakest) else {
+                    continue;
+                };
+                let Some(v) = self.board.vertices.get_mut(&city) else {
+                    continue;
+                };
+                if settlements[p] < MAX_SETTLEMENTS {
+                    v.build_type = BuildType::Settlement;
+                } else {
+                    // with no settlement left to put in its place the city is lost
+                    v.build_type = BuildType::None;
+                    v.owner = None;
+                    self.players[p].buildings.retain(|&pos| pos != city);
+                }
+            }
+        }
+        ck.barbarians = 0;
+        ck.barbarians_attacked = true;
+        let knights: Vec<Axial> = (0..n)
+            .flat_map(|p| self.board.knights(p))
+            .map(|(pos, _, _)| pos)
+            .collect();
+        for pos in knights {
+            self.set_knight_active(pos, false);
+        }
+    }
+
+    pub(crate) fn play_progress_card(
+        &mut self,
+        player: usize,
+        card: ProgressCard,
+        resource: Option<Resource>,
+    ) {
+        let Some(ck) = self.cities_knights.as_mut() else {
+            return;
+        };
+        let cards = &mut ck.progress_cards[player];
+        if let Some(i) = cards.iter().position(|&c| c == card) {
+            cards.remove(i);
+        }
+        match card {
+            ProgressCard::Irrigation => self.harvest(player, Terrain::Fields),
+            ProgressCard::Mining => self.harvest(player, Terrain::Mountains),
+            ProgressCard::ResourceMonopoly => {
+                let resource = resource.expect("validated resource");
+                for other in (0..self.players.len()).filter(|&p| p != player) {
+                    let taken = ResourceGroup::of(
+                        resource,
+                        self.players[other].resources.get(resource).min(2),
+                    );
+                    self.players[other].resources -= taken;
+                    self.players[player].resources += taken;
+                }
+            }
+            ProgressCard::MerchantFleet => {
+                ck.merchant_fleet = resource;
+            }
+            ProgressCard::MasterMerchant => {
+                let points = self.public_victory_points(player);
+                let leader = (0..self.players.len())
+                    .filter(|&p| self.public_victory_points(p) > points)
+                    .max_by_key(|&p| (self.public_victory_points(p), std::cmp::Reverse(p)));
+                if let Some(leader) = leader {
+                    for _ in 0..2 {
+                        if self.players[leader].hand_size() > 0 {
+                            self.steal(player, leader);
+                        }
+                    }
+                }
+            }
+            ProgressCard::Warlord => {
+                for (pos, _, _) in self.board.knights(player) {
+                    self.set_knight_active(pos, true);
+                }
+            }
+            ProgressCard::Wedding => {
+                let points = self.public_victory_points(player);
+                for other in 0..self.players.len() {
+                    if self.public_victory_points(other) > points {
+                        for _ in 0..2 {
+                            if self.players[other].hand_size() > 0 {
+                                self.steal(player, other);
+                            }
+                        }
+                    }
+                }
+            }
+            ProgressCard::Printer | ProgressCard::Constitution => {}
+        }
+    }
+
+    /// Two of `terrain`'s resource from the bank for each such hex next to one of
+    /// `player`'s buildings.
+    fn harvest(&mut self, player: usize, terrain: Terrain) {
+        let Some(resource) = terrain.resource() else {
+            return;
+        };
+        let hexes = self
+            .board
+            .hexes
+            .values()
+            .filter(|hex| hex.terrain == terrain)
+            .filter(|hex| {
+                self.board
+                    .get_adjacent_vertices(hex.pos)
+                    .iter()
+                    .any(|v| v.owner == Some(player) && v.build_type.is_building())
+            })
+            .count() as i32;
+        let amount = (2 * hexes).min(self.bank.get(resource));
+        let got = ResourceGroup::of(resource, amount);
+        self.bank -= got;
+        self.players[player].resources += got;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        action::Action,
+        dice::Dice,
+        edge::PathCoords,
+        phase::{DevelopmentPhase, Phase},
+    };
+
+    use super::*;
+
+    /// Three players past initial placement, with player 0 free to act.
+    fn started_game() -> Game {
+        started_game_with(Rules::cities_and_knights())
+    }
+
+    fn started_game_with(rules: Rules) -> Game {
+        let mut game =
+            Game::with_cities_and_knights(Player::init_players(3), Board::new(), rules, 5).unwrap();
+        while !game.phase().is_turn() {
+            let action = game.legal_actions()[0].clone();
+            game.apply(action).unwrap();
+        }
+        game.phase = Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::Free,
+            development_phase: DevelopmentPhase::Ready,
+        };
+        game
+    }
+
+    fn upgrade_to_city(game: &mut Game, player: usize) -> Axial {
+        let pos = game.players[player].buildings[0];
+        game.board.vertices.get_mut(&pos).unwrap().build_type = BuildType::City;
+        pos
+    }
+
+    #[test]
+    fn test_rules_keep_their_own_target() {
+        let game = started_game();
+        assert_eq!(game.rules().victory_points, VICTORY_POINTS);
+
+        let rules = Rules {
+            victory_points: 8,
+            discard_limit: 9,
+            ..Rules::default()
+        };
+        let game =
+            Game::with_cities_and_knights(Player::init_players(3), Board::new(), rules.clone(), 5)
+                .unwrap();
+        assert_eq!(game.rules(), &rules);
+    }
+
+    #[test]
+    fn test_ten_point_game_is_won_at_ten() {
+        let mut game = started_game_with(Rules {
+            victory_points: 10,
+            ..Rules::default()
+        });
+        game.cities_knights.as_mut().unwrap().progress_points[0] = 7;
+        assert_eq!(game.victory_points(0), 9);
+        assert_eq!(game.winner(), None);
+
+        game.players[0].resources = ResourceGroup::new(3, 2, 0, 0, 0);
+        let settlement = game.players[0].buildings[0];
+        game.apply(Action::BuildCity(settlement)).unwrap();
+        assert_eq!(game.victory_points(0), 10);
+        assert_eq!(game.winner(), Some(0));
+    }
+
+    #[test]
+    fn test_cities_collect_commodities() {
+        let mut game = started_game();
+        let city = upgrade_to_city(&mut game, 0);
+        let forest = game
+            .board
+            .get_adjacent_hexes(city)
+            .into_iter()
+            .find(|hex| {
+                hex.terrain
+                    .resource()
+                    .and_then(Commodity::from_resource)
+                    .is_some()
+            })
+            .map(|hex| (hex.terrain, hex.number));
+        let Some((terrain, number)) = forest else {
+            panic!("seed 5 puts player 0's first settlement next to a commodity hex");
+        };
+        let resource = terrain.resource().unwrap();
+        let commodity = Commodity::from_resource(resource).unwrap();
+        let expected = game.board.yield_for_roll(number)[&0];
+        let before = game.players[0].resources;
+
+        game.give_resources_for_roll(number);
+
+        let got = game.players[0].resources - before;
+        assert_eq!(got[commodity], 1);
+        assert_eq!(got.get(resource), expected.get(resource) - 1);
+        assert_eq!(got.total(), expected.total());
+        assert_eq!(game.bank()[commodity], COMMODITY_SUPPLY - 1);
+    }
+
+    #[test]
+    fn test_knights_build_activate_and_promote() {
+        let mut game = started_game();
+        game.players[0].resources = ResourceGroup::new(3, 1, 3, 0, 0);
+        let build = game
+            .legal_actions()
+            .into_iter()
+            .find(|a| matches!(a, Action::BuildKnight(_)))
+            .expect("player 0 can afford a knight");
+        let Action::BuildKnight(pos) = build else {
+            unreachable!()
+        };
+        game.apply(build).unwrap();
+        assert_eq!(game.board().knights(0), vec![(pos, 1, false)]);
+        assert_eq!(game.knight_strength(0), 0);
+
+        game.apply(Action::ActivateKnight(pos)).unwrap();
+        assert_eq!(
+            game.apply(Action::ActivateKnight(pos)),
+            Err(RuleError::KnightAlreadyActive(pos))
+        );
+        game.apply(Action::PromoteKnight(pos)).unwrap();
+        assert_eq!(game.knight_strength(0), 2);
+        assert_eq!(
+            game.apply(Action::PromoteKnight(pos)),
+            Err(RuleError::CantPromote(pos))
+        );
+        assert_eq!(
+            game.players()[0].resources,
+            ResourceGroup::new(1, 0, 1, 0, 0)
+        );
+        assert_eq!(
+            game.board().validate_settlement(0, pos, false),
+            Err(RuleError::VertexOccupied(pos))
+        );
+    }
+
+    /// Puts an active knight of player 0 at the far end of the road from their first
+    /// settlement and gives them a second road from it. Returns the knight and the far
+    /// end of the new road.
+    fn knight_with_roads(game: &mut Game) -> (Axial, Axial) {
+        let settlement = game.players[0].buildings[0];
+        let far_end = |coords: &PathCoords| {
+            let [a, b] = coords.vertices();
+            if a == settlement {
+                b
+            } else {
+                a
+            }
+        };
+        let paths = game.board.get_adjacent_paths(settlement);
+        let road = paths
+            .iter()
+            .find(|c| game.board.edges[*c].owner == Some(0))
+            .unwrap();
+        let knight = far_end(road);
+        let new_road = paths
+            .iter()
+            .find(|c| game.board.edges[*c].owner.is_none() && game.board.is_on_land(far_end(c)))
+            .unwrap()
+            .clone();
+        let edge = game.board.edges.get_mut(&new_road).unwrap();
+        edge.owner = Some(0);
+        edge.path_type = PathType::Road;
+        game.players[0].paths.push(new_road.clone());
+        let active = BuildType::Knight {
+            level: 1,
+            active: true,
+        };
+        game.board.place_building(0, knight, active, true).unwrap();
+        (knight, far_end(&new_road))
+    }
+
+    #[test]
+    fn test_knights_move_along_roads() {
+        let mut game = started_game();
+        let (knight, to) = knight_with_roads(&mut game);
+        assert!(game.board().knight_moves(0, knight).contains(&to));
+        assert_eq!(
+            game.apply(Action::MoveKnight {
+                from: knight,
+                to: game.players[1].buildings[0],
+            }),
+            Err(RuleError::KnightCantReach(game.players[1].buildings[0]))
+        );
+
+        game.apply(Action::MoveKnight { from: knight, to }).unwrap();
+        assert_eq!(game.board().knights(0), vec![(to, 1, false)]);
+        assert_eq!(game.board().vertices[&knight].owner, None);
+        assert_eq!(
+            game.apply(Action::MoveKnight {
+                from: to,
+                to: knight
+            }),
+            Err(RuleError::KnightInactive(to))
+        );
+    }
+
+    #[test]
+    fn test_knights_chase_the_robber() {
+        let mut game = started_game();
+        let (knight, _) = knight_with_roads(&mut game);
+        let hex = game
+            .board
+            .get_adjacent_hexes(knight)
+            .into_iter()
+            .find(|hex| hex.terrain.is_land())
+            .unwrap()
+            .pos;
+        game.board.robber = hex;
+        assert_eq!(
+            game.apply(Action::ChaseRobber(knight)),
+            Err(RuleError::RobberAsleep)
+        );
+
+        game.cities_knights.as_mut().unwrap().barbarians_attacked = true;
+        game.apply(Action::ChaseRobber(knight)).unwrap();
+        assert!(game.phase().is_thief());
+        assert_eq!(game.knight_strength(0), 0);
+        let chase = game
+            .legal_actions()
+            .into_iter()
+            .find(|a| matches!(a, Action::MoveRobber { .. }))
+            .unwrap();
+        game.apply(chase).unwrap();
+        assert_ne!(game.board().robber, hex);
+        assert!(matches!(
+            game.phase(),
+            Phase::Turn {
+                turn_phase: TurnPhase::Free,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_commodity_trades() {
+        let mut game = started_game();
+        game.players[0].resources = ResourceGroup::of_commodity(Commodity::Coin, 6);
+        let trade = Action::CommodityTrade {
+            give: Commodity::Coin,
+            get: Resource::Wheat,
+        };
+        game.apply(trade.clone()).unwrap();
+        assert_eq!(
+            game.players()[0].resources,
+            ResourceGroup::of_commodity(Commodity::Coin, 2) + ResourceGroup::of(Resource::Wheat, 1)
+        );
+        assert_eq!(
+            game.apply(trade.clone()),
+            Err(RuleError::InsufficientResources)
+        );
+
+        game.cities_knights.as_mut().unwrap().improvements[0] = [0, TRADING_HOUSE_LEVEL, 0];
+        game.apply(trade).unwrap();
+        assert_eq!(game.players()[0].resources[Commodity::Coin], 0);
+    }
+
+    #[test]
+    fn test_improvements_earn_a_metropolis() {
+        let mut game = started_game();
+        game.bank += ResourceGroup::of_commodity(Commodity::Paper, 20);
+        game.players[0].resources = ResourceGroup::of_commodity(Commodity::Paper, 20);
+        let improve = Action::ImproveCity(Track::Science);
+        assert_eq!(game.apply(improve.clone()), Err(RuleError::NoCity));
+
+        let city = upgrade_to_city(&mut game, 0);
+        let points = game.public_victory_points(0);
+        for _ in 0..METROPOLIS_LEVEL {
+            game.apply(improve.clone()).unwrap();
+        }
+        let ck = game.cities_knights().unwrap();
+        assert_eq!(ck.level(0, Track::Science), METROPOLIS_LEVEL);
+        assert_eq!(ck.metropolises, [Some(city), None, None]);
+        assert_eq!(game.public_victory_points(0), points + METROPOLIS_POINTS);
+        // levels 1 to 4 cost 1 + 2 + 3 + 4
+        assert_eq!(game.players()[0].resources[Commodity::Paper], 10);
+
+        game.apply(improve.clone()).unwrap();
+        assert_eq!(
+            game.apply(improve),
+            Err(RuleError::TrackComplete(Track::Science))
+        );
+    }
+
+    #[test]
+    fn test_barbarians_take_a_city_from_the_weakest() {
+        let mut game = started_game();
+        upgrade_to_city(&mut game, 0);
+        let lost = upgrade_to_city(&mut game, 1);
+        let knight = game
+            .board
+            .get_valid_path_spots(0, PathType::Road, true)
+            .iter()
+            .flat_map(|coords| coords.vertices())
+            .find(|&v| game.board.validate_knight(0, v).is_ok())
+            .unwrap();
+        let active = BuildType::Knight {
+            level: 1,
+            active: true,
+        };
+        game.board.place_building(0, knight, active, true).unwrap();
+
+        game.barbarian_attack();
+
+        assert_eq!(
+            game.board().vertices[&lost].build_type,
+            BuildType::Settlement
+        );
+        assert_eq!(game.barbarian_strength(), 1);
+        assert_eq!(game.knight_strength(0), 0);
+        let ck = game.cities_knights().unwrap();
+        assert!(ck.barbarians_attacked);
+        assert_eq!(ck.barbarians, 0);
+
+        // one city against one knight is a win for the knight's owner
+        game.set_knight_active(knight, true);
+        game.barbarian_attack();
+        let ck = game.cities_knights().unwrap();
+        assert_eq!(ck.progress_points, [1, 0, 0]);
+        assert_eq!(ck.defender_cards, DEFENDER_CARDS - 1);
+    }
+
+    #[test]
+    fn test_pillaged_city_needs_a_settlement_piece() {
+        let mut game = started_game();
+        let lost = upgrade_to_city(&mut game, 1);
+        while game.pieces(1, BuildType::Settlement) < MAX_SETTLEMENTS {
+            let pos = game
+                .board
+                .get_valid_build_spots(BuildType::Settlement, 1, false)[0];
+            game.board
+                .place_building(1, pos, BuildType::Settlement, false)
+                .unwrap();
+            game.players[1].buildings.push(pos);
+        }
+
+        game.barbarian_attack();
+
+        assert_eq!(game.board().vertices[&lost].owner, None);
+        assert!(!game.players()[1].buildings.contains(&lost));
+        assert_eq!(game.pieces(1, BuildType::Settlement), MAX_SETTLEMENTS);
+    }
+
+    #[test]
+    fn test_robber_sleeps_until_the_barbarians_land() {
+        let mut game = started_game();
+        game.phase = Phase::START_TURNS;
+        game.set_dice(Dice::scripted([7]));
+        game.apply(Action::RollDice).unwrap();
+        assert_eq!(
+            game.phase(),
+            Phase::Turn {
+                player: 0,
+                turn_phase: TurnPhase::Free,
+                development_phase: DevelopmentPhase::Ready,
+            }
+        );
+        assert!(game.cities_knights().unwrap().last_event.is_some());
+    }
+
+    #[test]
+    fn test_progress_cards() {
+        let mut game = started_game();
+        game.players[0].resources = ResourceGroup::empty();
+        game.players[1].resources = ResourceGroup::of(Resource::Brick, 3);
+        game.players[2].resources = ResourceGroup::empty();
+        let ck = game.cities_knights.as_mut().unwrap();
+        ck.progress_cards[0] = vec![ProgressCard::ResourceMonopoly, ProgressCard::Warlord];
+        ck.progress_decks[Track::Science as usize].push(ProgressCard::Printer);
+        Game::draw_progress_card(ck, 0, Track::Science);
+        assert_eq!(ck.progress_points[0], 1);
+
+        assert_eq!(
+            game.apply(Action::PlayProgressCard {
+                card: ProgressCard::ResourceMonopoly,
+                resource: None,
+            }),
+            Err(RuleError::InvalidChoice)
+        );
+        game.apply(Action::PlayProgressCard {
+            card: ProgressCard::ResourceMonopoly,
+            resource: Some(Resource::Brick),
+        })
+        .unwrap();
+        assert_eq!(game.players()[0].resources.get(Resource::Brick), 2);
+        assert_eq!(
+            game.apply(Action::PlayProgressCard {
+                card: ProgressCard::Mining,
+                resource: None,
+            }),
+            Err(RuleError::NoProgressCard(ProgressCard::Mining))
+        );
+        assert_eq!(
+            game.apply(Action::BuyDevelopmentCard),
+            Err(RuleError::NotInGame)
+        );
+    }
+
+    #[test]
+    fn test_trade_progress_cards() {
+        let mut game = started_game();
+        game.players[0].resources = ResourceGroup::of(Resource::Sheep, 2);
+        game.players[1].resources = ResourceGroup::of(Resource::Brick, 3);
+        game.players[2].resources = ResourceGroup::empty();
+        game.cities_knights.as_mut().unwrap().progress_cards[0] =
+            vec![ProgressCard::MerchantFleet, ProgressCard::MasterMerchant];
+        let trade = Action::BankTrade {
+            give: Resource::Sheep,
+            get: Resource::Ore,
+        };
+        assert_eq!(
+            game.apply(trade.clone()),
+            Err(RuleError::InsufficientResources)
+        );
+
+        game.apply(Action::PlayProgressCard {
+            card: ProgressCard::MerchantFleet,
+            resource: Some(Resource::Sheep),
+        })
+        .unwrap();
+        game.apply(trade).unwrap();
+        assert_eq!(
+            game.players()[0].resources,
+            ResourceGroup::of(Resource::Ore, 1)
+        );
+
+        // player 1 is ahead once their first settlement is a city
+        upgrade_to_city(&mut game, 1);
+        game.apply(Action::PlayProgressCard {
+            card: ProgressCard::MasterMerchant,
+            resource: None,
+        })
+        .unwrap();
+        assert_eq!(game.players()[0].resources.get(Resource::Brick), 2);
+        assert_eq!(game.players()[1].resources.get(Resource::Brick), 1);
+
+        game.apply(Action::EndTurn).unwrap();
+        assert_eq!(game.cities_knights().unwrap().merchant_fleet, None);
+    }
+
+    #[test]
+    fn test_base_game_has_no_knights() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 5).unwrap();
+        game.phase = Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::Free,
+            development_phase: DevelopmentPhase::Ready,
+        };
+        assert_eq!(
+            game.apply(Action::ImproveCity(Track::Trade)),
+            Err(RuleError::NotInGame)
+        );
+        assert!(!game
+            .legal_actions()
+            .iter()
+            .any(|a| matches!(a, Action::BuildKnight(_) | Action::ImproveCity(_))));
+    }
+}
@@ -0,0 +1,613 @@
+use std::{
+    collections::HashMap,
+    ops::{BitAnd, BitOr, Not},
+    sync::Arc,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::{
+    axial::Axial,
+    board::{Board, OFFSETS},
+    edge::{Edge, PathCoords, PathType},
+    game,
+    harbor::Harbor,
+    hex::Hex,
+    resource::{Commodity, ResourceGroup},
+    vertex::{BuildType, Vertex},
+};
+
+/// The most players a compact board keeps pieces for, as in the 5-6 player extension.
+pub const MAX_PLAYERS: usize = game::MAX_PLAYERS;
+
+/// A set of vertex or edge indices of a `Topology`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Bitboard([u64; 4]);
+impl Bitboard {
+    /// The most vertices or edges a board can have, enough for the Seafarers boards.
+    pub const CAPACITY: usize = 256;
+
+    pub fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.0[i / 64] &= !(1 << (i % 64));
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    /// The indices in the set, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(w, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    w * 64 + bit
+                })
+            })
+        })
+    }
+}
+impl FromIterator<usize> for Bitboard {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut bits = Bitboard::default();
+        for i in iter {
+            bits.insert(i);
+        }
+        bits
+    }
+}
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+    fn bitor(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(std::array::from_fn(|i| self.0[i] | rhs.0[i]))
+    }
+}
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+    fn bitand(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(std::array::from_fn(|i| self.0[i] & rhs.0[i]))
+    }
+}
+impl Not for Bitboard {
+    type Output = Bitboard;
+    fn not(self) -> Bitboard {
+        Bitboard(self.0.map(|word| !word))
+    }
+}
+
+/// The parts of a board that never change during a game, with hexes, vertices and edges
+/// numbered in sorted order and their adjacency worked out once.
+#[derive(Debug)]
+pub struct Topology {
+    pub hexes: Vec<Hex>,
+    pub vertices: Vec<Axial>,
+    pub edges: Vec<PathCoords>,
+    pub harbors: Vec<(usize, Harbor)>,
+    /// The hexes at the corners of each vertex.
+    pub vertex_hexes: Vec<Vec<usize>>,
+    pub vertex_neighbours: Vec<Bitboard>,
+    pub vertex_edges: Vec<Bitboard>,
+    pub edge_vertices: Vec<[usize; 2]>,
+    /// The vertices at the corners of each hex.
+    pub hex_vertices: Vec<Bitboard>,
+    /// Vertices touching at least one land hex.
+    pub land: Bitboard,
+    hex_index: HashMap<Axial, usize>,
+    vertex_index: HashMap<Axial, usize>,
+    edge_index: HashMap<PathCoords, usize>,
+}
+impl Topology {
+    pub(crate) fn new(board: &Board) -> Result<Self> {
+        let mut hexes: Vec<Hex> = board.hexes.values().copied().collect();
+        hexes.sort_by_key(|h| h.pos);
+        let mut vertices: Vec<Axial> = board.vertices.keys().copied().collect();
+        vertices.sort();
+        let mut edges: Vec<PathCoords> = board.edges.keys().cloned().collect();
+        edges.sort();
+        ensure!(
+            vertices.len() <= Bitboard::CAPACITY && edges.len() <= Bitboard::CAPACITY,
+            "{} vertices and {} edges don't fit in a bitboard",
+            vertices.len(),
+            edges.len()
+        );
+
+        let hex_index: HashMap<Axial, usize> =
+            hexes.iter().enumerate().map(|(i, h)| (h.pos, i)).collect();
+        let vertex_index: HashMap<Axial, usize> =
+            vertices.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+        let edge_index: HashMap<PathCoords, usize> = edges
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.clone(), i))
+            .collect();
+        let around = |pos: Axial| OFFSETS.iter().map(move |&offset| pos + offset);
+
+        let vertex_hexes: Vec<Vec<usize>> = vertices
+            .iter()
+            .map(|&v| {
+                around(v)
+                    .filter_map(|h| hex_index.get(&h).copied())
+                    .collect()
+            })
+            .collect();
+        let vertex_neighbours = vertices
+            .iter()
+            .map(|&v| {
+                around(v)
+                    .filter_map(|w| vertex_index.get(&w).copied())
+                    .collect()
+            })
+            .collect();
+        let vertex_edges = vertices
+            .iter()
+            .map(|&v| {
+                around(v)
+                    .filter_map(|w| edge_index.get(&PathCoords::new(v, w)).copied())
+                    .collect()
+            })
+            .collect();
+        let edge_vertices = edges
+            .iter()
+            .map(|e| e.vertices().map(|v| vertex_index[&v]))
+            .collect();
+        let hex_vertices = hexes
+            .iter()
+            .map(|h| {
+                around(h.pos)
+                    .filter_map(|v| vertex_index.get(&v).copied())
+                    .collect()
+            })
+            .collect();
+        let land = vertex_hexes
+            .iter()
+            .enumerate()
+            .filter(|(_, hs)| hs.iter().any(|&h| hexes[h].terrain.is_land()))
+            .map(|(v, _)| v)
+            .collect();
+        let mut harbors = board
+            .harbors
+            .iter()
+            .map(|(coords, &harbor)| {
+                let e = edge_index
+                    .get(coords)
+                    .with_context(|| format!("Harbor on missing edge {:?}", coords))?;
+                Ok((*e, harbor))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        harbors.sort_by_key(|&(e, _)| e);
+
+        Ok(Topology {
+            hexes,
+            vertices,
+            edges,
+            harbors,
+            vertex_hexes,
+            vertex_neighbours,
+            vertex_edges,
+            edge_vertices,
+            hex_vertices,
+            land,
+            hex_index,
+            vertex_index,
+            edge_index,
+        })
+    }
+
+    pub fn hex(&self, pos: Axial) -> Option<usize> {
+        self.hex_index.get(&pos).copied()
+    }
+
+    pub fn vertex(&self, pos: Axial) -> Option<usize> {
+        self.vertex_index.get(&pos).copied()
+    }
+
+    pub fn edge(&self, coords: &PathCoords) -> Option<usize> {
+        self.edge_index.get(coords).copied()
+    }
+}
+
+/// One player's pieces on a `CompactBoard`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pieces {
+    pub settlements: Bitboard,
+    pub cities: Bitboard,
+    pub roads: Bitboard,
+    pub ships: Bitboard,
+    /// Cities & Knights knights of levels 1 to 3.
+    pub knights: [Bitboard; 3],
+    pub active_knights: Bitboard,
+}
+impl Pieces {
+    /// Settlements and cities.
+    pub fn buildings(&self) -> Bitboard {
+        self.settlements | self.cities
+    }
+
+    /// Every vertex the player holds, knights included.
+    pub fn vertices(&self) -> Bitboard {
+        self.knights
+            .iter()
+            .fold(self.buildings(), |all, &k| all | k)
+    }
+
+    pub fn paths(&self) -> Bitboard {
+        self.roads | self.ships
+    }
+}
+
+/// A board kept as bitboards of each player's pieces over a shared `Topology`, so cloning
+/// it copies a few hundred bytes and takes well under a microsecond. The rules engine
+/// works on `Board`, so games and their forks still clone one of those.
+#[derive(Debug, Clone)]
+pub struct CompactBoard {
+    topology: Arc<Topology>,
+    pub pieces: [Pieces; MAX_PLAYERS],
+    /// Index of the robber's hex.
+    pub robber: usize,
+    pub pirate: Option<usize>,
+}
+impl CompactBoard {
+    pub fn from_board(board: &Board) -> Result<Self> {
+        let topology = Topology::new(board)?;
+        let mut pieces = [Pieces::default(); MAX_PLAYERS];
+        for (i, pos) in topology.vertices.iter().enumerate() {
+            let vertex = &board.vertices[pos];
+            let Some(owner) = vertex.owner else {
+                continue;
+            };
+            let Some(p) = pieces.get_mut(owner) else {
+                bail!(
+                    "Player {} at {:?} is beyond {} players",
+                    owner,
+                    pos,
+                    MAX_PLAYERS
+                );
+            };
+            match vertex.build_type {
+                BuildType::Settlement => p.settlements.insert(i),
+                BuildType::City => p.cities.insert(i),
+                BuildType::Knight { level, active } => {
+                    ensure!(
+                        (1..=3).contains(&level),
+                        "Knight level {} at {:?}",
+                        level,
+                        pos
+                    );
+                    p.knights[level as usize - 1].insert(i);
+                    if active {
+                        p.active_knights.insert(i);
+                    }
+                }
+                BuildType::None => bail!("Empty vertex {:?} has an owner", pos),
+            }
+        }
+        for (i, coords) in topology.edges.iter().enumerate() {
+            let edge = &board.edges[coords];
+            let Some(owner) = edge.owner else {
+                continue;
+            };
+            let Some(p) = pieces.get_mut(owner) else {
+                bail!(
+                    "Player {} at {:?} is beyond {} players",
+                    owner,
+                    coords,
+                    MAX_PLAYERS
+                );
+            };
+            match edge.path_type {
+                PathType::Road => p.roads.insert(i),
+                PathType::Ship => p.ships.insert(i),
+                PathType::None => bail!("Empty edge {:?} has an owner", coords),
+            }
+        }
+        let robber = topology
+            .hex(board.robber)
+            .with_context(|| format!("Robber on missing hex {:?}", board.robber))?;
+        let pirate = match board.pirate {
+            Some(pos) => Some(
+                topology
+                    .hex(pos)
+                    .with_context(|| format!("Pirate on missing hex {:?}", pos))?,
+            ),
+            None => None,
+        };
+        Ok(CompactBoard {
+            topology: Arc::new(topology),
+            pieces,
+            robber,
+            pirate,
+        })
+    }
+
+    pub fn to_board(&self) -> Board {
+        let t = &self.topology;
+        let mut vertices: HashMap<Axial, Vertex> = t
+            .vertices
+            .iter()
+            .map(|&v| (v, Vertex::new(v, BuildType::None)))
+            .collect();
+        let mut edges: HashMap<PathCoords, Edge> = t
+            .edges
+            .iter()
+            .map(|e| (e.clone(), Edge::new(e.clone(), PathType::None)))
+            .collect();
+        for (player, p) in self.pieces.iter().enumerate() {
+            let mut place = |bits: Bitboard, build_type: BuildType| {
+                for i in bits.iter() {
+                    let vertex = vertices.get_mut(&t.vertices[i]).expect("indexed vertex");
+                    vertex.build_type = build_type;
+                    vertex.owner = Some(player);
+                }
+            };
+            place(p.settlements, BuildType::Settlement);
+            place(p.cities, BuildType::City);
+            for (level, &knights) in p.knights.iter().enumerate() {
+                for active in [false, true] {
+                    let bits = if active {
+                        knights & p.active_knights
+                    } else {
+                        knights & !p.active_knights
+                    };
+                    let level = level as u8 + 1;
+                    place(bits, BuildType::Knight { level, active });
+                }
+            }
+            for (bits, path_type) in [(p.roads, PathType::Road), (p.ships, PathType::Ship)] {
+                for i in bits.iter() {
+                    let edge = edges.get_mut(&t.edges[i]).expect("indexed edge");
+                    edge.path_type = path_type;
+                    edge.owner = Some(player);
+                }
+            }
+        }
+        Board {
+            hexes: t.hexes.iter().map(|h| (h.pos, *h)).collect(),
+            edges,
+            vertices,
+            harbors: t
+                .harbors
+                .iter()
+                .map(|&(e, harbor)| (t.edges[e].clone(), harbor))
+                .collect(),
+            robber: t.hexes[self.robber].pos,
+            pirate: self.pirate.map(|h| t.hexes[h].pos),
+        }
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    /// Every vertex with a piece on it.
+    pub fn occupied(&self) -> Bitboard {
+        self.pieces
+            .iter()
+            .fold(Bitboard::default(), |all, p| all | p.vertices())
+    }
+
+    pub fn owner(&self, vertex: usize) -> Option<usize> {
+        self.pieces
+            .iter()
+            .position(|p| p.vertices().contains(vertex))
+    }
+
+    /// Where `player` may settle: free land vertices with no building next to them, which
+    /// with `ensure_connected` also end one of the player's roads or ships.
+    pub fn settlement_spots(&self, player: usize, ensure_connected: bool) -> Bitboard {
+        let t = &self.topology;
+        let buildings = self
+            .pieces
+            .iter()
+            .fold(Bitboard::default(), |all, p| all | p.buildings());
+        let crowded: Bitboard = (0..t.vertices.len())
+            .filter(|&v| !(t.vertex_neighbours[v] & buildings).is_empty())
+            .collect();
+        let mut spots = t.land & !self.occupied() & !crowded;
+        if ensure_connected {
+            let paths = self.pieces[player].paths();
+            spots = (0..t.vertices.len())
+                .filter(|&v| !(t.vertex_edges[v] & paths).is_empty())
+                .collect::<Bitboard>()
+                & spots;
+        }
+        spots
+    }
+
+    /// The resources each player collects for `roll`, like `Board::yield_for_roll`.
+    pub fn yield_for_roll(&self, roll: i32) -> [ResourceGroup; MAX_PLAYERS] {
+        let t = &self.topology;
+        let mut yields = [ResourceGroup::empty(); MAX_PLAYERS];
+        for (h, hex) in t.hexes.iter().enumerate() {
+            let Some(resource) = hex.terrain.resource() else {
+                continue;
+            };
+            if hex.number != roll || h == self.robber {
+                continue;
+            }
+            for (player, p) in self.pieces.iter().enumerate() {
+                let count = (t.hex_vertices[h] & p.settlements).len()
+                    + 2 * (t.hex_vertices[h] & p.cities).len();
+                yields[player].add_resource(resource, count as i32);
+            }
+        }
+        yields
+    }
+
+    /// What cities collect for `roll` on top of `yield_for_roll` in Cities & Knights, like
+    /// `Board::commodity_yield`.
+    pub fn commodity_yield(&self, roll: i32) -> [ResourceGroup; MAX_PLAYERS] {
+        let t = &self.topology;
+        let mut yields = [ResourceGroup::empty(); MAX_PLAYERS];
+        for (h, hex) in t.hexes.iter().enumerate() {
+            let Some(resource) = hex.terrain.resource() else {
+                continue;
+            };
+            let Some(commodity) = Commodity::from_resource(resource) else {
+                continue;
+            };
+            if hex.number != roll || h == self.robber {
+                continue;
+            }
+            for (player, p) in self.pieces.iter().enumerate() {
+                let cities = (t.hex_vertices[h] & p.cities).len() as i32;
+                yields[player] += ResourceGroup::of_commodity(commodity, cities)
+                    - ResourceGroup::of(resource, cities);
+            }
+        }
+        yields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        hint::black_box,
+        time::{Duration, Instant},
+    };
+
+    use crate::{game::Game, player::Player};
+
+    use super::*;
+
+    /// The state of every vertex and edge, in sorted order.
+    fn pieces_of(board: &Board) -> (Vec<String>, Vec<String>) {
+        let mut vertices: Vec<String> = board
+            .vertices
+            .values()
+            .map(|v| format!("{:?} {:?} {:?}", v.pos, v.build_type, v.owner))
+            .collect();
+        vertices.sort();
+        let mut edges: Vec<String> = board
+            .edges
+            .values()
+            .map(|e| format!("{:?} {:?} {:?}", e.path_coords, e.path_type, e.owner))
+            .collect();
+        edges.sort();
+        (vertices, edges)
+    }
+
+    fn placed_board() -> Board {
+        let mut game = Game::with_seed(Player::init_players(4), Board::new(), 0).unwrap();
+        for _ in 0..16 {
+            let action = game.legal_actions()[0].clone();
+            game.apply(action).unwrap();
+        }
+        let mut board = game.board;
+        let city = board
+            .vertices
+            .values()
+            .find(|v| v.owner == Some(1))
+            .unwrap()
+            .pos;
+        board.vertices.get_mut(&city).unwrap().build_type = BuildType::City;
+        board
+    }
+
+    #[test]
+    fn test_bitboard() {
+        let mut bits: Bitboard = [0, 63, 64, 200].into_iter().collect();
+        assert_eq!(bits.len(), 4);
+        assert!(bits.contains(64));
+        bits.remove(64);
+        assert!(!bits.contains(64));
+        assert_eq!(bits.iter().collect::<Vec<_>>(), vec![0, 63, 200]);
+        assert_eq!((bits & !bits).len(), 0);
+        assert!(Bitboard::default().is_empty());
+    }
+
+    #[test]
+    fn test_clones_in_microseconds() {
+        const CLONES: u32 = 10_000;
+        let board = placed_board();
+        let compact = CompactBoard::from_board(&board).unwrap();
+        let time = |clone: &dyn Fn()| {
+            let start = Instant::now();
+            for _ in 0..CLONES {
+                clone();
+            }
+            start.elapsed() / CLONES
+        };
+        let compact_clone = time(&|| drop(black_box(compact.clone())));
+        let board_clone = time(&|| drop(black_box(board.clone())));
+        assert!(
+            compact_clone < Duration::from_micros(1),
+            "{compact_clone:?}"
+        );
+        assert!(
+            compact_clone * 10 < board_clone,
+            "{compact_clone:?} {board_clone:?}"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for board in [placed_board(), Board::extension(), Board::new_shores()] {
+            let compact = CompactBoard::from_board(&board).unwrap();
+            let back = compact.to_board();
+            assert_eq!(pieces_of(&back), pieces_of(&board));
+            assert_eq!(back.harbors, board.harbors);
+            assert_eq!(back.robber, board.robber);
+            assert_eq!(back.pirate, board.pirate);
+            assert_eq!(back.hexes.len(), board.hexes.len());
+        }
+    }
+
+    #[test]
+    fn test_queries_match_the_board() {
+        let board = placed_board();
+        let compact = CompactBoard::from_board(&board).unwrap();
+        let t = compact.topology();
+        for player in 0..4 {
+            for connected in [false, true] {
+                let spots: Vec<Axial> = compact
+                    .settlement_spots(player, connected)
+                    .iter()
+                    .map(|v| t.vertices[v])
+                    .collect();
+                assert_eq!(
+                    spots,
+                    board.get_valid_build_spots(BuildType::Settlement, player, connected)
+                );
+            }
+        }
+        for roll in 2..=12 {
+            let yields = board.yield_for_roll(roll);
+            let compact_yields = compact.yield_for_roll(roll);
+            for (player, group) in compact_yields.iter().enumerate() {
+                let expected = yields.get(&player).copied().unwrap_or_default();
+                assert_eq!(*group, expected, "roll {roll} player {player}");
+            }
+        }
+        let owner = board.vertices.values().find(|v| v.owner.is_some()).unwrap();
+        assert_eq!(compact.owner(t.vertex(owner.pos).unwrap()), owner.owner);
+
+        let mut board = board.clone();
+        for vertex in board.vertices.values_mut() {
+            if vertex.build_type == BuildType::Settlement {
+                vertex.build_type = BuildType::City;
+            }
+        }
+        let compact = CompactBoard::from_board(&board).unwrap();
+        for roll in 2..=12 {
+            let yields = board.commodity_yield(roll);
+            for (player, group) in compact.commodity_yield(roll).iter().enumerate() {
+                let expected = yields.get(&player).copied().unwrap_or_default();
+                assert_eq!(*group, expected, "roll {roll} player {player}");
+            }
+        }
+    }
+}
@@ -1 +1,82 @@
-pub struct Deck {}
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::resource::ResourceGroup;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DevelopmentCard {
+    Knight,
+    VictoryPoint,
+    RoadBuilding,
+    YearOfPlenty,
+    Monopoly,
+}
+impl DevelopmentCard {
+    pub fn cost() -> ResourceGroup {
+        ResourceGroup::new(1, 1, 1, 0, 0)
+    }
+}
+
+/// The face down pile of development cards, drawn from the back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deck {
+    cards: Vec<DevelopmentCard>,
+}
+impl Default for Deck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Deck {
+    /// The 25 cards of the base game, unshuffled.
+    pub fn new() -> Self {
+        Deck::with_counts([14, 5, 2, 2, 2])
+    }
+
+    /// The 34 cards of the 5-6 player extension, unshuffled.
+    pub fn extension() -> Self {
+        Deck::with_counts([20, 5, 3, 3, 3])
+    }
+
+    /// Knights, victory points, road building, year of plenty and monopoly cards.
+    fn with_counts([knights, points, roads, plenty, monopoly]: [usize; 5]) -> Self {
+        let counts = [
+            (DevelopmentCard::Knight, knights),
+            (DevelopmentCard::VictoryPoint, points),
+            (DevelopmentCard::RoadBuilding, roads),
+            (DevelopmentCard::YearOfPlenty, plenty),
+            (DevelopmentCard::Monopoly, monopoly),
+        ];
+        Deck {
+            cards: counts
+                .iter()
+                .flat_map(|&(card, count)| std::iter::repeat_n(card, count))
+                .collect(),
+        }
+    }
+
+    pub fn shuffle(&mut self, rng: &mut impl Rng) {
+        self.cards.shuffle(rng);
+    }
+
+    /// A deck drawn from the back of `cards`.
+    pub(crate) fn from_cards(cards: Vec<DevelopmentCard>) -> Self {
+        Deck { cards }
+    }
+
+    pub(crate) fn cards(&self) -> &[DevelopmentCard] {
+        &self.cards
+    }
+
+    pub fn draw(&mut self) -> Option<DevelopmentCard> {
+        self.cards.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// The rng a game draws every random outcome from: rolls, steals, shuffles and boards.
+pub type GameRng = ChaCha8Rng;
+
+/// Boards are generated from their own stream of the game's seed, so a generated board
+/// doesn't change the rolls and shuffles that follow it.
+const BOARD_STREAM: u64 = 1;
+
+/// The dice deck is shuffled again once this few cards are left, so the last rolls
+/// can't be counted.
+pub const DECK_RESHUFFLE_AT: usize = 5;
+
+/// How many of the 36 outcomes of two dice add up to `total`.
+pub fn outcomes(total: i32) -> i32 {
+    match total {
+        2..=12 => 6 - (7 - total).abs(),
+        _ => 0,
+    }
+}
+
+pub fn game_rng(seed: u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
+
+pub fn board_rng(seed: u64) -> GameRng {
+    let mut rng = GameRng::seed_from_u64(seed);
+    rng.set_stream(BOARD_STREAM);
+    rng
+}
+
+/// Where the rolls of a game come from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dice {
+    /// Two six sided dice thrown with the game's rng.
+    #[default]
+    Random,
+    /// Rolls handed out in order, then random ones once they run out. Games using scripted
+    /// rolls can't be replayed from their seed alone.
+    Scripted(VecDeque<i32>),
+    /// Cards for each of the 36 outcomes of two dice, drawn from the back and shuffled
+    /// with the game's rng, which evens out the rolls of a game.
+    Deck(Vec<(i32, i32)>),
+}
+impl Dice {
+    /// Panics unless every roll is a total two dice can show, from 2 to 12.
+    pub fn scripted(rolls: impl IntoIterator<Item = i32>) -> Self {
+        let rolls: VecDeque<i32> = rolls.into_iter().collect();
+        if let Some(roll) = rolls.iter().find(|r| !(2..=12).contains(*r)) {
+            panic!("two dice can't roll {}", roll);
+        }
+        Dice::Scripted(rolls)
+    }
+
+    /// A dice deck, shuffled when the first card is drawn.
+    pub fn deck() -> Self {
+        Dice::Deck(Vec::new())
+    }
+
+    pub fn roll(&mut self, rng: &mut impl Rng) -> i32 {
+        let (red, yellow) = self.roll_pair(rng);
+        red + yellow
+    }
+
+    /// Both dice, for rules such as Cities & Knights that look at the red one. A scripted
+    /// total is split as evenly as possible, with the red die getting the smaller half.
+    pub fn roll_pair(&mut self, rng: &mut impl Rng) -> (i32, i32) {
+        match self {
+            Dice::Scripted(rolls) if !rolls.is_empty() => {
+                let total = rolls.pop_front().expect("a roll is left");
+                (total / 2, total - total / 2)
+            }
+            Dice::Deck(cards) => {
+                if cards.len() <= DECK_RESHUFFLE_AT {
+                    *cards = (1..=6)
+                        .flat_map(|red| (1..=6).map(move |yellow| (red, yellow)))
+                        .collect();
+                    cards.shuffle(rng);
+                }
+                cards.pop().expect("a shuffled deck has cards")
+            }
+            _ => (rng.gen_range(1..=6), rng.gen_range(1..=6)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_rolls_then_random() {
+        let mut rng = game_rng(0);
+        let mut dice = Dice::scripted([8, 7]);
+        assert_eq!(dice.roll(&mut rng), 8);
+        assert_eq!(dice.roll(&mut rng), 7);
+        assert!((2..=12).contains(&dice.roll(&mut rng)));
+        assert_eq!(Dice::scripted([9]).roll_pair(&mut rng), (4, 5));
+        assert_eq!(Dice::scripted([2]).roll_pair(&mut rng), (1, 1));
+        assert_eq!(Dice::scripted([12]).roll_pair(&mut rng), (6, 6));
+    }
+
+    #[test]
+    #[should_panic(expected = "two dice can't roll 13")]
+    fn test_scripted_rolls_are_dice_totals() {
+        Dice::scripted([8, 13]);
+    }
+
+    #[test]
+    fn test_deck_deals_every_outcome() {
+        let mut rng = game_rng(0);
+        let mut dice = Dice::deck();
+        let mut counts = [0; 13];
+        for _ in 0..36 - DECK_RESHUFFLE_AT {
+            counts[dice.roll(&mut rng) as usize] += 1;
+        }
+        let Dice::Deck(left) = &dice else {
+            unreachable!()
+        };
+        for (red, yellow) in left.iter() {
+            counts[(red + yellow) as usize] += 1;
+        }
+        assert_eq!(counts, [0, 0, 1, 2, 3, 4, 5, 6, 5, 4, 3, 2, 1]);
+
+        let mut again = Dice::deck();
+        let mut rng = game_rng(0);
+        let rolls: Vec<i32> = (0..50).map(|_| again.roll(&mut rng)).collect();
+        let mut rng = game_rng(0);
+        let mut same = Dice::deck();
+        assert_eq!(
+            rolls,
+            (0..50).map(|_| same.roll(&mut rng)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_board_stream_is_independent() {
+        let a: u64 = game_rng(3).gen();
+        let b: u64 = board_rng(3).gen();
+        assert_ne!(a, b);
+        assert_eq!(board_rng(3).gen::<u64>(), b);
+    }
+}
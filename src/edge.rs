@@ -1,22 +1,32 @@
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-};
+use serde::{Deserialize, Serialize};
+
+use crate::resource::ResourceGroup;
 
 use super::axial::Axial;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Edge {
     pub path_coords: PathCoords,
     pub path_type: PathType,
     pub owner: Option<usize>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PathType {
     Road,
+    /// Seafarers ship, sailing along edges beside the sea.
+    Ship,
     None,
 }
+impl PathType {
+    pub fn cost(&self) -> ResourceGroup {
+        match self {
+            PathType::Road => ResourceGroup::new(0, 0, 0, 1, 1),
+            PathType::Ship => ResourceGroup::new(0, 0, 1, 0, 1),
+            PathType::None => ResourceGroup::empty(),
+        }
+    }
+}
 
 impl Edge {
     pub fn new(path_coords: PathCoords, path_type: PathType) -> Self {
@@ -28,7 +38,7 @@ impl Edge {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct PathCoords {
     a: Axial,
     b: Axial,
@@ -44,6 +54,9 @@ impl PathCoords {
     pub fn contains(&self, c: Axial) -> bool {
         self.a == c || self.b == c
     }
+    pub fn vertices(&self) -> [Axial; 2] {
+        [self.a, self.b]
+    }
 }
 #[cfg(test)]
 mod tests {
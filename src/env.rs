@@ -0,0 +1,604 @@
+use anyhow::ensure;
+
+use crate::{
+    action::Action,
+    agent::{next_action, Agent},
+    arena::AgentKind,
+    compact::Topology,
+    deck::DevelopmentCard,
+    dice::outcomes,
+    error::RuleError,
+    game::Game,
+    phase::{DevelopmentPhase, Phase, TurnPhase},
+    player::Player,
+    resource::{Resource, ResourceGroup},
+    vertex::BuildType,
+    view::PlayerView,
+};
+
+const TERRAINS: usize = 8;
+const DEVELOPMENT_CARDS: [DevelopmentCard; 5] = [
+    DevelopmentCard::Knight,
+    DevelopmentCard::VictoryPoint,
+    DevelopmentCard::RoadBuilding,
+    DevelopmentCard::YearOfPlenty,
+    DevelopmentCard::Monopoly,
+];
+/// Actions without arguments, in the order they are numbered.
+const SIMPLE_ACTIONS: [Action; 8] = [
+    Action::RollDice,
+    Action::EndTurn,
+    Action::BuyDevelopmentCard,
+    Action::PlayKnight,
+    Action::PlayRoadBuilding,
+    Action::PlayYearOfPlenty,
+    Action::AcceptTrade,
+    Action::RejectTrade,
+];
+
+/// How an `Env` sets up its games.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvConfig {
+    pub players: usize,
+    /// The seat the learning agent plays.
+    pub seat: usize,
+    /// Plays every other seat.
+    pub opponents: AgentKind,
+    /// Games that haven't been won after this many actions end without a winner.
+    pub max_actions: usize,
+}
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            players: 4,
+            seat: 0,
+            opponents: AgentKind::Random,
+            max_actions: 20_000,
+        }
+    }
+}
+
+/// What an action index stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Choice {
+    Act(Action),
+    /// One card of a discard, which is made once enough cards are picked.
+    DiscardOne(Resource),
+}
+
+/// A reinforcement learning environment in the style of OpenAI Gym, where one agent plays
+/// a seat of the base game against built in opponents.
+///
+/// Actions are numbered from 0 to `action_count`, with settlements, cities and roads on
+/// every vertex and edge of the board, robber moves onto every hex stealing from each
+/// seat, the actions without arguments, monopolies, year of plenty picks, single discarded
+/// cards and bank trades, in that order. Trade offers can't be made. `action_mask` tells
+/// which are legal. Discards are picked one card at a time so any hand fits.
+///
+/// Observations are `observation_len` numbers seen from the agent's seat, with seats
+/// numbered from the agent's: per hex its terrain, pips and the robber, per vertex every
+/// seat's settlements and cities, per edge every seat's roads and the harbor, then the
+/// agent's hand and development cards, the cards picked to discard, every seat's public
+/// counts, the bank, the deck, the phase, the acting seat and the last roll.
+pub struct Env {
+    config: EnvConfig,
+    topology: Topology,
+    game: Game,
+    opponents: Vec<Option<Box<dyn Agent>>>,
+    discard: ResourceGroup,
+    /// Set when the seat to act has no legal action, which ends the game without a winner.
+    stuck: bool,
+}
+impl Env {
+    pub fn new(config: EnvConfig) -> anyhow::Result<Self> {
+        ensure!(
+            config.seat < config.players,
+            "Seat {} is beyond {} players",
+            config.seat,
+            config.players
+        );
+        let game = Game::with_random_board(Player::init_players(config.players), 0)?;
+        let mut env = Env {
+            topology: Topology::new(game.board())?,
+            config,
+            game,
+            opponents: Vec::new(),
+            discard: ResourceGroup::empty(),
+            stuck: false,
+        };
+        env.reset(0)?;
+        Ok(env)
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Starts a new game on a board shuffled from `seed`, playing the opponents until it
+    /// is the agent's turn.
+    pub fn reset(&mut self, seed: u64) -> Result<Vec<f32>, RuleError> {
+        let players = self.config.players;
+        self.game = Game::with_random_board(Player::init_players(players), seed)?;
+        self.opponents = (0..players)
+            .map(|seat| {
+                (seat != self.config.seat)
+                    .then(|| self.config.opponents.build(seed.wrapping_add(seat as u64)))
+            })
+            .collect();
+        self.discard = ResourceGroup::empty();
+        self.stuck = false;
+        self.play_opponents()?;
+        Ok(self.observation())
+    }
+
+    /// Takes the action numbered `action` for the agent and plays the opponents until it
+    /// is the agent's turn again. The reward is 1 for winning, -1 for losing and 0 until
+    /// the game is done.
+    pub fn step(&mut self, action: usize) -> Result<(Vec<f32>, f32, bool), RuleError> {
+        if self.is_done() {
+            return Err(RuleError::GameOver);
+        }
+        match self
+            .decode(action)
+            .ok_or(RuleError::InvalidActionIndex(action))?
+        {
+            Choice::Act(action) => self.game.apply(action)?,
+            Choice::DiscardOne(resource) => {
+                if !self.is_discarding() {
+                    return Err(RuleError::WrongPhase);
+                }
+                let hand = self.game.players()[self.config.seat].resources;
+                if hand.get(resource) <= self.discard.get(resource) {
+                    return Err(RuleError::InsufficientResources);
+                }
+                self.discard.add_resource(resource, 1);
+                if self.discard.total() == hand.total() / 2 {
+                    let cards = std::mem::take(&mut self.discard);
+                    self.game.apply(Action::Discard(cards))?;
+                }
+            }
+        }
+        self.play_opponents()?;
+        let done = self.is_done();
+        let reward = match self.game.winner() {
+            Some(winner) if winner == self.config.seat => 1.0,
+            Some(_) => -1.0,
+            None => 0.0,
+        };
+        Ok((self.observation(), reward, done))
+    }
+
+    /// Whether the game has been won, has run out of actions or is stuck with no legal
+    /// action for the seat to act.
+    pub fn is_done(&self) -> bool {
+        self.stuck
+            || self.game.winner().is_some()
+            || self.game.log().len() >= self.config.max_actions
+    }
+
+    fn is_discarding(&self) -> bool {
+        matches!(
+            self.game.phase(),
+            Phase::Turn {
+                turn_phase: TurnPhase::Discard(player),
+                ..
+            } if player == self.config.seat
+        )
+    }
+
+    fn play_opponents(&mut self) -> Result<(), RuleError> {
+        while self.game.phase().player() != self.config.seat && !self.is_done() {
+            let player = self.game.phase().player();
+            let agent = self.opponents[player]
+                .as_deref_mut()
+                .expect("every other seat has an opponent");
+            let Some(action) = next_action(&self.game, agent) else {
+                self.stuck = true;
+                return Ok(());
+            };
+            self.game.apply(action)?;
+        }
+        self.stuck = !self.is_done() && !self.action_mask().contains(&true);
+        Ok(())
+    }
+
+    /// The number of actions, legal or not.
+    pub fn action_count(&self) -> usize {
+        let t = &self.topology;
+        2 * t.vertices.len()
+            + t.edges.len()
+            + t.hexes.len() * self.config.players
+            + SIMPLE_ACTIONS.len()
+            + 3 * Resource::ALL.len()
+            + Resource::ALL.len() * Resource::ALL.len()
+    }
+
+    /// Which actions the agent may take now.
+    pub fn action_mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; self.action_count()];
+        if self.is_done() {
+            return mask;
+        }
+        if self.is_discarding() {
+            let hand = self.game.players()[self.config.seat].resources;
+            for r in Resource::ALL {
+                mask[self.discard_index(r)] = hand.get(r) > self.discard.get(r);
+            }
+            return mask;
+        }
+        for action in self.game.legal_actions() {
+            if let Some(i) = self.encode(&action) {
+                mask[i] = true;
+            }
+        }
+        mask
+    }
+
+    /// The index of `action`, if it has one.
+    pub fn encode(&self, action: &Action) -> Option<usize> {
+        let t = &self.topology;
+        let players = self.config.players;
+        let vertices = t.vertices.len();
+        let edges = t.edges.len();
+        let robber = 2 * vertices + edges;
+        let simple = robber + t.hexes.len() * players;
+        let resource_actions = simple + SIMPLE_ACTIONS.len();
+        let trades = resource_actions + 3 * Resource::ALL.len();
+        match action {
+            Action::BuildSettlement(pos) => t.vertex(*pos),
+            Action::BuildCity(pos) => t.vertex(*pos).map(|v| vertices + v),
+            Action::BuildRoad(coords) => t.edge(coords).map(|e| 2 * vertices + e),
+            Action::MoveRobber { pos, victim } => {
+                let victim = victim.map_or(0, |v| self.relative(v));
+                t.hex(*pos).map(|h| robber + h * players + victim)
+            }
+            Action::PlayMonopoly(r) => Some(resource_actions + r.index()),
+            Action::TakeResource(r) => Some(resource_actions + Resource::ALL.len() + r.index()),
+            Action::BankTrade { give, get } => {
+                Some(trades + give.index() * Resource::ALL.len() + get.index())
+            }
+            _ => SIMPLE_ACTIONS
+                .iter()
+                .position(|a| a == action)
+                .map(|i| simple + i),
+        }
+    }
+
+    fn decode(&self, index: usize) -> Option<Choice> {
+        let t = &self.topology;
+        let players = self.config.players;
+        let resources = Resource::ALL.len();
+        let mut i = index;
+        if i < t.vertices.len() {
+            return Some(Choice::Act(Action::BuildSettlement(t.vertices[i])));
+        }
+        i -= t.vertices.len();
+        if i < t.vertices.len() {
+            return Some(Choice::Act(Action::BuildCity(t.vertices[i])));
+        }
+        i -= t.vertices.len();
+        if i < t.edges.len() {
+            return Some(Choice::Act(Action::BuildRoad(t.edges[i].clone())));
+        }
+        i -= t.edges.len();
+        if i < t.hexes.len() * players {
+            let victim = i % players;
+            return Some(Choice::Act(Action::MoveRobber {
+                pos: t.hexes[i / players].pos,
+                victim: (victim > 0).then(|| (self.config.seat + victim) % players),
+            }));
+        }
+        i -= t.hexes.len() * players;
+        if i < SIMPLE_ACTIONS.len() {
+            return Some(Choice::Act(SIMPLE_ACTIONS[i].clone()));
+        }
+        i -= SIMPLE_ACTIONS.len();
+        if i < 3 * resources {
+            let r = Resource::ALL[i % resources];
+            return Some(match i / resources {
+                0 => Choice::Act(Action::PlayMonopoly(r)),
+                1 => Choice::Act(Action::TakeResource(r)),
+                _ => Choice::DiscardOne(r),
+            });
+        }
+        i -= 3 * resources;
+        (i < resources * resources).then(|| {
+            Choice::Act(Action::BankTrade {
+                give: Resource::ALL[i / resources],
+                get: Resource::ALL[i % resources],
+            })
+        })
+    }
+
+    fn discard_index(&self, resource: Resource) -> usize {
+        let first = self
+            .encode(&Action::TakeResource(Resource::ALL[0]))
+            .unwrap();
+        first + Resource::ALL.len() + resource.index()
+    }
+
+    /// `player` counted from the agent's seat.
+    fn relative(&self, player: usize) -> usize {
+        (player + self.config.players - self.config.seat) % self.config.players
+    }
+
+    /// The length of every observation.
+    pub fn observation_len(&self) -> usize {
+        let t = &self.topology;
+        let players = self.config.players;
+        t.hexes.len() * (TERRAINS + 2)
+            + t.vertices.len() * 2 * players
+            + t.edges.len() * (players + 1 + Resource::ALL.len())
+            + 2 * Resource::ALL.len()
+            + 2 * DEVELOPMENT_CARDS.len()
+            + players * 7
+            + Resource::ALL.len()
+            + 1
+            + 15
+            + players
+            + 11
+    }
+
+    /// The game as the agent sees it.
+    pub fn observation(&self) -> Vec<f32> {
+        let t = &self.topology;
+        let view = self.game.view_for(self.config.seat);
+        let board = view.board;
+        let players = self.config.players;
+        let mut obs = Vec::with_capacity(self.observation_len());
+
+        for hex in &t.hexes {
+            let hex = &board.hexes[&hex.pos];
+            let mut terrain = [0.0; TERRAINS];
+            terrain[hex.terrain as usize] = 1.0;
+            obs.extend(terrain);
+            obs.push(outcomes(hex.number) as f32 / 5.0);
+            obs.push(flag(hex.pos == board.robber));
+        }
+        for pos in &t.vertices {
+            let vertex = &board.vertices[pos];
+            let mut pieces = vec![0.0; 2 * players];
+            if let Some(owner) = vertex.owner {
+                match vertex.build_type {
+                    BuildType::Settlement => pieces[2 * self.relative(owner)] = 1.0,
+                    BuildType::City => pieces[2 * self.relative(owner) + 1] = 1.0,
+                    _ => {}
+                }
+            }
+            obs.extend(pieces);
+        }
+        for coords in &t.edges {
+            let mut roads = vec![0.0; players];
+            if let Some(owner) = board.edges[coords].owner {
+                roads[self.relative(owner)] = 1.0;
+            }
+            obs.extend(roads);
+            let mut harbor = [0.0; 1 + Resource::ALL.len()];
+            if let Some(h) = board.harbors.get(coords) {
+                harbor[h.resource.map_or(0, |r| 1 + r.index())] = 1.0;
+            }
+            obs.extend(harbor);
+        }
+
+        let hand = view.hand.as_ref().expect("the agent sees its own hand");
+        obs.extend(resource_counts(&hand.resources));
+        for cards in [&hand.development_cards, &hand.new_development_cards] {
+            for kind in DEVELOPMENT_CARDS {
+                obs.push(cards.iter().filter(|&&c| c == kind).count() as f32);
+            }
+        }
+        obs.extend(resource_counts(&self.discard));
+
+        for seat in 0..players {
+            let player = view.public((self.config.seat + seat) % players);
+            obs.extend([
+                player.resource_cards as f32,
+                player.development_cards as f32,
+                player.knights_played as f32,
+                player.longest_road as f32,
+                player.victory_points as f32,
+                flag(view.player_with_road == Some(player.id as u32)),
+                flag(view.player_with_army == Some(player.id as u32)),
+            ]);
+        }
+        obs.extend(resource_counts(&view.bank));
+        obs.push(view.development_cards_left as f32);
+        obs.extend(phase_features(&view));
+
+        let mut acting = vec![0.0; players];
+        acting[self.relative(view.phase.player())] = 1.0;
+        obs.extend(acting);
+        let mut roll = [0.0; 11];
+        if let Some(r) = view.last_roll {
+            roll[r as usize - 2] = 1.0;
+        }
+        obs.extend(roll);
+        obs
+    }
+}
+
+fn flag(value: bool) -> f32 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn resource_counts(group: &ResourceGroup) -> [f32; 5] {
+    Resource::ALL.map(|r| group.get(r) as f32)
+}
+
+/// Flags for the stage of the game and of the turn, and for the card being played.
+fn phase_features(view: &PlayerView) -> [f32; 15] {
+    let mut features = [0.0; 15];
+    match view.phase {
+        Phase::InitialPlacement {
+            placing_second,
+            placing_road,
+            ..
+        } => {
+            features[0] = 1.0;
+            features[1] = flag(placing_second);
+            features[2] = flag(placing_road);
+        }
+        Phase::Turn {
+            turn_phase,
+            development_phase,
+            ..
+        } => {
+            let stage = match turn_phase {
+                TurnPhase::PreRoll => 3,
+                TurnPhase::Discard(_) => 4,
+                TurnPhase::MoveThief => 5,
+                TurnPhase::Free => 6,
+                TurnPhase::TradeOffer(_) => 7,
+                TurnPhase::SpecialBuild(_) => 8,
+                TurnPhase::GoldChoice { .. } => 9,
+            };
+            features[stage] = 1.0;
+            let card = match development_phase {
+                DevelopmentPhase::Ready => None,
+                DevelopmentPhase::KnightActive => Some(10),
+                DevelopmentPhase::RoadBuildingActive { .. } => Some(11),
+                DevelopmentPhase::YearOfPlentyActive { .. } => Some(12),
+                DevelopmentPhase::DevelopmentPlayed => Some(13),
+            };
+            if let Some(i) = card {
+                features[i] = 1.0;
+            }
+        }
+        Phase::FinishedGame { .. } => features[14] = 1.0,
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::SliceRandom, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn legal_indices(env: &Env) -> Vec<usize> {
+        env.action_mask()
+            .iter()
+            .enumerate()
+            .filter(|(_, &legal)| legal)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Plays random legal actions until the game is done, returning the last step.
+    fn play(env: &mut Env, seed: u64) -> (Vec<f32>, f32, bool) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut last = (env.reset(seed).unwrap(), 0.0, false);
+        while !last.2 {
+            let legal = legal_indices(env);
+            let action = *legal.choose(&mut rng).expect("a legal action until done");
+            last = env.step(action).unwrap();
+            assert_eq!(last.0.len(), env.observation_len());
+        }
+        last
+    }
+
+    #[test]
+    fn test_mask_matches_legal_actions() {
+        let env = Env::new(EnvConfig::default()).unwrap();
+        assert_eq!(env.observation().len(), env.observation_len());
+        let legal = env.game().legal_actions();
+        let mask = env.action_mask();
+        assert_eq!(mask.iter().filter(|&&m| m).count(), legal.len());
+        for action in &legal {
+            let i = env.encode(action).unwrap();
+            assert!(mask[i]);
+            assert_eq!(env.decode(i), Some(Choice::Act(action.clone())));
+        }
+        for i in 0..env.action_count() {
+            let choice = env.decode(i).unwrap();
+            if let Choice::Act(action) = choice {
+                assert_eq!(env.encode(&action), Some(i));
+            }
+        }
+        assert_eq!(env.decode(env.action_count()), None);
+    }
+
+    #[test]
+    fn test_invalid_action_index() {
+        let mut env = Env::new(EnvConfig::default()).unwrap();
+        let count = env.action_count();
+        assert_eq!(env.step(count), Err(RuleError::InvalidActionIndex(count)));
+    }
+
+    #[test]
+    fn test_stuck_opponent_ends_the_game() {
+        let mut env = Env::new(EnvConfig::default()).unwrap();
+        env.game.phase = Phase::Turn {
+            player: 1,
+            turn_phase: TurnPhase::GoldChoice { player: 1, left: 1 },
+            development_phase: DevelopmentPhase::Ready,
+        };
+        env.game.bank = ResourceGroup::empty();
+        assert!(env.game().legal_actions().is_empty());
+
+        env.play_opponents().unwrap();
+        assert!(env.is_done());
+        assert!(!env.action_mask().contains(&true));
+        assert_eq!(env.step(0), Err(RuleError::GameOver));
+
+        env.reset(0).unwrap();
+        assert!(!env.is_done());
+    }
+
+    #[test]
+    fn test_random_play_finishes() {
+        let config = EnvConfig {
+            players: 3,
+            seat: 1,
+            ..EnvConfig::default()
+        };
+        let mut env = Env::new(config).unwrap();
+        let (obs, reward, done) = play(&mut env, 3);
+        assert!(done);
+        let winner = env.game().winner();
+        assert!(winner.is_some());
+        assert_eq!(reward, if winner == Some(1) { 1.0 } else { -1.0 });
+        assert_eq!(env.step(0), Err(RuleError::GameOver));
+
+        // the same seed and choices replay the same game
+        let mut again = Env::new(env.config().clone()).unwrap();
+        assert_eq!(play(&mut again, 3).0, obs);
+    }
+
+    #[test]
+    fn test_discarding_one_card_at_a_time() {
+        let mut env = Env::new(EnvConfig::default()).unwrap();
+        env.game.phase = Phase::Turn {
+            player: 1,
+            turn_phase: TurnPhase::Discard(0),
+            development_phase: DevelopmentPhase::Ready,
+        };
+        env.game.players[0].resources = ResourceGroup::new(5, 0, 0, 0, 3);
+        let ore = env.discard_index(Resource::Ore);
+        let wood = env.discard_index(Resource::Wood);
+        let legal = legal_indices(&env);
+        assert_eq!(legal, vec![ore, wood]);
+
+        for _ in 0..3 {
+            env.step(wood).unwrap();
+        }
+        assert_eq!(legal_indices(&env), vec![ore]);
+        assert_eq!(
+            env.step(wood).unwrap_err(),
+            RuleError::InsufficientResources
+        );
+        env.step(ore).unwrap();
+        let discarded = Action::Discard(ResourceGroup::new(1, 0, 0, 0, 3));
+        assert!(env.game().log().contains(&discarded));
+        assert!(env.discard.is_empty());
+    }
+}
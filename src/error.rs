@@ -0,0 +1,127 @@
+use thiserror::Error;
+
+use crate::{
+    action::Action,
+    axial::Axial,
+    cities_knights::{ProgressCard, Track},
+    deck::DevelopmentCard,
+    edge::{PathCoords, PathType},
+    resource::Resource,
+};
+
+/// Why an action or placement was refused. Variants carry the data a UI needs to
+/// describe the problem in its own words, and bots can match on them.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RuleError {
+    #[error("the game is over")]
+    GameOver,
+    #[error("a game is for 3 to 6 players, not {0}")]
+    PlayerCount(usize),
+    #[error("the board is too small for {0} players")]
+    BoardTooSmall(usize),
+    #[error("the action isn't part of this game's rules")]
+    NotInGame,
+    #[error("there is no action numbered {0}")]
+    InvalidActionIndex(usize),
+    #[error("it is player {current}'s turn")]
+    NotYourTurn { current: usize },
+    #[error("the action isn't allowed in this phase")]
+    WrongPhase,
+    #[error("the action isn't allowed until the dice are rolled and no card is active")]
+    TurnNotFree,
+    #[error("a development card was already played this turn")]
+    CardAlreadyPlayed,
+    #[error("there is no playable {0:?} card")]
+    NoPlayableCard(DevelopmentCard),
+    #[error("there is no vertex at {0:?}")]
+    NoSuchVertex(Axial),
+    #[error("there is no edge at {0:?}")]
+    NoSuchEdge(PathCoords),
+    #[error("vertex {0:?} is already occupied")]
+    VertexOccupied(Axial),
+    #[error("the neighbouring vertex {0:?} is occupied")]
+    NeighbourOccupied(Axial),
+    #[error("edge {0:?} is already occupied")]
+    EdgeOccupied(PathCoords),
+    #[error("a {path_type:?} can't go on edge {coords:?}")]
+    UnsuitableEdge {
+        coords: PathCoords,
+        path_type: PathType,
+    },
+    #[error("vertex {0:?} isn't on land")]
+    NotOnLand(Axial),
+    #[error("the piece isn't connected to the player's roads")]
+    NotConnected,
+    #[error("the road has to continue the settlement just placed")]
+    NotAtNewSettlement,
+    #[error("vertex {0:?} isn't owned by the player")]
+    NotOwner(Axial),
+    #[error("there is no settlement at {0:?}")]
+    NoSettlement(Axial),
+    #[error("there is nothing to build")]
+    NothingToBuild,
+    #[error("no roads left")]
+    NoRoadsLeft,
+    #[error("no settlements left")]
+    NoSettlementsLeft,
+    #[error("no cities left")]
+    NoCitiesLeft,
+    #[error("no ships left")]
+    NoShipsLeft,
+    #[error("a ship was already moved this turn")]
+    ShipAlreadyMoved,
+    #[error("the ship on {0:?} can't be moved")]
+    ShipNotMovable(PathCoords),
+    #[error("no level {level} knights left")]
+    NoKnightsLeft { level: u8 },
+    #[error("the player has no knight at {0:?}")]
+    NoKnight(Axial),
+    #[error("the knight at {0:?} is already active")]
+    KnightAlreadyActive(Axial),
+    #[error("the knight at {0:?} can't be promoted")]
+    CantPromote(Axial),
+    #[error("the knight at {0:?} isn't active")]
+    KnightInactive(Axial),
+    #[error("no knight can move to {0:?}")]
+    KnightCantReach(Axial),
+    #[error("the knight at {0:?} isn't beside the robber")]
+    NotBesideRobber(Axial),
+    #[error("the robber stays put until the barbarians first land")]
+    RobberAsleep,
+    #[error("city improvements need a city")]
+    NoCity,
+    #[error("the {0:?} track is already complete")]
+    TrackComplete(Track),
+    #[error("there is no {0:?} progress card to play")]
+    NoProgressCard(ProgressCard),
+    #[error("a resource has to be named for exactly the cards that use one")]
+    InvalidChoice,
+    #[error("no development cards left")]
+    DeckEmpty,
+    #[error("not enough resources")]
+    InsufficientResources,
+    #[error("the bank has no {0:?}")]
+    BankEmpty(Resource),
+    #[error("must discard {expected} cards")]
+    WrongDiscard { expected: i32 },
+    #[error("the robber has to move to another land hex")]
+    InvalidRobberHex,
+    #[error("the pirate has to move to another sea hex")]
+    InvalidPirateHex,
+    #[error("must steal from one of {victims:?}")]
+    InvalidVictim { victims: Vec<usize> },
+    #[error("can't trade {give:?} for {get:?}")]
+    InvalidBankTrade { give: Resource, get: Resource },
+    #[error("can't offer a trade to player {0}")]
+    InvalidTradePartner(usize),
+    #[error("the house rules don't allow this trade")]
+    TradeNotAllowed,
+    #[error("trades must give and get a positive number of cards")]
+    InvalidTrade,
+    #[error("nothing to undo")]
+    NothingToUndo,
+    #[error("can't undo past {0:?}")]
+    CantUndo(Action),
+    #[error("nothing to redo")]
+    NothingToRedo,
+}
@@ -1,45 +1,345 @@
-use anyhow::Result;
-use rand::Rng;
+use std::{collections::HashMap, ops::IndexMut};
 
-use crate::{board::Board, phase::Phase, player::Player, resource::ResourceGroup};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use crate::{
+    action::Action,
+    board::Board,
+    cities_knights::CitiesKnights,
+    deck::Deck,
+    dice::{board_rng, game_rng, Dice, GameRng},
+    error::RuleError,
+    phase::Phase,
+    player::Player,
+    resource::{Commodity, Resource, ResourceGroup},
+    rules::Rules,
+    seafarers::ShipTurn,
+    undo::Snapshot,
+    vertex::BuildType,
+};
+
+/// Victory points needed to win the standard game, see `Rules::victory_points`.
+pub const VICTORY_POINTS: u32 = 10;
+
+/// The most players the base game is for. Larger games use the 5-6 player extension, with
+/// its bigger board, bank and deck and a special building phase between turns.
+pub const BASE_MAX_PLAYERS: usize = 4;
+
+/// The fewest and the most players a game is for.
+pub const MIN_PLAYERS: usize = 3;
+pub const MAX_PLAYERS: usize = 6;
+
+/// The land hexes of `Board::extension`, the smallest board 5 or 6 players fit on.
+const EXTENSION_LAND_HEXES: usize = 30;
+
+/// The cards each player collects when `roll` is rolled with `bank` left. When the bank
+/// can't cover every player owed a resource nobody receives it, unless only one player is
+/// owed it. In Cities & Knights cities take commodities the same way.
+pub(crate) fn roll_payouts(
+    board: &Board,
+    bank: &ResourceGroup,
+    cities_and_knights: bool,
+    roll: i32,
+) -> HashMap<usize, ResourceGroup> {
+    let mut payouts = HashMap::new();
+    if roll == 7 {
+        return payouts;
+    }
+    let mut player_yields = board.yield_for_roll(roll);
+    if cities_and_knights {
+        for (player, change) in board.commodity_yield(roll) {
+            *player_yields.entry(player).or_default() += change;
+        }
+    }
+    for resource in Resource::ALL {
+        pay_out(&player_yields, bank, &mut payouts, resource);
+    }
+    for commodity in Commodity::ALL {
+        pay_out(&player_yields, bank, &mut payouts, commodity);
+    }
+    payouts
+}
+
+fn pay_out<K: Copy>(
+    player_yields: &HashMap<usize, ResourceGroup>,
+    bank: &ResourceGroup,
+    payouts: &mut HashMap<usize, ResourceGroup>,
+    kind: K,
+) where
+    ResourceGroup: IndexMut<K, Output = i32>,
+{
+    let owed: i32 = player_yields.values().map(|res| res[kind]).sum();
+    let available = bank[kind];
+    let receivers = player_yields.values().filter(|res| res[kind] > 0).count();
+    if owed > available && receivers > 1 {
+        return;
+    }
+    for (&index, res) in player_yields.iter() {
+        payouts.entry(index).or_default()[kind] += res[kind].min(available);
+    }
+}
+
+/// A trade offered by the player whose turn it is, waiting on the other player.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeOffer {
+    pub from: usize,
+    pub to: usize,
+    pub give: ResourceGroup,
+    pub get: ResourceGroup,
+}
+
+fn is_extension(players: usize) -> bool {
+    players > BASE_MAX_PLAYERS
+}
+
+/// Checks that `players` can play a game on `board`.
+fn check_players(players: usize, board: &Board) -> Result<(), RuleError> {
+    if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&players) {
+        return Err(RuleError::PlayerCount(players));
+    }
+    let land = board.hexes.values().filter(|h| h.terrain.is_land()).count();
+    if is_extension(players) && land < EXTENSION_LAND_HEXES {
+        return Err(RuleError::BoardTooSmall(players));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Game {
-    players: Vec<Player>,
-    board: Board,
-    bank: ResourceGroup,
-    player_with_road: Option<u32>,
-    player_with_army: Option<u32>,
+    pub(crate) players: Vec<Player>,
+    pub(crate) board: Board,
+    pub(crate) bank: ResourceGroup,
+    pub(crate) deck: Deck,
+    pub(crate) phase: Phase,
+    pub(crate) player_with_road: Option<u32>,
+    pub(crate) player_with_army: Option<u32>,
+    pub(crate) trade: Option<TradeOffer>,
+    pub(crate) last_roll: Option<i32>,
+    /// How many times the dice have been rolled, which counts the rounds.
+    pub(crate) rolls: u32,
+    pub(crate) ships: ShipTurn,
+    /// Set in Cities & Knights games.
+    pub(crate) cities_knights: Option<CitiesKnights>,
+    pub(crate) rules: Rules,
+    /// The seed `rng` started from, which together with `log` reproduces the game.
+    pub(crate) seed: u64,
+    pub(crate) rng: GameRng,
+    pub(crate) dice: Dice,
+    /// Every action applied so far, in order.
+    pub(crate) log: Vec<Action>,
+    #[serde(skip)]
+    pub(crate) undo: Vec<Snapshot>,
+    #[serde(skip)]
+    pub(crate) redo: Vec<Action>,
 }
 impl Game {
-    pub fn new(players: Vec<Player>, board: Board) -> Self {
-        Self {
+    pub fn new(players: Vec<Player>, board: Board, rules: Rules) -> Result<Self, RuleError> {
+        Self::with_rules(players, board, rules, rand::random())
+    }
+    /// A game with the standard rules, see `with_rules`.
+    pub fn with_seed(players: Vec<Player>, board: Board, seed: u64) -> Result<Self, RuleError> {
+        Self::with_rules(players, board, Rules::default(), seed)
+    }
+    /// A game with the bank and deck for the number of players, which must be from
+    /// `MIN_PLAYERS` to `MAX_PLAYERS`. More than `BASE_MAX_PLAYERS` need a board at least
+    /// as big as `Board::extension`.
+    pub fn with_rules(
+        players: Vec<Player>,
+        board: Board,
+        rules: Rules,
+        seed: u64,
+    ) -> Result<Self, RuleError> {
+        check_players(players.len(), &board)?;
+        let extension = is_extension(players.len());
+        let mut rng = game_rng(seed);
+        let mut deck = if extension {
+            Deck::extension()
+        } else {
+            Deck::new()
+        };
+        deck.shuffle(&mut rng);
+        let dice = if rules.dice_deck {
+            Dice::deck()
+        } else {
+            Dice::Random
+        };
+        Ok(Self {
             players,
             board,
-            bank: ResourceGroup::new(20, 20, 20, 20, 20),
+            bank: if extension {
+                ResourceGroup::new(24, 24, 24, 24, 24)
+            } else {
+                ResourceGroup::new(20, 20, 20, 20, 20)
+            },
+            deck,
+            phase: Phase::START_GAME,
             player_with_road: None,
             player_with_army: None,
+            trade: None,
+            last_roll: None,
+            rolls: 0,
+            ships: ShipTurn::default(),
+            cities_knights: None,
+            rules,
+            seed,
+            rng,
+            dice,
+            log: Vec::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+        })
+    }
+    /// A game on a board shuffled from `seed`, so the seed alone reproduces both. Replay it
+    /// with `Board::generate_from(&template, &mut board_rng(seed))`, where the template is
+    /// `Board::extension()` for more than `BASE_MAX_PLAYERS` and `Board::new()` otherwise.
+    pub fn with_random_board(players: Vec<Player>, seed: u64) -> Result<Self, RuleError> {
+        let template = if is_extension(players.len()) {
+            Board::extension()
+        } else {
+            Board::new()
+        };
+        let board = Board::generate_from(&template, &mut board_rng(seed));
+        Self::with_seed(players, board, seed)
+    }
+    /// Rebuilds a game by applying `actions` to a new game started from `seed`.
+    pub fn replay(
+        players: Vec<Player>,
+        board: Board,
+        rules: Rules,
+        seed: u64,
+        actions: &[Action],
+    ) -> Result<Self> {
+        let mut game = Self::with_rules(players, board, rules, seed)?;
+        for (i, action) in actions.iter().enumerate() {
+            game.apply(action.clone())
+                .with_context(|| format!("Replaying action {} {:?}", i, action))?;
         }
+        Ok(game)
     }
-    pub fn give_resources_for_roll(&mut self, roll: i32) {
-        if roll == 7 {
-            dbg!("7!");
-            return;
+    /// A copy of the game without its log and its undo and redo history, which is all a
+    /// search playing ahead needs and much cheaper than `clone` late in a game.
+    pub fn fork(&self) -> Game {
+        Game {
+            players: self.players.clone(),
+            board: self.board.clone(),
+            bank: self.bank,
+            deck: self.deck.clone(),
+            phase: self.phase,
+            player_with_road: self.player_with_road,
+            player_with_army: self.player_with_army,
+            trade: self.trade.clone(),
+            last_roll: self.last_roll,
+            rolls: self.rolls,
+            ships: self.ships.clone(),
+            cities_knights: self.cities_knights.clone(),
+            rules: self.rules.clone(),
+            seed: self.seed,
+            rng: self.rng.clone(),
+            dice: self.dice.clone(),
+            log: Vec::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
         }
-        let player_yields = self.board.yield_for_roll(roll);
-        for (index, res) in player_yields {
-            self.players[index].resources += res;
+    }
+    /// Whether the game is played with the 5-6 player extension.
+    pub fn is_extension(&self) -> bool {
+        is_extension(self.players.len())
+    }
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+    pub fn bank(&self) -> &ResourceGroup {
+        &self.bank
+    }
+    pub fn deck(&self) -> &Deck {
+        &self.deck
+    }
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+    /// The player who has to take the next action.
+    pub fn current_player(&self) -> usize {
+        self.phase.player()
+    }
+    pub fn player_with_road(&self) -> Option<u32> {
+        self.player_with_road
+    }
+    pub fn player_with_army(&self) -> Option<u32> {
+        self.player_with_army
+    }
+    pub fn trade(&self) -> Option<&TradeOffer> {
+        self.trade.as_ref()
+    }
+    pub fn last_roll(&self) -> Option<i32> {
+        self.last_roll
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn log(&self) -> &[Action] {
+        &self.log
+    }
+    pub fn winner(&self) -> Option<usize> {
+        match self.phase {
+            Phase::FinishedGame { winner } => Some(winner),
+            _ => None,
         }
     }
-    pub fn roll() -> i32 {
-        let mut rng = rand::thread_rng();
-
-        rng.gen_range(1..=6) + rng.gen_range(1..=6)
+    /// Points everyone can see: buildings, longest road, largest army and the bonuses of
+    /// the expansions.
+    pub fn public_victory_points(&self, player: usize) -> u32 {
+        let buildings: u32 = self.players[player]
+            .buildings
+            .iter()
+            .map(|pos| match self.board.vertices[pos].build_type {
+                BuildType::City => 2,
+                BuildType::Settlement => 1,
+                BuildType::Knight { .. } | BuildType::None => 0,
+            })
+            .sum();
+        let road = if self.player_with_road == Some(player as u32) {
+            2
+        } else {
+            0
+        };
+        let army = if self.player_with_army == Some(player as u32) {
+            2
+        } else {
+            0
+        };
+        buildings + road + army + self.island_bonus(player) + self.cities_knights_points(player)
     }
-    pub fn play() -> Result<()> {
-        let mut phase = Phase::START_GAME;
-
-        Ok(())
+    pub fn victory_points(&self, player: usize) -> u32 {
+        self.public_victory_points(player) + self.players[player].victory_point_cards()
+    }
+    /// Pays out a roll from the bank, see `roll_payouts`.
+    pub fn give_resources_for_roll(&mut self, roll: i32) {
+        let payouts = roll_payouts(&self.board, &self.bank, self.is_cities_and_knights(), roll);
+        for (player, paid) in payouts {
+            self.players[player].resources += paid;
+            self.bank -= paid;
+        }
+    }
+    /// Replaces where rolls come from, for example with scripted rolls in tests.
+    pub fn set_dice(&mut self, dice: Dice) {
+        self.dice = dice;
+    }
+    pub fn dice(&self) -> &Dice {
+        &self.dice
+    }
+    pub fn roll(&mut self) -> i32 {
+        self.dice.roll(&mut self.rng)
+    }
+    /// Rolls the red and the yellow die.
+    pub fn roll_pair(&mut self) -> (i32, i32) {
+        self.dice.roll_pair(&mut self.rng)
+    }
+    pub fn rules(&self) -> &Rules {
+        &self.rules
     }
 }
 #[cfg(test)]
@@ -54,9 +354,10 @@ mod test {
     #[test]
     fn test_roll() {
         // Arrange
-        let mut game = Game::new(Player::init_players(4), Board::new()); // You should implement a new method for Game struct
+        let mut game = Game::new(Player::init_players(4), Board::new(), Rules::default()).unwrap(); // You should implement a new method for Game struct
         game.board
-            .place_building(0, Axial::new(0, 1), BuildType::Settlement, false);
+            .place_building(0, Axial::new(0, 1), BuildType::Settlement, false)
+            .unwrap();
         dbg!(&game.board.vertices);
 
         // Act
@@ -72,8 +373,24 @@ mod test {
         assert_eq!(game.players[0].resources, res)
     }
     #[test]
+    fn test_random_board_is_reproducible() {
+        let game = Game::with_random_board(Player::init_players(4), 5).unwrap();
+        let board = Board::generate(&mut board_rng(5));
+
+        assert_eq!(game.board.to_string(), board.to_string());
+        assert_eq!(game.board.hexes.len(), 19);
+        assert_eq!(
+            game.board.hexes[&game.board.robber].terrain,
+            crate::terrain::Terrain::Desert
+        );
+        assert_ne!(
+            board.to_string(),
+            Board::generate(&mut board_rng(6)).to_string()
+        );
+    }
+    #[test]
     fn test_place_building() {
-        let mut game = Game::new(Player::init_players(4), Board::new()); // You should implement a new method for Game struct
+        let mut game = Game::new(Player::init_players(4), Board::new(), Rules::default()).unwrap(); // You should implement a new method for Game struct
 
         let b = game
             .board
@@ -82,10 +399,7 @@ mod test {
             Ok(_) => assert!(game.board.vertices[&Axial::new(0, 2)]
                 .owner
                 .is_some_and(|x| x == 0)),
-            Err(e) => {
-                eprintln!("Error {}", e);
-                assert!(false)
-            }
+            Err(e) => panic!("Error {}", e),
         }
         let c = game
             .board
@@ -100,24 +414,56 @@ mod test {
                     BuildType::City
                 );
             }
-            Err(e) => {
-                eprintln!("Error {}", e);
-                assert!(false)
-            }
+            Err(e) => panic!("Error {}", e),
         }
     }
     #[test]
     fn test_place_path() {
-        let mut game = Game::new(Player::init_players(4), Board::new());
+        let mut game = Game::new(Player::init_players(4), Board::new(), Rules::default()).unwrap();
         let path = PathCoords::new(Axial::new(1, 0), Axial::new(0, 1));
         let b = game
             .board
-            .place_path(&game.players[0], path.clone(), PathType::Road);
+            .place_path(&game.players[0], path.clone(), PathType::Road, false);
         match b {
             Ok(_) => assert!(game.board.edges[&path].owner.is_some_and(|x| x == 0)),
-            Err(e) => {
-                assert!(false)
-            }
+            Err(e) => panic!("Error {}", e),
+        }
+    }
+
+    #[test]
+    fn test_extension_components() {
+        let mut game = Game::with_random_board(Player::init_players(6), 3).unwrap();
+        assert!(game.is_extension());
+        assert_eq!(game.board.hexes.len(), 30);
+        assert_eq!(game.bank, ResourceGroup::new(24, 24, 24, 24, 24));
+        assert_eq!(game.deck.len(), 34);
+
+        for _ in 0..24 {
+            let action = game.legal_actions()[0].clone();
+            game.apply(action).unwrap();
+        }
+        assert_eq!(game.phase, Phase::START_TURNS);
+        assert!(game.players.iter().all(|p| p.buildings.len() == 2));
+
+        let base = Game::with_random_board(Player::init_players(4), 3).unwrap();
+        assert!(!base.is_extension());
+        assert_eq!(base.board.hexes.len(), 19);
+        assert_eq!(base.deck.len(), 25);
+    }
+
+    #[test]
+    fn test_player_counts() {
+        for players in [0, 1, 2, 7] {
+            assert_eq!(
+                Game::with_seed(Player::init_players(players), Board::new(), 0).err(),
+                Some(RuleError::PlayerCount(players))
+            );
         }
+        assert_eq!(
+            Game::with_seed(Player::init_players(6), Board::new(), 0).err(),
+            Some(RuleError::BoardTooSmall(6))
+        );
+        assert!(Game::with_seed(Player::init_players(5), Board::extension(), 0).is_ok());
+        assert!(Game::with_seed(Player::init_players(3), Board::new_shores(), 0).is_ok());
     }
 }
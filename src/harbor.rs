@@ -1,2 +1,22 @@
-#[derive(Debug)]
-pub struct Harbor {}
+use serde::{Deserialize, Serialize};
+
+use crate::resource::Resource;
+
+/// A harbor on a coastal edge, trading 2:1 for its resource or 3:1 for anything when it
+/// has none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Harbor {
+    pub resource: Option<Resource>,
+}
+impl Harbor {
+    pub fn new(resource: Option<Resource>) -> Self {
+        Harbor { resource }
+    }
+
+    pub fn ratio(&self) -> i32 {
+        match self.resource {
+            Some(_) => 2,
+            None => 3,
+        }
+    }
+}
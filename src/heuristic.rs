@@ -0,0 +1,416 @@
+use std::collections::HashSet;
+
+use crate::{
+    action::Action,
+    agent::Agent,
+    axial::Axial,
+    board::Board,
+    deck::DevelopmentCard,
+    dice::outcomes,
+    edge::{PathCoords, PathType},
+    game::TradeOffer,
+    resource::{Resource, ResourceGroup, CARD_KINDS},
+    vertex::BuildType,
+    view::PlayerView,
+};
+
+/// How many of the 36 dice outcomes roll `number`, counting nothing for the 7 since no
+/// hex pays out on it.
+pub fn pips(number: i32) -> i32 {
+    if number == 7 {
+        0
+    } else {
+        outcomes(number)
+    }
+}
+
+/// A rule-based player: it settles where the dice pay best, builds cities before
+/// anything else, then settlements, then development cards, and robs the leader.
+#[derive(Debug, Default)]
+pub struct HeuristicAgent;
+impl HeuristicAgent {
+    pub fn new() -> Self {
+        HeuristicAgent
+    }
+
+    /// How good a spot `pos` is for a settlement of `player`'s: the pips of its hexes,
+    /// a point for each resource the player doesn't produce yet and a bonus for a
+    /// harbor that suits its production.
+    pub fn vertex_score(board: &Board, player: usize, pos: Axial) -> f64 {
+        let produced = production(board, player);
+        let pip_sum: f64 = 36.0 * board.expected_vertex_production(pos).iter().sum::<f64>();
+        let resources: HashSet<Resource> = board.producing(pos).into_iter().collect();
+        let new = resources.difference(&produced).count();
+        let harbor = board
+            .harbors
+            .iter()
+            .filter(|(coords, _)| coords.contains(pos))
+            .map(|(_, h)| match h.resource {
+                None => 1.0,
+                Some(r) if resources.contains(&r) || produced.contains(&r) => 2.0,
+                Some(_) => 0.5,
+            })
+            .fold(0.0, f64::max);
+        pip_sum.round() + new as f64 + harbor
+    }
+
+    /// The best settlement spot a road along `coords` opens up, or 0 when it leads
+    /// nowhere new.
+    fn path_score(board: &Board, player: usize, coords: &PathCoords) -> f64 {
+        let open = |v: Axial| board.validate_settlement(player, v, false).is_ok();
+        coords
+            .vertices()
+            .iter()
+            .flat_map(|&v| {
+                let near = open(v).then(|| Self::vertex_score(board, player, v));
+                let far = board
+                    .get_adjacent_vertices(v)
+                    .into_iter()
+                    .filter(|w| open(w.pos))
+                    .map(|w| Self::vertex_score(board, player, w.pos) / 2.0);
+                near.into_iter().chain(far)
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// How much moving the robber to `pos` hurts the leader, less what it costs us.
+    fn robber_score(view: &PlayerView, me: usize, pos: Axial) -> f64 {
+        let leader = leader(view, me);
+        let Some(hex) = view.board.hexes.get(&pos) else {
+            return f64::MIN;
+        };
+        view.board
+            .get_adjacent_vertices(pos)
+            .into_iter()
+            .filter(|v| v.build_type.is_building())
+            .filter_map(|v| v.owner.map(|o| (o, v.build_type)))
+            .map(|(owner, build_type)| {
+                let weight = if owner == me {
+                    -2.0
+                } else if Some(owner) == leader {
+                    1.0
+                } else {
+                    0.25
+                };
+                let size = if build_type == BuildType::City { 2 } else { 1 };
+                weight * (pips(hex.number) * size) as f64
+            })
+            .sum()
+    }
+
+    /// The cost of the next thing worth saving for.
+    fn target(view: &PlayerView, me: usize) -> ResourceGroup {
+        let hand = hand(view);
+        let board = view.board;
+        let has_settlement = view
+            .public(me)
+            .buildings
+            .iter()
+            .any(|v| board.vertices[v].build_type == BuildType::Settlement);
+        let has_spot = !board
+            .get_valid_build_spots(BuildType::Settlement, me, true)
+            .is_empty();
+        let mut wishes = Vec::new();
+        if has_settlement {
+            wishes.push(BuildType::City.cost());
+        }
+        if has_spot {
+            wishes.push(BuildType::Settlement.cost());
+        }
+        wishes.push(DevelopmentCard::cost());
+        wishes
+            .iter()
+            .find(|cost| !hand.can_afford(cost))
+            .or(wishes.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn score(&self, view: &PlayerView, me: usize, action: &Action) -> f64 {
+        let board = view.board;
+        let initial = !view.phase.is_turn();
+        match action {
+            Action::BuildCity(pos) => 100.0 + Self::vertex_score(board, me, *pos),
+            Action::BuildSettlement(pos) => 80.0 + Self::vertex_score(board, me, *pos),
+            Action::BuyDevelopmentCard => 40.0,
+            Action::BuildRoad(coords) | Action::BuildShip(coords) => {
+                let value = Self::path_score(board, me, coords);
+                if initial || value > 0.0 {
+                    20.0 + value
+                } else {
+                    -1.0
+                }
+            }
+            Action::RollDice => 50.0,
+            Action::PlayKnight if robbed(view, me) => 60.0,
+            Action::PlayKnight => 15.0,
+            Action::PlayRoadBuilding => {
+                let open = board
+                    .get_valid_path_spots(me, PathType::Road, true)
+                    .into_iter()
+                    .any(|c| Self::path_score(board, me, &c) > 0.0);
+                if open {
+                    30.0
+                } else {
+                    -1.0
+                }
+            }
+            Action::PlayYearOfPlenty => 35.0,
+            Action::PlayMonopoly(r) => 30.0 + opponents_pips(view, me, *r) as f64 / 100.0,
+            Action::TakeResource(r) => {
+                let need = Self::target(view, me).get(*r) - hand(view).get(*r);
+                10.0 + need as f64
+            }
+            Action::BankTrade { give, get } => {
+                let target = Self::target(view, me);
+                let hand = hand(view);
+                let ratio = board.trade_ratio(me, *give);
+                if hand.get(*give) - target.get(*give) >= ratio && hand.get(*get) < target.get(*get)
+                {
+                    10.0
+                } else {
+                    -1.0
+                }
+            }
+            Action::CommodityTrade { get, .. } => {
+                if hand(view).get(*get) < Self::target(view, me).get(*get) {
+                    10.0
+                } else {
+                    -1.0
+                }
+            }
+            Action::MoveRobber { pos, victim } => {
+                let bonus = match victim {
+                    Some(v) if Some(*v) == leader(view, me) => 0.5,
+                    Some(v) => view.public(*v).resource_cards as f64 / 100.0,
+                    None => 0.0,
+                };
+                Self::robber_score(view, me, *pos) + bonus
+            }
+            Action::MovePirate { .. } => 0.0,
+            Action::ImproveCity(_) => 70.0,
+            Action::PlayProgressCard { .. } => 30.0,
+            Action::BuildKnight(_) | Action::ActivateKnight(_) => 20.0,
+            Action::PromoteKnight(_) => 15.0,
+            Action::ChaseRobber(_) if robbed(view, me) => 25.0,
+            Action::EndTurn => 0.0,
+            Action::AcceptTrade => 0.0,
+            Action::RejectTrade => 0.0,
+            Action::Discard(_) => 0.0,
+            Action::MoveShip { .. }
+            | Action::MoveKnight { .. }
+            | Action::ChaseRobber(_)
+            | Action::OfferTrade { .. } => -1.0,
+        }
+    }
+}
+
+impl Agent for HeuristicAgent {
+    fn choose_action(&mut self, view: &PlayerView, legal: &[Action]) -> Action {
+        let me = view.player.expect("agents play as a player");
+        let mut best = &legal[0];
+        let mut best_score = f64::NEG_INFINITY;
+        for action in legal {
+            let score = self.score(view, me, action);
+            if score > best_score {
+                best = action;
+                best_score = score;
+            }
+        }
+        best.clone()
+    }
+
+    /// Accepts trades that bring the next build closer without shrinking the hand.
+    fn respond_to_trade(&mut self, view: &PlayerView, offer: &TradeOffer) -> bool {
+        let me = view.player.expect("agents play as a player");
+        let hand = hand(view);
+        let Some(rest) = hand.checked_sub(&offer.get) else {
+            return false;
+        };
+        let target = Self::target(view, me);
+        let after = rest + offer.give;
+        missing(&after, &target) < missing(&hand, &target)
+            && offer.give.total() >= offer.get.total()
+    }
+
+    /// Gives back whatever is furthest beyond the next build's cost.
+    fn discard(&mut self, view: &PlayerView, count: i32) -> ResourceGroup {
+        let me = view.player.expect("agents play as a player");
+        let target = Self::target(view, me).counts();
+        let mut left = hand(view).counts();
+        let mut discarded = [0; CARD_KINDS];
+        for _ in 0..count {
+            let Some(i) = (0..CARD_KINDS)
+                .filter(|&i| left[i] > 0)
+                .max_by_key(|&i| (left[i] - target[i], left[i]))
+            else {
+                break;
+            };
+            left[i] -= 1;
+            discarded[i] += 1;
+        }
+        ResourceGroup::from_counts(discarded)
+    }
+}
+
+fn hand(view: &PlayerView) -> ResourceGroup {
+    view.hand.as_ref().map(|h| h.resources).unwrap_or_default()
+}
+
+/// How many cards `hand` is short of `cost`.
+fn missing(hand: &ResourceGroup, cost: &ResourceGroup) -> i32 {
+    hand.counts()
+        .iter()
+        .zip(cost.counts())
+        .map(|(have, need)| (need - have).max(0))
+        .sum()
+}
+
+/// The resources `player`'s buildings collect.
+fn production(board: &Board, player: usize) -> HashSet<Resource> {
+    board
+        .vertices
+        .values()
+        .filter(|v| v.owner == Some(player) && v.build_type.is_building())
+        .flat_map(|v| board.producing(v.pos))
+        .collect()
+}
+
+/// The opponent with the most public victory points, the earliest on a tie.
+fn leader(view: &PlayerView, me: usize) -> Option<usize> {
+    view.players
+        .iter()
+        .filter(|p| p.id != me)
+        .max_by_key(|p| (p.victory_points, std::cmp::Reverse(p.id)))
+        .map(|p| p.id)
+}
+
+/// Whether the robber sits on a hex next to one of `me`'s buildings.
+fn robbed(view: &PlayerView, me: usize) -> bool {
+    view.board
+        .get_adjacent_vertices(view.board.robber)
+        .iter()
+        .any(|v| v.owner == Some(me) && v.build_type.is_building())
+}
+
+/// The pips of every hex producing `resource` next to an opponent's building.
+fn opponents_pips(view: &PlayerView, me: usize, resource: Resource) -> i32 {
+    view.board
+        .vertices
+        .values()
+        .filter(|v| v.build_type.is_building() && v.owner.is_some_and(|o| o != me))
+        .flat_map(|v| view.board.get_adjacent_hexes(v.pos))
+        .filter(|h| h.terrain.resource() == Some(resource))
+        .map(|h| pips(h.number))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        agent::{play_game, RandomAgent},
+        game::Game,
+        phase::{DevelopmentPhase, Phase, TurnPhase},
+        player::Player,
+    };
+
+    use super::*;
+
+    fn heuristic_agents(n: usize) -> Vec<Box<dyn Agent>> {
+        (0..n)
+            .map(|_| Box::new(HeuristicAgent::new()) as Box<dyn Agent>)
+            .collect()
+    }
+
+    /// Four heuristic players through initial placement, with player 0 to act freely.
+    fn placed_game() -> Game {
+        let mut game = Game::with_seed(Player::init_players(4), Board::new(), 3).unwrap();
+        play_game(&mut game, &mut heuristic_agents(4), 16).unwrap();
+        assert!(game.phase().is_turn());
+        game.phase = Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::Free,
+            development_phase: DevelopmentPhase::Ready,
+        };
+        game
+    }
+
+    #[test]
+    fn test_pips() {
+        assert_eq!(pips(2), 1);
+        assert_eq!(pips(6), 5);
+        assert_eq!(pips(7), 0);
+        assert_eq!(pips(8), 5);
+        assert_eq!(pips(12), 1);
+        assert_eq!(pips(0), 0);
+    }
+
+    #[test]
+    fn test_settles_on_the_best_spot() {
+        let game = Game::with_seed(Player::init_players(4), Board::new(), 3).unwrap();
+        let legal = game.legal_actions();
+        let view = game.view_for(0);
+        let Action::BuildSettlement(chosen) = HeuristicAgent::new().choose_action(&view, &legal)
+        else {
+            panic!("placement starts with a settlement");
+        };
+        let best = legal
+            .iter()
+            .filter_map(|a| match a {
+                Action::BuildSettlement(pos) => {
+                    Some(HeuristicAgent::vertex_score(&game.board, 0, *pos))
+                }
+                _ => None,
+            })
+            .fold(0.0, f64::max);
+        assert_eq!(HeuristicAgent::vertex_score(&game.board, 0, chosen), best);
+        assert!(best >= 10.0);
+    }
+
+    #[test]
+    fn test_cities_come_first() {
+        let mut game = placed_game();
+        game.players[0].resources = ResourceGroup::new(5, 5, 5, 5, 5);
+        let action = HeuristicAgent::new().choose_action(&game.view_for(0), &game.legal_actions());
+        assert!(matches!(action, Action::BuildCity(_)), "{action:?}");
+
+        game.players[0].resources = ResourceGroup::new(1, 1, 1, 0, 0);
+        let action = HeuristicAgent::new().choose_action(&game.view_for(0), &game.legal_actions());
+        assert_eq!(action, Action::BuyDevelopmentCard);
+    }
+
+    #[test]
+    fn test_robs_the_leader() {
+        let mut game = placed_game();
+        let city = game.view_for(2).public(2).buildings[0];
+        game.board.vertices.get_mut(&city).unwrap().build_type = BuildType::City;
+        game.players[2].resources = ResourceGroup::new(1, 1, 0, 0, 0);
+        game.phase = Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::MoveThief,
+            development_phase: DevelopmentPhase::Ready,
+        };
+        let action = HeuristicAgent::new().choose_action(&game.view_for(0), &game.legal_actions());
+        let Action::MoveRobber { pos, victim } = action else {
+            panic!("expected to move the robber, got {action:?}");
+        };
+        assert_eq!(victim, Some(2));
+        let owners: Vec<Option<usize>> = game
+            .board
+            .get_adjacent_vertices(pos)
+            .iter()
+            .map(|v| v.owner)
+            .collect();
+        assert!(owners.contains(&Some(2)));
+        assert!(!owners.contains(&Some(0)));
+    }
+
+    #[test]
+    fn test_beats_random_agents() {
+        let mut game = Game::with_seed(Player::init_players(4), Board::new(), 5).unwrap();
+        let mut agents: Vec<Box<dyn Agent>> = vec![Box::new(HeuristicAgent::new())];
+        agents.extend((1..4).map(|i| Box::new(RandomAgent::new(i)) as Box<dyn Agent>));
+        let winner = play_game(&mut game, &mut agents, 20_000).unwrap();
+        assert_eq!(winner, Some(0));
+    }
+}
@@ -1,10 +1,12 @@
-use crate::resource::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::Terrain;
 
 use super::axial::Axial;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Hex {
-    pub resource_type: Resource,
+    pub terrain: Terrain,
     pub number: i32,
     pub pos: Axial,
 }
@@ -0,0 +1,504 @@
+//! A plain text description of a board.
+//!
+//! Every row of hexes is a line of tokens, read top to bottom and centred on the widest
+//! row like the printed map. A token is the letter of the resource a hex produces followed
+//! by its number (`O10`, `W8`), `Au` and a number for gold, `D` for the desert, `~` for sea
+//! or `-` for a gap with no hex. Resource letters are `O` ore, `G` wheat (grain), `S`
+//! sheep, `B` brick and `W` wood. Rows that line up with no half-hex offset have to be
+//! padded with gaps.
+//!
+//! Harbors, the robber and the Seafarers pirate are given on their own lines, addressing a
+//! hex by its row and column, counted from zero and including gaps:
+//!
+//! ```text
+//! harbor 3:1 0 0 NW   # generic harbor on the north west side of the first hex
+//! harbor O 1 3 NE     # 2:1 ore harbor
+//! robber 2 2
+//! pirate 0 1          # has to be on a sea hex
+//! ```
+//!
+//! The robber defaults to the desert when no robber line is given, and there is no pirate
+//! without a pirate line. Lines starting with `#` are comments.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+
+use crate::{
+    axial::Axial,
+    board::{Board, OFFSETS},
+    edge::PathCoords,
+    harbor::Harbor,
+    hex::Hex,
+    resource::Resource,
+    terrain::Terrain,
+};
+
+/// The beginner board from the base game rules.
+pub const BASE_MAP: &str = "
+    O10 S2 W9
+  G12 B6 S4 B10
+G9 W11 D W3 O8
+  W8 O3 G4 S5
+    B5 G6 S11
+harbor 3:1 0 0 NW
+harbor G 0 1 NE
+harbor O 1 3 NE
+harbor 3:1 2 4 SE
+harbor S 3 3 SE
+harbor 3:1 4 1 SE
+harbor 3:1 4 0 SW
+harbor B 3 0 W
+harbor W 1 0 W
+";
+
+/// The board of the 5-6 player extension, with two deserts and eleven harbors.
+pub const EXTENSION_MAP: &str = "
+      B10 O3 G8
+    D G6 B10 W3
+  G9 O2 W5 W4 D
+B2 S12 G9 G8 O9 S11
+  W5 W6 O12 S11 S4
+    S4 W11 G3 O6
+      S8 B10 B5
+harbor 3:1 0 0 NW
+harbor G 0 1 NE
+harbor O 1 3 NE
+harbor 3:1 2 4 E
+harbor S 3 5 SE
+harbor 3:1 5 3 E
+harbor S 6 2 SE
+harbor 3:1 6 1 SW
+harbor 3:1 5 0 SW
+harbor B 4 0 W
+harbor W 2 0 W
+";
+
+/// A Seafarers scenario after "Heading for New Shores" for 3-4 players: a home island
+/// and two smaller islands to settle for bonus points, framed by sea. It has no gold
+/// fields.
+pub const NEW_SHORES_MAP: &str = "
+  ~   ~   ~   ~   ~   ~   ~   ~
+~   W5  G9  B10 ~   ~   O3  ~   ~
+  ~   O8  S4  G11 W3  ~   G6  ~
+~   S10 B2  D   W9  ~   ~   B4  ~
+  ~   G3  W11 O5  S8  ~   ~   ~
+~   B9  S6  G4  ~   ~   W10 S12 ~
+  ~   ~   ~   ~   ~   ~   ~   ~
+harbor 3:1 1 1 NW
+harbor O 2 1 W
+harbor S 3 4 E
+harbor 3:1 5 3 SE
+pirate 3 5
+";
+
+/// Hex sides in clockwise order, side `i` joining corners `OFFSETS[i]` and `OFFSETS[i + 1]`.
+const SIDES: [&str; 6] = ["NE", "E", "SE", "SW", "W", "NW"];
+
+/// A position in the text grid: the row and the column in half-hex steps from the left.
+type Cell = (i32, i32);
+
+fn resource_letter(resource: Resource) -> &'static str {
+    match resource {
+        Resource::Ore => "O",
+        Resource::Wheat => "G",
+        Resource::Sheep => "S",
+        Resource::Brick => "B",
+        Resource::Wood => "W",
+    }
+}
+
+fn hex_token(hex: &Hex) -> String {
+    if let Some(r) = hex.terrain.resource() {
+        return format!("{}{}", resource_letter(r), hex.number);
+    }
+    match hex.terrain {
+        Terrain::Gold => format!("Au{}", hex.number),
+        Terrain::Sea => "~".to_string(),
+        _ => "D".to_string(),
+    }
+}
+
+fn parse_resource_letter(letter: &str) -> Option<Resource> {
+    match letter {
+        "O" => Some(Resource::Ore),
+        "G" => Some(Resource::Wheat),
+        "S" => Some(Resource::Sheep),
+        "B" => Some(Resource::Brick),
+        "W" => Some(Resource::Wood),
+        _ => None,
+    }
+}
+
+fn parse_hex_token(token: &str) -> Result<Option<(Terrain, i32)>> {
+    match token {
+        "-" => return Ok(None),
+        "D" => return Ok(Some((Terrain::Desert, 0))),
+        "~" => return Ok(Some((Terrain::Sea, 0))),
+        _ => {}
+    }
+    let split = token
+        .find(|c: char| c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("Hex {} has no number", token))?;
+    let terrain = match &token[..split] {
+        "Au" => Some(Terrain::Gold),
+        letter => parse_resource_letter(letter).map(Terrain::producing),
+    }
+    .ok_or_else(|| anyhow!("Unknown terrain in hex {}", token))?;
+    let number: i32 = token[split..]
+        .parse()
+        .map_err(|_| anyhow!("Invalid number in hex {}", token))?;
+    if !(2..=12).contains(&number) || number == 7 {
+        return Err(anyhow!("Hex {} has no matching dice roll", token));
+    }
+    Ok(Some((terrain, number)))
+}
+
+/// Maps a cell relative to the centre of the grid onto the axial coordinates of a hex
+/// centre. Moving right along a row is `(-1, 2)` and moving down and left is `(-1, -1)`.
+fn cell_to_axial((row, col): Cell) -> Axial {
+    let right = (col + row) / 2;
+    Axial::new(-right - row, 2 * right - row)
+}
+
+fn axial_to_cell(pos: Axial) -> Option<Cell> {
+    let (q, r) = (pos.q(), pos.r());
+    if (r - q) % 3 != 0 {
+        return None;
+    }
+    let right = (r - q) / 3;
+    let row = -q - right;
+    Some((row, 2 * right - row))
+}
+
+/// The text grid a board is read from, with the coordinates of every column.
+struct Grid {
+    rows: Vec<Vec<String>>,
+    /// The half-hex column of the first token on each row.
+    starts: Vec<i32>,
+    origin: Cell,
+}
+impl Grid {
+    fn new(rows: Vec<Vec<String>>) -> Result<Self> {
+        let widest = rows.iter().map(Vec::len).max().unwrap_or(0) as i32;
+        let starts: Vec<i32> = rows.iter().map(|r| widest - r.len() as i32).collect();
+        let parity = |row: usize| (row as i32 + starts[row]).rem_euclid(2);
+        if let Some(row) = (1..rows.len()).find(|&row| parity(row) != parity(0)) {
+            return Err(anyhow!(
+                "Row {} does not line up with the rows above it, pad it with '-'",
+                row
+            ));
+        }
+        let origin_row = rows.len() as i32 / 2;
+        let mut origin_col = widest - 1;
+        if !rows.is_empty() && (origin_row + origin_col).rem_euclid(2) != parity(0) {
+            origin_col += 1;
+        }
+        Ok(Grid {
+            rows,
+            starts,
+            origin: (origin_row, origin_col),
+        })
+    }
+
+    fn pos(&self, row: usize, col: usize) -> Axial {
+        cell_to_axial((
+            row as i32 - self.origin.0,
+            self.starts[row] + 2 * col as i32 - self.origin.1,
+        ))
+    }
+
+    fn parse_pos(&self, board: &Board, row: &str, col: &str) -> Result<Axial> {
+        let row: usize = row.parse()?;
+        let col: usize = col.parse()?;
+        if row >= self.rows.len() || col >= self.rows[row].len() {
+            return Err(anyhow!("There is no hex at row {} column {}", row, col));
+        }
+        let pos = self.pos(row, col);
+        if !board.hexes.contains_key(&pos) {
+            return Err(anyhow!("There is no hex at row {} column {}", row, col));
+        }
+        Ok(pos)
+    }
+}
+
+fn side_coords(hex: Axial, side: usize) -> PathCoords {
+    PathCoords::new(hex + OFFSETS[side], hex + OFFSETS[(side + 1) % 6])
+}
+
+impl FromStr for Board {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rows = Vec::new();
+        let mut features = Vec::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let tokens: Vec<String> = line.split_whitespace().map(String::from).collect();
+            match tokens.first().map(String::as_str) {
+                None => {}
+                Some("harbor") | Some("robber") | Some("pirate") => {
+                    features.push((number + 1, tokens))
+                }
+                Some(_) => rows.push(tokens),
+            }
+        }
+
+        let grid = Grid::new(rows)?;
+        let mut hexes = HashMap::new();
+        for (row, tokens) in grid.rows.iter().enumerate() {
+            for (col, token) in tokens.iter().enumerate() {
+                if let Some((terrain, number)) = parse_hex_token(token)? {
+                    let pos = grid.pos(row, col);
+                    hexes.insert(
+                        pos,
+                        Hex {
+                            terrain,
+                            number,
+                            pos,
+                        },
+                    );
+                }
+            }
+        }
+        if hexes.is_empty() {
+            return Err(anyhow!("Board has no hexes"));
+        }
+
+        let mut board = Board::from_hexes(hexes);
+        for (number, tokens) in features {
+            let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+            match tokens.as_slice() {
+                ["harbor", kind, row, col, side] => {
+                    let resource = match *kind {
+                        "3:1" => None,
+                        letter => Some(parse_resource_letter(letter).ok_or_else(|| {
+                            anyhow!("Line {}: unknown harbor {}", number, letter)
+                        })?),
+                    };
+                    let hex = grid
+                        .parse_pos(&board, row, col)
+                        .map_err(|e| anyhow!("Line {}: {}", number, e))?;
+                    let side = SIDES
+                        .iter()
+                        .position(|s| s == side)
+                        .ok_or_else(|| anyhow!("Line {}: unknown side {}", number, side))?;
+                    board
+                        .harbors
+                        .insert(side_coords(hex, side), Harbor::new(resource));
+                }
+                ["robber", row, col] => {
+                    board.robber = grid
+                        .parse_pos(&board, row, col)
+                        .map_err(|e| anyhow!("Line {}: {}", number, e))?;
+                }
+                ["pirate", row, col] => {
+                    let pos = grid
+                        .parse_pos(&board, row, col)
+                        .map_err(|e| anyhow!("Line {}: {}", number, e))?;
+                    if board.hexes[&pos].terrain != Terrain::Sea {
+                        return Err(anyhow!("Line {}: the pirate has to be at sea", number));
+                    }
+                    board.pirate = Some(pos);
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "Line {}: could not read {}",
+                        number,
+                        tokens.join(" ")
+                    ))
+                }
+            }
+        }
+        Ok(board)
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut cells: Vec<(Cell, &Hex)> = self
+            .hexes
+            .values()
+            .filter_map(|hex| axial_to_cell(hex.pos).map(|cell| (cell, hex)))
+            .collect();
+        cells.sort_by_key(|(cell, _)| *cell);
+        let Some(top) = cells.first().map(|((row, _), _)| *row) else {
+            return Ok(());
+        };
+        let bottom = cells.last().map(|((row, _), _)| *row).unwrap_or(top);
+        let min_col = cells.iter().map(|((_, col), _)| *col).min().unwrap_or(0);
+        let max_col = cells.iter().map(|((_, col), _)| *col).max().unwrap_or(0);
+        let centre = (min_col + max_col).div_euclid(2);
+        let parity = (top + cells[0].0 .1).rem_euclid(2);
+
+        // Every row is widened with gaps until it is symmetric around the centre column,
+        // which is where the parser places it again.
+        let mut rows = Vec::new();
+        for row in top..=bottom {
+            let in_row = cells.iter().filter(|((r, _), _)| *r == row);
+            let (mut first, mut last) = in_row
+                .clone()
+                .fold((i32::MAX, i32::MIN), |(a, b), ((_, c), _)| {
+                    (a.min(*c), b.max(*c))
+                });
+            if first > last {
+                // A row without hexes still needs a gap in the right half-hex position.
+                first = centre - (row + centre - parity).rem_euclid(2);
+                last = first;
+            }
+            let first = first.min(2 * centre - last);
+            let last = 2 * centre - first;
+            rows.push((row, first, last));
+        }
+
+        let widest = rows
+            .iter()
+            .map(|(_, first, last)| (last - first) / 2 + 1)
+            .max()
+            .unwrap_or(0);
+        let mut positions = HashMap::new();
+        for &(row, first, last) in rows.iter() {
+            let width = (last - first) / 2 + 1;
+            let mut tokens = Vec::new();
+            for (col, c) in (first..=last).step_by(2).enumerate() {
+                let token = match cells.iter().find(|(cell, _)| *cell == (row, c)) {
+                    Some((_, hex)) => {
+                        positions.insert(hex.pos, (row - top, col));
+                        hex_token(hex)
+                    }
+                    None => "-".to_string(),
+                };
+                tokens.push(token);
+            }
+            let indent = " ".repeat(2 * (widest - width) as usize);
+            writeln!(f, "{}{}", indent, tokens.join(" "))?;
+        }
+
+        let mut harbors = Vec::new();
+        for (coords, harbor) in self.harbors.iter() {
+            let side = positions
+                .iter()
+                .flat_map(|(&hex, &(row, col))| (0..6).map(move |side| (hex, row, col, side)))
+                .filter(|&(hex, _, _, side)| side_coords(hex, side) == *coords)
+                .map(|(_, row, col, side)| (row, col, side))
+                .min();
+            if let Some(side) = side {
+                harbors.push((side, harbor));
+            }
+        }
+        harbors.sort_by_key(|(side, _)| *side);
+        for ((row, col, side), harbor) in harbors {
+            let kind = harbor.resource.map_or("3:1", resource_letter);
+            writeln!(f, "harbor {} {} {} {}", kind, row, col, SIDES[side])?;
+        }
+        if let Some((row, col)) = positions.get(&self.robber) {
+            writeln!(f, "robber {} {}", row, col)?;
+        }
+        if let Some((row, col)) = self.pirate.and_then(|pirate| positions.get(&pirate)) {
+            writeln!(f, "pirate {} {}", row, col)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_map_positions() {
+        let board = Board::new();
+        assert_eq!(board.hexes.len(), 19);
+        assert_eq!(board.hexes[&Axial::new(4, -2)].terrain, Terrain::Mountains);
+        assert_eq!(board.hexes[&Axial::new(4, -2)].number, 10);
+        assert_eq!(board.hexes[&Axial::new(-4, 2)].terrain, Terrain::Pasture);
+        assert_eq!(board.hexes[&Axial::new(-4, 2)].number, 11);
+        assert_eq!(board.robber, Axial::new(0, 0));
+        assert_eq!(board.harbors.len(), 9);
+        for coords in board.harbors.keys() {
+            assert!(board.edges.contains_key(coords));
+        }
+    }
+
+    #[test]
+    fn test_print_parse_round_trip() {
+        let board = Board::new();
+        let text = board.to_string();
+        let parsed: Board = text.parse().unwrap();
+
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.robber, board.robber);
+        assert_eq!(parsed.harbors, board.harbors);
+        for (pos, hex) in board.hexes.iter() {
+            assert_eq!(parsed.hexes[pos].terrain, hex.terrain);
+            assert_eq!(parsed.hexes[pos].number, hex.number);
+        }
+    }
+
+    #[test]
+    fn test_parse_gaps_and_robber() {
+        let board: Board = "
+              W8 - B5
+            O10 S6 G9 D
+            robber 1 1
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(board.hexes.len(), 6);
+        assert_eq!(board.hexes[&board.robber].number, 6);
+        let reparsed: Board = board.to_string().parse().unwrap();
+        assert_eq!(reparsed.to_string(), board.to_string());
+    }
+
+    #[test]
+    fn test_parse_sea_and_gold() {
+        let board: Board = "
+              ~ Au5
+            W8 D ~
+        "
+        .parse()
+        .unwrap();
+        let count = |t: Terrain| board.hexes.values().filter(|h| h.terrain == t).count();
+        assert_eq!(count(Terrain::Sea), 2);
+        assert_eq!(count(Terrain::Gold), 1);
+        assert_eq!(board.hexes[&board.robber].terrain, Terrain::Desert);
+        let reparsed: Board = board.to_string().parse().unwrap();
+        assert_eq!(reparsed.to_string(), board.to_string());
+    }
+
+    #[test]
+    fn test_extension_map() {
+        let board = Board::extension();
+        let count = |t: Terrain| board.hexes.values().filter(|h| h.terrain == t).count();
+        assert_eq!(board.hexes.len(), 30);
+        assert_eq!(count(Terrain::Desert), 2);
+        assert_eq!(count(Terrain::Fields), 6);
+        assert_eq!(count(Terrain::Hills), 5);
+        let mut numbers: Vec<i32> = board.hexes.values().map(|h| h.number).collect();
+        numbers.sort();
+        assert_eq!(numbers.iter().filter(|&&n| n == 0).count(), 2);
+        assert_eq!(numbers.iter().filter(|&&n| n == 2 || n == 12).count(), 4);
+        assert_eq!(numbers.iter().filter(|&&n| n == 6).count(), 3);
+        assert_eq!(board.hexes[&board.robber].terrain, Terrain::Desert);
+        assert_eq!(board.harbors.len(), 11);
+        for coords in board.harbors.keys() {
+            let [a, b] = coords.vertices();
+            let shared = board
+                .get_adjacent_hexes(a)
+                .iter()
+                .filter(|hex| board.get_adjacent_hexes(b).iter().any(|h| h.pos == hex.pos))
+                .count();
+            assert_eq!(shared, 1, "harbor {:?} is not on the coast", coords);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("W8 X5".parse::<Board>().is_err());
+        assert!("W7".parse::<Board>().is_err());
+        assert!("W8 B5\nO10 S6".parse::<Board>().is_err());
+        assert!("W8 B5\nharbor 3:1 0 5 NE".parse::<Board>().is_err());
+        assert!("W8 B5\nharbor 3:1 0 0 N".parse::<Board>().is_err());
+        assert!("W8 B5\nrobber 0".parse::<Board>().is_err());
+    }
+}
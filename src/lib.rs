@@ -1,14 +1,35 @@
+pub mod action;
+pub mod advisor;
+pub mod agent;
+pub mod arena;
 pub mod axial;
 pub mod board;
+pub mod cities_knights;
+pub mod compact;
 pub mod deck;
+pub mod dice;
 pub mod edge;
+pub mod env;
+pub mod error;
 pub mod game;
 pub mod harbor;
+pub mod heuristic;
 pub mod hex;
+pub mod layout;
+pub mod mcts;
 pub mod phase;
 pub mod player;
+pub mod production;
 pub mod resource;
+pub mod rules;
+pub mod save;
+pub mod seafarers;
+pub mod stats;
+pub mod terrain;
+pub mod tracker;
+pub mod undo;
 pub mod vertex;
+pub mod view;
 #[cfg(test)]
 mod tests {
 
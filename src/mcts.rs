@@ -0,0 +1,536 @@
+use std::time::{Duration, Instant};
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    action::Action,
+    agent::{Agent, Belief},
+    cities_knights::{ProgressCard, Track},
+    deck::Deck,
+    dice::{game_rng, Dice},
+    game::{Game, TradeOffer},
+    heuristic::HeuristicAgent,
+    phase::{DevelopmentPhase, Phase, TurnPhase},
+    resource::{Resource, ResourceGroup, CARD_KINDS},
+    vertex::BuildType,
+    view::PlayerView,
+};
+
+/// How long a search may run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    Iterations(usize),
+    Time(Duration),
+}
+
+/// Settings for `MctsAgent`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MctsConfig {
+    pub budget: Budget,
+    /// The UCB exploration constant.
+    pub exploration: f64,
+    /// Rollouts that haven't been won after this many actions are scored by points.
+    pub rollout_limit: usize,
+}
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            budget: Budget::Iterations(1000),
+            exploration: std::f64::consts::SQRT_2,
+            rollout_limit: 500,
+        }
+    }
+}
+
+impl Game {
+    /// A copy of the game as `observer` might believe it to be: the other players' hands,
+    /// development cards and progress cards are dealt again from the cards `observer`
+    /// can't see, keeping the size of every hand and how many progress cards of each track
+    /// it holds, and the decks, dice deck and rng are shuffled. Every pool is sorted before
+    /// it is shuffled, so the sample tells nothing of who held which card or of the order
+    /// of any deck, even to someone who knows `rng`.
+    pub fn determinize(&self, observer: usize, rng: &mut impl Rng) -> Game {
+        let mut game = self.fork();
+        let others: Vec<usize> = (0..game.players.len()).filter(|&p| p != observer).collect();
+
+        let mut cards: Vec<usize> = others
+            .iter()
+            .flat_map(|&p| {
+                let counts = game.players[p].resources.counts();
+                (0..CARD_KINDS)
+                    .flat_map(move |kind| std::iter::repeat_n(kind, counts[kind] as usize))
+            })
+            .collect();
+        cards.sort();
+        cards.shuffle(rng);
+        for &p in &others {
+            let mut counts = [0; CARD_KINDS];
+            for _ in 0..game.players[p].hand_size() {
+                counts[cards.pop().expect("dealing as many cards as were pooled")] += 1;
+            }
+            game.players[p].resources = ResourceGroup::from_counts(counts);
+        }
+
+        let mut development_cards = game.deck.cards().to_vec();
+        for &p in &others {
+            let player = &game.players[p];
+            development_cards.extend_from_slice(&player.development_cards);
+            development_cards.extend_from_slice(&player.new_development_cards);
+        }
+        development_cards.sort_by_key(|&card| card as usize);
+        development_cards.shuffle(rng);
+        for &p in &others {
+            let player = &mut game.players[p];
+            let playable = player.development_cards.len();
+            let new = player.new_development_cards.len();
+            player.development_cards =
+                development_cards.split_off(development_cards.len() - playable);
+            player.new_development_cards =
+                development_cards.split_off(development_cards.len() - new);
+        }
+        game.deck = Deck::from_cards(development_cards);
+
+        if let Some(ck) = game.cities_knights.as_mut() {
+            for track in Track::ALL {
+                let of_track = |card: &ProgressCard| card.track() == track;
+                let deck = &mut ck.progress_decks[track as usize];
+                let mut counts = Vec::new();
+                for &p in &others {
+                    let hand = &mut ck.progress_cards[p];
+                    counts.push(hand.iter().filter(|c| of_track(c)).count());
+                    deck.extend(hand.iter().copied().filter(of_track));
+                    hand.retain(|c| !of_track(c));
+                }
+                deck.sort_by_key(|&card| card as usize);
+                deck.shuffle(rng);
+                for (&p, count) in others.iter().zip(counts) {
+                    let dealt = deck.split_off(deck.len() - count);
+                    ck.progress_cards[p].extend(dealt);
+                }
+            }
+        }
+
+        if let Dice::Deck(outcomes) = &mut game.dice {
+            outcomes.sort();
+            outcomes.shuffle(rng);
+        }
+        game.seed = rng.gen();
+        game.rng = game_rng(game.seed);
+        game
+    }
+}
+
+/// A node of the search tree, reached by `action` of `player`.
+struct Node {
+    action: Option<Action>,
+    player: usize,
+    visits: u32,
+    /// How often the action was legal when its parent was visited.
+    available: u32,
+    reward: f64,
+    children: Vec<usize>,
+}
+impl Node {
+    fn new(action: Option<Action>, player: usize) -> Self {
+        Node {
+            action,
+            player,
+            visits: 0,
+            available: 1,
+            reward: 0.0,
+            children: Vec::new(),
+        }
+    }
+
+    fn ucb(&self, exploration: f64) -> f64 {
+        let visits = self.visits.max(1) as f64;
+        self.reward / visits + exploration * ((self.available as f64).ln() / visits).sqrt()
+    }
+}
+
+/// Information-set Monte Carlo tree search. Every iteration searches a fresh
+/// determinization of the game, so the tree is shared between the ways the hidden cards
+/// might lie, and finishes with a quick random rollout. Trades and discards, which are
+/// answered from a view alone, are left to `HeuristicAgent`.
+pub struct MctsAgent {
+    config: MctsConfig,
+    rng: ChaCha8Rng,
+    fallback: HeuristicAgent,
+}
+impl MctsAgent {
+    pub fn new(config: MctsConfig, seed: u64) -> Self {
+        MctsAgent {
+            config,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            fallback: HeuristicAgent::new(),
+        }
+    }
+
+    /// The most visited of `legal` after searching determinizations of `belief` within
+    /// the budget.
+    pub fn search(&mut self, belief: &Belief, legal: &[Action]) -> Action {
+        if legal.len() == 1 {
+            return legal[0].clone();
+        }
+        let me = belief.player();
+        let mut tree = vec![Node::new(None, me)];
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            let done = match self.config.budget {
+                Budget::Iterations(n) => iterations >= n,
+                Budget::Time(limit) => iterations > 0 && start.elapsed() >= limit,
+            };
+            if done {
+                break;
+            }
+            self.iterate(&mut tree, belief, legal);
+            iterations += 1;
+        }
+        tree[0]
+            .children
+            .iter()
+            .map(|&c| &tree[c])
+            .max_by_key(|node| node.visits)
+            .and_then(|node| node.action.clone())
+            .unwrap_or_else(|| legal[0].clone())
+    }
+
+    fn iterate(&mut self, tree: &mut Vec<Node>, belief: &Belief, root_legal: &[Action]) {
+        let mut game = belief.sample(&mut self.rng);
+        let mut node = 0;
+        let mut path = vec![0];
+        while game.winner().is_none() {
+            let legal = if node == 0 {
+                root_legal.to_vec()
+            } else {
+                game.legal_actions()
+            };
+            if legal.is_empty() {
+                break;
+            }
+            let player = game.phase().player();
+            let children = tree[node].children.clone();
+            for &c in &children {
+                if legal.contains(tree[c].action.as_ref().expect("children have actions")) {
+                    tree[c].available += 1;
+                }
+            }
+            let untried: Vec<&Action> = legal
+                .iter()
+                .filter(|a| !children.iter().any(|&c| tree[c].action.as_ref() == Some(a)))
+                .collect();
+            if let Some(&action) = untried.choose(&mut self.rng) {
+                game.apply(action.clone()).expect("legal actions apply");
+                tree.push(Node::new(Some(action.clone()), player));
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                path.push(child);
+                break;
+            }
+            let exploration = self.config.exploration;
+            let best = children
+                .iter()
+                .copied()
+                .filter(|&c| {
+                    legal.contains(tree[c].action.as_ref().expect("children have actions"))
+                })
+                .max_by(|&a, &b| {
+                    tree[a]
+                        .ucb(exploration)
+                        .total_cmp(&tree[b].ucb(exploration))
+                })
+                .expect("every legal action has been tried");
+            let action = tree[best].action.clone().expect("children have actions");
+            game.apply(action).expect("legal actions apply");
+            node = best;
+            path.push(best);
+        }
+
+        rollout(&mut game, self.config.rollout_limit, &mut self.rng);
+        let rewards = rewards(&game);
+        for &n in &path {
+            tree[n].visits += 1;
+            tree[n].reward += rewards[tree[n].player];
+        }
+    }
+}
+
+impl Agent for MctsAgent {
+    fn choose_action(&mut self, view: &PlayerView, legal: &[Action]) -> Action {
+        self.fallback.choose_action(view, legal)
+    }
+
+    fn choose_action_in(&mut self, belief: &Belief, legal: &[Action]) -> Action {
+        self.search(belief, legal)
+    }
+
+    fn respond_to_trade(&mut self, view: &PlayerView, offer: &TradeOffer) -> bool {
+        self.fallback.respond_to_trade(view, offer)
+    }
+
+    fn discard(&mut self, view: &PlayerView, count: i32) -> ResourceGroup {
+        self.fallback.discard(view, count)
+    }
+}
+
+/// Plays on with random moves until someone wins or `limit` actions have been applied.
+/// On a free turn it tries cities, settlements, development cards and roads by the
+/// player's own pieces before ending the turn, which avoids listing every legal action.
+fn rollout(game: &mut Game, limit: usize, rng: &mut impl Rng) {
+    for _ in 0..limit {
+        if game.winner().is_some() {
+            return;
+        }
+        if let Some(action) = quick_action(game, rng) {
+            game.apply(action).expect("quick actions are validated");
+            continue;
+        }
+        let legal = game.legal_actions();
+        let Some(action) = legal.choose(rng) else {
+            return;
+        };
+        game.apply(action.clone()).expect("legal actions apply");
+    }
+}
+
+/// A random action found without listing every legal one, or `None` when the phase
+/// needs the full list.
+fn quick_action(game: &Game, rng: &mut impl Rng) -> Option<Action> {
+    let Phase::Turn {
+        player,
+        turn_phase,
+        development_phase,
+    } = game.phase
+    else {
+        return None;
+    };
+    let no_card_active = matches!(
+        development_phase,
+        DevelopmentPhase::Ready | DevelopmentPhase::DevelopmentPlayed
+    );
+    match turn_phase {
+        TurnPhase::PreRoll if no_card_active => Some(Action::RollDice),
+        TurnPhase::Discard(discarding) => {
+            let p = &game.players[discarding];
+            let mut cards: Vec<usize> = (0..CARD_KINDS)
+                .flat_map(|kind| std::iter::repeat_n(kind, p.resources.counts()[kind] as usize))
+                .collect();
+            cards.shuffle(rng);
+            let mut counts = [0; CARD_KINDS];
+            for &kind in &cards[..(p.hand_size() / 2) as usize] {
+                counts[kind] += 1;
+            }
+            Some(Action::Discard(ResourceGroup::from_counts(counts)))
+        }
+        TurnPhase::GoldChoice { .. } => {
+            let available: Vec<Resource> = Resource::ALL
+                .into_iter()
+                .filter(|&r| game.bank.get(r) > 0)
+                .collect();
+            available.choose(rng).map(|&r| Action::TakeResource(r))
+        }
+        TurnPhase::Free if no_card_active => {
+            let p = &game.players[player];
+            let mut cities: Vec<Action> = p
+                .buildings
+                .iter()
+                .filter(|v| game.board.vertices[v].build_type == BuildType::Settlement)
+                .map(|&v| Action::BuildCity(v))
+                .collect();
+            let ends: Vec<_> = p.paths.iter().flat_map(|c| c.vertices()).collect();
+            let mut settlements: Vec<Action> =
+                ends.iter().map(|&v| Action::BuildSettlement(v)).collect();
+            let mut roads: Vec<Action> = ends
+                .iter()
+                .flat_map(|&v| game.board.get_adjacent_paths(v))
+                .map(Action::BuildRoad)
+                .collect();
+            cities.shuffle(rng);
+            settlements.shuffle(rng);
+            roads.shuffle(rng);
+            cities
+                .into_iter()
+                .chain(settlements)
+                .chain(std::iter::once(Action::BuyDevelopmentCard))
+                .chain(roads.into_iter().take(2))
+                .find(|action| game.validate(action).is_ok())
+                .or(Some(Action::EndTurn))
+        }
+        _ => None,
+    }
+}
+
+/// Each player's share of the outcome: everything to the winner, otherwise their points
+/// towards winning.
+fn rewards(game: &Game) -> Vec<f64> {
+    let players = game.players.len();
+    match game.winner() {
+        Some(winner) => (0..players)
+            .map(|p| f64::from(u8::from(p == winner)))
+            .collect(),
+        None => (0..players)
+            .map(|p| f64::from(game.victory_points(p)) / f64::from(game.rules.victory_points))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        agent::play_game, board::Board, cities_knights::CitiesKnights, player::Player, rules::Rules,
+    };
+
+    use super::*;
+
+    fn placed_game(rules: Rules) -> Game {
+        let mut game = Game::with_rules(Player::init_players(4), Board::new(), rules, 3).unwrap();
+        let mut agents: Vec<Box<dyn Agent>> = (0..4)
+            .map(|_| Box::new(HeuristicAgent::new()) as Box<dyn Agent>)
+            .collect();
+        play_game(&mut game, &mut agents, 16).unwrap();
+        game.phase = Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::Free,
+            development_phase: DevelopmentPhase::Ready,
+        };
+        game
+    }
+
+    fn totals(game: &Game, players: &[usize]) -> ResourceGroup {
+        players
+            .iter()
+            .map(|&p| game.players[p].resources)
+            .fold(ResourceGroup::empty(), |a, b| a + b)
+    }
+
+    #[test]
+    fn test_determinize_keeps_what_the_observer_knows() {
+        let mut game = placed_game(Rules::default());
+        game.players[0].resources = ResourceGroup::new(1, 2, 0, 0, 0);
+        game.players[1].resources = ResourceGroup::new(3, 0, 0, 0, 0);
+        game.players[2].resources = ResourceGroup::new(0, 0, 2, 2, 0);
+        game.players[3].development_cards = vec![crate::deck::DevelopmentCard::Monopoly];
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut moved = false;
+        for _ in 0..20 {
+            let sample = game.determinize(0, &mut rng);
+            assert_eq!(sample.players[0].resources, game.players[0].resources);
+            assert_eq!(totals(&sample, &[1, 2, 3]), totals(&game, &[1, 2, 3]));
+            for p in 1..4 {
+                assert_eq!(sample.players[p].hand_size(), game.players[p].hand_size());
+            }
+            assert_eq!(sample.players[3].development_cards.len(), 1);
+            assert_eq!(sample.deck.len(), game.deck.len());
+            assert_eq!(sample.bank, game.bank);
+            assert!(!sample.can_undo());
+            moved |= sample.players[1].resources != game.players[1].resources;
+        }
+        assert!(moved);
+    }
+
+    #[test]
+    fn test_determinize_hides_who_held_what() {
+        let mut game = placed_game(Rules::default());
+        game.players[1].resources = ResourceGroup::new(3, 0, 0, 0, 0);
+        game.players[2].resources = ResourceGroup::new(0, 0, 2, 1, 0);
+        let mut swapped = placed_game(Rules::default());
+        swapped.players[1].resources = ResourceGroup::new(1, 0, 2, 0, 0);
+        swapped.players[2].resources = ResourceGroup::new(2, 0, 0, 1, 0);
+        swapped.deck =
+            crate::deck::Deck::from_cards(game.deck.cards().iter().rev().copied().collect());
+
+        let sample = game.determinize(0, &mut ChaCha8Rng::seed_from_u64(7));
+        let other = swapped.determinize(0, &mut ChaCha8Rng::seed_from_u64(7));
+        for p in 1..4 {
+            assert_eq!(sample.players[p].resources, other.players[p].resources);
+        }
+        assert_eq!(sample.deck.cards(), other.deck.cards());
+        assert_eq!(sample.seed(), other.seed());
+        assert_ne!(sample.seed(), game.seed());
+    }
+
+    #[test]
+    fn test_determinize_deals_progress_cards() {
+        let mut game = Game::with_cities_and_knights(
+            Player::init_players(3),
+            Board::new(),
+            Rules::cities_and_knights(),
+            4,
+        )
+        .unwrap();
+        let ck = game.cities_knights.as_mut().unwrap();
+        let science = &mut ck.progress_decks[Track::Science as usize];
+        let mine = science.pop().unwrap();
+        ck.progress_cards[0] = vec![mine];
+        ck.progress_cards[1] = vec![ProgressCard::Warlord, ProgressCard::ResourceMonopoly];
+        ck.progress_cards[2] = vec![ProgressCard::Wedding];
+        let ck = game.cities_knights().unwrap().clone();
+        let track_counts = |cards: &[ProgressCard]| {
+            Track::ALL.map(|track| cards.iter().filter(|c| c.track() == track).count())
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut moved = false;
+        for _ in 0..20 {
+            let sample = game.determinize(0, &mut rng);
+            let dealt = sample.cities_knights().unwrap();
+            assert_eq!(dealt.progress_cards[0], vec![mine]);
+            for p in 1..3 {
+                assert_eq!(
+                    track_counts(&dealt.progress_cards[p]),
+                    track_counts(&ck.progress_cards[p])
+                );
+            }
+            for track in Track::ALL {
+                let pooled = |ck: &CitiesKnights| {
+                    let mut cards = ck.progress_decks[track as usize].clone();
+                    cards.extend(
+                        ck.progress_cards[1..]
+                            .iter()
+                            .flatten()
+                            .filter(|c| c.track() == track),
+                    );
+                    cards.sort_by_key(|&c| c as usize);
+                    cards
+                };
+                assert_eq!(pooled(dealt), pooled(&ck));
+            }
+            moved |= dealt.progress_cards[2] != ck.progress_cards[2];
+        }
+        assert!(moved);
+    }
+
+    #[test]
+    fn test_finds_the_winning_build() {
+        let rules = Rules {
+            victory_points: 3,
+            ..Rules::default()
+        };
+        let mut game = placed_game(rules);
+        game.players[0].resources = BuildType::City.cost();
+        let legal = game.legal_actions();
+        let config = MctsConfig {
+            budget: Budget::Iterations(3 * legal.len()),
+            rollout_limit: 50,
+            ..MctsConfig::default()
+        };
+        let action = MctsAgent::new(config, 0).search(&Belief::new(&game), &legal);
+        assert!(matches!(action, Action::BuildCity(_)), "{action:?}");
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let game = placed_game(Rules::default());
+        let legal = game.legal_actions();
+        let config = MctsConfig {
+            budget: Budget::Time(Duration::from_millis(50)),
+            rollout_limit: 20,
+            ..MctsConfig::default()
+        };
+        let start = Instant::now();
+        let action = MctsAgent::new(config, 0).search(&Belief::new(&game), &legal);
+        assert!(legal.contains(&action));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
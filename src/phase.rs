@@ -1,4 +1,6 @@
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Phase {
     InitialPlacement {
         player: usize,
@@ -39,6 +41,21 @@ impl Phase {
                 turn_phase: TurnPhase::Discard(player),
                 development_phase: _,
             } => *player,
+            Phase::Turn {
+                player: _,
+                turn_phase: TurnPhase::TradeOffer(player),
+                development_phase: _,
+            } => *player,
+            Phase::Turn {
+                player: _,
+                turn_phase: TurnPhase::SpecialBuild(player),
+                development_phase: _,
+            } => *player,
+            Phase::Turn {
+                player: _,
+                turn_phase: TurnPhase::GoldChoice { player, left: _ },
+                development_phase: _,
+            } => *player,
             Phase::Turn {
                 player,
                 turn_phase: _,
@@ -48,16 +65,7 @@ impl Phase {
         }
     }
     pub fn is_turn(&self) -> bool {
-        if let Phase::Turn {
-            player: _,
-            turn_phase: _,
-            development_phase: _,
-        } = self
-        {
-            true
-        } else {
-            false
-        }
+        matches!(self, Phase::Turn { .. })
     }
 
     pub fn is_thief(&self) -> bool {
@@ -75,15 +83,25 @@ impl Phase {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TurnPhase {
     PreRoll,
     Discard(usize),
     MoveThief,
     Free,
+    /// Waiting for the player offered a trade to accept or reject it.
+    TradeOffer(usize),
+    /// In 5-6 player games, another player building after the turn has ended.
+    SpecialBuild(usize),
+    /// A player next to a gold field that produced picking the `left` resources they
+    /// are owed, one at a time.
+    GoldChoice {
+        player: usize,
+        left: i32,
+    },
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DevelopmentPhase {
     Ready,
     KnightActive,
@@ -92,18 +110,36 @@ pub enum DevelopmentPhase {
     DevelopmentPlayed,
 }
 
+impl DevelopmentPhase {
+    /// Whether a played card still has to be resolved before anything else can happen.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            *self,
+            DevelopmentPhase::KnightActive
+                | DevelopmentPhase::RoadBuildingActive { .. }
+                | DevelopmentPhase::YearOfPlentyActive { .. }
+        )
+    }
+}
+
 impl TurnPhase {
     pub fn unbound(&self) -> bool {
-        match *self {
-            TurnPhase::PreRoll | TurnPhase::Free => true,
-            _ => false,
-        }
+        matches!(*self, TurnPhase::PreRoll | TurnPhase::Free)
+    }
+
+    pub fn is_trade_offer(&self) -> bool {
+        matches!(*self, TurnPhase::TradeOffer(_))
     }
 
     pub fn is_discard(&self) -> bool {
-        match *self {
-            TurnPhase::Discard(_) => true,
-            _ => false,
-        }
+        matches!(*self, TurnPhase::Discard(_))
+    }
+
+    pub fn is_special_build(&self) -> bool {
+        matches!(*self, TurnPhase::SpecialBuild(_))
+    }
+
+    pub fn is_gold_choice(&self) -> bool {
+        matches!(*self, TurnPhase::GoldChoice { .. })
     }
 }
@@ -1,11 +1,24 @@
-use crate::{axial::Axial, edge::PathCoords, resource::ResourceGroup, vertex::Vertex};
+use serde::{Deserialize, Serialize};
+
+use crate::{axial::Axial, deck::DevelopmentCard, edge::PathCoords, resource::ResourceGroup};
 type PlayerId = usize;
-#[derive(Debug)]
+
+pub const MAX_SETTLEMENTS: usize = 5;
+pub const MAX_CITIES: usize = 4;
+pub const MAX_ROADS: usize = 15;
+pub const MAX_SHIPS: usize = 15;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: PlayerId,
     pub buildings: Vec<Axial>,
     pub paths: Vec<PathCoords>,
     pub resources: ResourceGroup,
+    /// Development cards that can be played, bought on an earlier turn.
+    pub development_cards: Vec<DevelopmentCard>,
+    /// Development cards bought this turn, which can't be played until the next one.
+    pub new_development_cards: Vec<DevelopmentCard>,
+    pub knights_played: u32,
 }
 impl Player {
     pub fn new(id: usize) -> Self {
@@ -14,9 +27,23 @@ impl Player {
             buildings: Vec::new(),
             paths: Vec::new(),
             resources: ResourceGroup::empty(),
+            development_cards: Vec::new(),
+            new_development_cards: Vec::new(),
+            knights_played: 0,
         }
     }
     pub fn init_players(size: usize) -> Vec<Player> {
-        Vec::from_iter((0..size).map(|i| Player::new(i)))
+        Vec::from_iter((0..size).map(Player::new))
+    }
+    pub fn hand_size(&self) -> i32 {
+        self.resources.total()
+    }
+    /// Victory point cards are kept hidden until they win the game.
+    pub fn victory_point_cards(&self) -> u32 {
+        self.development_cards
+            .iter()
+            .chain(self.new_development_cards.iter())
+            .filter(|&&card| card == DevelopmentCard::VictoryPoint)
+            .count() as u32
     }
 }
@@ -0,0 +1,158 @@
+use crate::{axial::Axial, board::Board, dice::outcomes, resource::Resource, vertex::BuildType};
+
+/// Expected cards of each resource per roll, indexed like `Resource::ALL`.
+pub type Production = [f64; 5];
+
+impl Board {
+    /// The cards a building at `pos` collects per roll on average, for a settlement and
+    /// half of what a city collects. The hex the robber is on pays nothing.
+    pub fn expected_vertex_production(&self, pos: Axial) -> Production {
+        let mut production = [0.0; 5];
+        for hex in self.get_adjacent_hexes(pos) {
+            if hex.pos == self.robber {
+                continue;
+            }
+            if let Some(resource) = hex.terrain.resource() {
+                production[resource.index()] += outcomes(hex.number) as f64 / 36.0;
+            }
+        }
+        production
+    }
+
+    /// The resources a building at `pos` collects, leaving out hexes that never pay.
+    pub fn producing(&self, pos: Axial) -> Vec<Resource> {
+        let production = self.expected_vertex_production(pos);
+        Resource::ALL
+            .into_iter()
+            .filter(|&r| production[r.index()] > 0.0)
+            .collect()
+    }
+
+    /// The cards `player`'s settlements and cities collect per roll on average, cities
+    /// counting double.
+    pub fn expected_production(&self, player: usize) -> Production {
+        let mut production = [0.0; 5];
+        for v in self.vertices.values().filter(|v| v.owner == Some(player)) {
+            let size = match v.build_type {
+                BuildType::City => 2.0,
+                BuildType::Settlement => 1.0,
+                BuildType::Knight { .. } | BuildType::None => continue,
+            };
+            for (total, cards) in production
+                .iter_mut()
+                .zip(self.expected_vertex_production(v.pos))
+            {
+                *total += size * cards;
+            }
+        }
+        production
+    }
+
+    /// How evenly the best starting spots are spread, from 0 to 1 for perfectly fair.
+    /// `players` pick the free spot with the highest expected production in snake order,
+    /// twice each as in the initial placement, and the score is the production of the
+    /// worst start divided by that of the best.
+    pub fn fairness(&self, players: usize) -> f64 {
+        let mut spots: Vec<(f64, Axial)> = self
+            .vertices
+            .keys()
+            .filter(|&&v| self.is_on_land(v))
+            .map(|&v| (self.expected_vertex_production(v).iter().sum(), v))
+            .collect();
+        spots.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        let mut taken: Vec<Axial> = Vec::new();
+        let mut starts = vec![0.0; players];
+        let order = (0..players).chain((0..players).rev());
+        for player in order {
+            let free = spots.iter().find(|(_, v)| {
+                !taken.contains(v)
+                    && !self
+                        .get_adjacent_vertices(*v)
+                        .iter()
+                        .any(|n| taken.contains(&n.pos))
+            });
+            if let Some(&(production, v)) = free {
+                starts[player] += production;
+                taken.push(v);
+            }
+        }
+        let best = starts.iter().copied().fold(0.0, f64::max);
+        let worst = starts.iter().copied().fold(f64::INFINITY, f64::min);
+        if best > 0.0 {
+            worst / best
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::hex::Hex;
+
+    use super::*;
+
+    /// A corner of a productive hex, with the hex.
+    fn productive_corner(board: &Board) -> (Hex, Axial) {
+        let mut hexes: Vec<&Hex> = board
+            .hexes
+            .values()
+            .filter(|h| h.terrain.resource().is_some() && h.pos != board.robber)
+            .collect();
+        hexes.sort_by_key(|h| h.pos);
+        let hex = *hexes[0];
+        (hex, board.get_adjacent_vertices(hex.pos)[0].pos)
+    }
+
+    #[test]
+    fn test_expected_production() {
+        let mut board = Board::new();
+        let (hex, corner) = productive_corner(&board);
+        let resource = hex.terrain.resource().unwrap();
+        let alone = outcomes(hex.number) as f64 / 36.0;
+        assert!(board.expected_vertex_production(corner)[resource.index()] >= alone);
+
+        assert_eq!(board.expected_production(0), [0.0; 5]);
+        board
+            .place_building(0, corner, BuildType::Settlement, false)
+            .unwrap();
+        let settlement = board.expected_production(0);
+        assert_eq!(settlement, board.expected_vertex_production(corner));
+        let total: f64 = settlement.iter().sum();
+        let expected: f64 = board
+            .get_adjacent_hexes(corner)
+            .iter()
+            .filter(|h| h.terrain.resource().is_some() && h.pos != board.robber)
+            .map(|h| outcomes(h.number) as f64 / 36.0)
+            .sum();
+        assert!((total - expected).abs() < 1e-12);
+
+        board.vertices.get_mut(&corner).unwrap().build_type = BuildType::City;
+        let city = board.expected_production(0);
+        assert!((city[resource.index()] - 2.0 * settlement[resource.index()]).abs() < 1e-12);
+
+        board.robber = hex.pos;
+        let robbed = board.expected_production(0);
+        assert!((city[resource.index()] - robbed[resource.index()] - 2.0 * alone).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fairness() {
+        let board = Board::new();
+        assert_eq!(board.fairness(1), 1.0);
+        let fairness = board.fairness(4);
+        assert!(fairness > 0.0 && fairness <= 1.0, "{fairness}");
+        assert_eq!(board.fairness(4), fairness);
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let scores: Vec<f64> = (0..10)
+            .map(|_| Board::generate(&mut rng).fairness(4))
+            .collect();
+        assert!(scores.iter().all(|&s| s > 0.0 && s <= 1.0));
+        assert!(scores.iter().any(|&s| s != scores[0]));
+    }
+}
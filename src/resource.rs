@@ -1,45 +1,256 @@
-use std::collections::HashMap;
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Index, IndexMut, Mul, Sub, SubAssign},
+};
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Resource {
-    None,
     Ore,
     Wheat,
     Sheep,
     Brick,
     Wood,
 }
+impl Resource {
+    /// Every resource that can be held as a card.
+    pub const ALL: [Resource; 5] = [
+        Resource::Ore,
+        Resource::Wheat,
+        Resource::Sheep,
+        Resource::Brick,
+        Resource::Wood,
+    ];
+
+    /// Position in `Resource::ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Resource::Ore => "ore",
+            Resource::Wheat => "wheat",
+            Resource::Sheep => "sheep",
+            Resource::Brick => "brick",
+            Resource::Wood => "wood",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Cities & Knights trade goods, which cities on forest, pasture and mountains produce
+/// instead of a second resource.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Commodity {
+    Paper,
+    Cloth,
+    Coin,
+}
+impl Commodity {
+    pub const ALL: [Commodity; 3] = [Commodity::Paper, Commodity::Cloth, Commodity::Coin];
+
+    /// The commodity a city collects from `resource`, if any.
+    pub fn from_resource(resource: Resource) -> Option<Commodity> {
+        match resource {
+            Resource::Wood => Some(Commodity::Paper),
+            Resource::Sheep => Some(Commodity::Cloth),
+            Resource::Ore => Some(Commodity::Coin),
+            Resource::Wheat | Resource::Brick => None,
+        }
+    }
+}
+impl fmt::Display for Commodity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Commodity::Paper => "paper",
+            Commodity::Cloth => "cloth",
+            Commodity::Coin => "coin",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Number of different cards a hand can hold: the resources, then the commodities.
+pub const CARD_KINDS: usize = 8;
 
-#[derive(Debug, PartialEq, Eq)]
+/// A count of each resource card, in the order of `Resource::ALL`, and of each Cities &
+/// Knights commodity, which stay at zero in other games. Counts may go negative, e.g. for
+/// the difference between two hands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ResourceGroup {
-    resources: HashMap<Resource, i32>,
+    resources: [i32; 5],
+    commodities: [i32; 3],
 }
 impl ResourceGroup {
     pub fn empty() -> Self {
-        ResourceGroup::new(0, 0, 0, 0, 0)
+        ResourceGroup::default()
     }
 
-    pub(crate) fn add_resource(&mut self, resource_type: Resource, amount: i32) {
-        self.resources
-            .entry(resource_type)
-            .and_modify(|e| *e += amount);
+    pub fn new(ore: i32, wheat: i32, sheep: i32, brick: i32, wood: i32) -> Self {
+        Self {
+            resources: [ore, wheat, sheep, brick, wood],
+            commodities: [0; 3],
+        }
     }
 
-    pub fn new(ore: i32, wheat: i32, sheep: i32, brick: i32, wood: i32) -> Self {
-        let mut resources = HashMap::new();
-        resources.insert(Resource::Ore, ore);
-        resources.insert(Resource::Wheat, wheat);
-        resources.insert(Resource::Sheep, sheep);
-        resources.insert(Resource::Brick, brick);
-        resources.insert(Resource::Wood, wood);
-        Self { resources }
+    /// A group holding `amount` of a single commodity.
+    pub fn of_commodity(commodity: Commodity, amount: i32) -> Self {
+        let mut group = ResourceGroup::empty();
+        group[commodity] = amount;
+        group
+    }
+
+    /// Every count, the resources followed by the commodities.
+    pub fn counts(&self) -> [i32; CARD_KINDS] {
+        let mut counts = [0; CARD_KINDS];
+        counts[..5].copy_from_slice(&self.resources);
+        counts[5..].copy_from_slice(&self.commodities);
+        counts
+    }
+
+    pub fn from_counts(counts: [i32; CARD_KINDS]) -> Self {
+        let mut group = ResourceGroup::empty();
+        group.resources.copy_from_slice(&counts[..5]);
+        group.commodities.copy_from_slice(&counts[5..]);
+        group
+    }
+
+    /// A group holding `amount` of a single resource.
+    pub fn of(resource_type: Resource, amount: i32) -> Self {
+        let mut group = ResourceGroup::empty();
+        group[resource_type] = amount;
+        group
+    }
+
+    pub fn add_resource(&mut self, resource_type: Resource, amount: i32) {
+        self[resource_type] += amount;
+    }
+
+    pub fn get(&self, resource_type: Resource) -> i32 {
+        self.resources[resource_type.index()]
+    }
+
+    /// Every card, commodities included.
+    pub fn total(&self) -> i32 {
+        self.counts().iter().sum()
+    }
+
+    /// Resource cards only, leaving out commodities.
+    pub fn resource_total(&self) -> i32 {
+        self.resources.iter().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts().iter().all(|&n| n == 0)
+    }
+
+    /// Whether no count is negative.
+    pub fn is_non_negative(&self) -> bool {
+        self.counts().iter().all(|&n| n >= 0)
+    }
+
+    pub fn can_afford(&self, cost: &ResourceGroup) -> bool {
+        self.checked_sub(cost).is_some()
+    }
+
+    /// `self - rhs`, or `None` if that would leave any count negative.
+    pub fn checked_sub(&self, rhs: &ResourceGroup) -> Option<ResourceGroup> {
+        let rest = *self - *rhs;
+        rest.is_non_negative().then_some(rest)
+    }
+
+    /// Each resource with its count, in the order of `Resource::ALL`.
+    pub fn iter(&self) -> impl Iterator<Item = (Resource, i32)> + '_ {
+        Resource::ALL
+            .into_iter()
+            .zip(self.resources.iter().copied())
+    }
+
+    /// Each commodity with its count, in the order of `Commodity::ALL`.
+    pub fn commodities(&self) -> impl Iterator<Item = (Commodity, i32)> + '_ {
+        Commodity::ALL
+            .into_iter()
+            .zip(self.commodities.iter().copied())
+    }
+}
+impl Index<Resource> for ResourceGroup {
+    type Output = i32;
+
+    fn index(&self, resource_type: Resource) -> &i32 {
+        &self.resources[resource_type.index()]
+    }
+}
+impl IndexMut<Resource> for ResourceGroup {
+    fn index_mut(&mut self, resource_type: Resource) -> &mut i32 {
+        &mut self.resources[resource_type.index()]
+    }
+}
+impl Index<Commodity> for ResourceGroup {
+    type Output = i32;
+
+    fn index(&self, commodity: Commodity) -> &i32 {
+        &self.commodities[commodity as usize]
+    }
+}
+impl IndexMut<Commodity> for ResourceGroup {
+    fn index_mut(&mut self, commodity: Commodity) -> &mut i32 {
+        &mut self.commodities[commodity as usize]
+    }
+}
+impl Add for ResourceGroup {
+    type Output = ResourceGroup;
+
+    fn add(mut self, rhs: ResourceGroup) -> ResourceGroup {
+        self += rhs;
+        self
     }
 }
-impl std::ops::AddAssign<ResourceGroup> for ResourceGroup {
+impl AddAssign for ResourceGroup {
     fn add_assign(&mut self, rhs: ResourceGroup) {
-        for (resource_type, amount) in rhs.resources {
-            self.add_resource(resource_type, amount);
+        let mut counts = self.counts();
+        for (a, b) in counts.iter_mut().zip(rhs.counts()) {
+            *a += b;
+        }
+        *self = ResourceGroup::from_counts(counts);
+    }
+}
+impl Sub for ResourceGroup {
+    type Output = ResourceGroup;
+
+    fn sub(mut self, rhs: ResourceGroup) -> ResourceGroup {
+        self -= rhs;
+        self
+    }
+}
+impl SubAssign for ResourceGroup {
+    fn sub_assign(&mut self, rhs: ResourceGroup) {
+        *self += rhs * -1;
+    }
+}
+impl Mul<i32> for ResourceGroup {
+    type Output = ResourceGroup;
+
+    fn mul(self, rhs: i32) -> ResourceGroup {
+        ResourceGroup::from_counts(self.counts().map(|n| n * rhs))
+    }
+}
+impl fmt::Display for ResourceGroup {
+    /// Lists the non-zero counts, e.g. "2 ore, 1 wood".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("nothing");
         }
+        let resources = self.iter().map(|(r, n)| (r.to_string(), n));
+        let commodities = self.commodities().map(|(c, n)| (c.to_string(), n));
+        let parts: Vec<String> = resources
+            .chain(commodities)
+            .filter(|&(_, n)| n != 0)
+            .map(|(name, n)| format!("{} {}", n, name))
+            .collect();
+        f.write_str(&parts.join(", "))
     }
 }
 #[cfg(test)]
@@ -51,7 +262,7 @@ mod tests {
         let mut resource_group = ResourceGroup::empty();
         resource_group.add_resource(Resource::Wood, 3);
 
-        assert_eq!(resource_group.resources[&Resource::Wood], 3);
+        assert_eq!(resource_group[Resource::Wood], 3);
     }
 
     #[test]
@@ -67,7 +278,47 @@ mod tests {
 
         resource_group1 += resource_group2;
 
-        assert_eq!(resource_group1.resources[&Resource::Wood], 5);
-        assert_eq!(resource_group1.resources[&Resource::Ore], 3);
+        assert_eq!(resource_group1[Resource::Wood], 5);
+        assert_eq!(resource_group1[Resource::Ore], 3);
+    }
+
+    #[test]
+    fn test_checked_sub_and_can_afford() {
+        let hand = ResourceGroup::new(1, 0, 0, 2, 2);
+        let road = ResourceGroup::new(0, 0, 0, 1, 1);
+
+        assert!(hand.can_afford(&road));
+        assert_eq!(
+            hand.checked_sub(&road),
+            Some(ResourceGroup::new(1, 0, 0, 1, 1))
+        );
+        assert!(!hand.can_afford(&(road * 3)));
+        assert_eq!(hand.checked_sub(&(road * 3)), None);
+        assert_eq!((road * 3).total(), 6);
+    }
+
+    #[test]
+    fn test_iter_and_display() {
+        let group = ResourceGroup::of(Resource::Ore, 2) + ResourceGroup::of(Resource::Wood, 1);
+
+        assert_eq!(
+            group.iter().filter(|&(_, n)| n > 0).collect::<Vec<_>>(),
+            vec![(Resource::Ore, 2), (Resource::Wood, 1)]
+        );
+        assert_eq!(group.to_string(), "2 ore, 1 wood");
+        assert_eq!(ResourceGroup::empty().to_string(), "nothing");
+    }
+
+    #[test]
+    fn test_commodities_count_as_cards() {
+        let hand =
+            ResourceGroup::of(Resource::Ore, 1) + ResourceGroup::of_commodity(Commodity::Coin, 2);
+
+        assert_eq!(hand.total(), 3);
+        assert_eq!(hand.resource_total(), 1);
+        assert_eq!(hand[Commodity::Coin], 2);
+        assert!(!hand.can_afford(&ResourceGroup::of_commodity(Commodity::Coin, 3)));
+        assert_eq!(ResourceGroup::from_counts(hand.counts()), hand);
+        assert_eq!(hand.to_string(), "1 ore, 2 coin");
     }
 }
@@ -0,0 +1,1535 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::Action,
+    axial::Axial,
+    cities_knights::Track,
+    deck::DevelopmentCard,
+    edge::{PathCoords, PathType},
+    error::RuleError,
+    game::{Game, TradeOffer, VICTORY_POINTS},
+    phase::{DevelopmentPhase, Phase, TurnPhase},
+    player::{MAX_CITIES, MAX_ROADS, MAX_SETTLEMENTS, MAX_SHIPS},
+    resource::{Commodity, Resource, ResourceGroup, CARD_KINDS},
+    seafarers::ShipTurn,
+    vertex::BuildType,
+};
+
+/// House rules a game is played with. The default is the standard game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    /// Points needed to win on your own turn.
+    pub victory_points: u32,
+    /// Players with fewer public points than this can't be robbed, `0` for the usual
+    /// robber.
+    pub friendly_robber: u32,
+    /// Players holding more cards than this discard half of them when a 7 is rolled.
+    pub discard_limit: i32,
+    /// 7s are rolled again during this many rounds at the start of the game.
+    pub no_seven_rounds: u32,
+    pub bank_trades: bool,
+    pub player_trades: bool,
+    /// Roll with `Dice::deck` rather than random dice.
+    pub dice_deck: bool,
+}
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            victory_points: VICTORY_POINTS,
+            friendly_robber: 0,
+            discard_limit: 7,
+            no_seven_rounds: 0,
+            bank_trades: true,
+            player_trades: true,
+            dice_deck: false,
+        }
+    }
+}
+
+/// Every way of choosing `count` cards out of `hand`.
+pub(crate) fn selections(hand: &ResourceGroup, count: i32) -> Vec<ResourceGroup> {
+    fn choose(
+        hand: &[i32; CARD_KINDS],
+        left: i32,
+        from: usize,
+        chosen: &mut [i32; CARD_KINDS],
+        out: &mut Vec<ResourceGroup>,
+    ) {
+        if from == CARD_KINDS {
+            if left == 0 {
+                out.push(ResourceGroup::from_counts(*chosen));
+            }
+            return;
+        }
+        for n in 0..=left.min(hand[from]) {
+            chosen[from] = n;
+            choose(hand, left - n, from + 1, chosen, out);
+        }
+        chosen[from] = 0;
+    }
+    let mut out = Vec::new();
+    choose(&hand.counts(), count, 0, &mut [0; CARD_KINDS], &mut out);
+    out
+}
+
+impl Game {
+    /// Checks `action` for the current player and applies it, adding it to the log.
+    /// Nothing changes when the action isn't allowed.
+    pub fn apply(&mut self, action: Action) -> Result<(), RuleError> {
+        self.validate(&action)?;
+        self.redo.clear();
+        self.commit(action);
+        Ok(())
+    }
+
+    /// Like `apply`, but refuses the action unless it is `player`'s turn to act.
+    pub fn apply_as(&mut self, player: usize, action: Action) -> Result<(), RuleError> {
+        let current = self.phase.player();
+        if player != current {
+            return Err(RuleError::NotYourTurn { current });
+        }
+        self.apply(action)
+    }
+
+    /// Applies a validated action and adds it to the log.
+    pub(crate) fn commit(&mut self, action: Action) {
+        self.record_undo(&action);
+        self.execute(action.clone());
+        self.log.push(action);
+        self.check_winner();
+        if self.winner().is_some() {
+            // the game is over, so the winning action can't be taken back
+            self.undo.clear();
+            self.redo.clear();
+        }
+    }
+
+    /// Every action the current player can take. Trade offers to other players are never
+    /// listed since any combination of cards can be offered.
+    pub fn legal_actions(&self) -> Vec<Action> {
+        let player = self.phase.player();
+        let mut vertices: Vec<Axial> = self.board.vertices.keys().cloned().collect();
+        vertices.sort();
+        let mut edges: Vec<PathCoords> = self.board.edges.keys().cloned().collect();
+        edges.sort();
+        let seafarers = self.board.has_sea();
+        let paths = |edges: &[PathCoords]| {
+            let mut paths: Vec<Action> = edges.iter().cloned().map(Action::BuildRoad).collect();
+            if seafarers {
+                paths.extend(edges.iter().cloned().map(Action::BuildShip));
+            }
+            paths
+        };
+
+        let mut candidates = Vec::new();
+        match self.phase {
+            Phase::FinishedGame { .. } => {}
+            Phase::InitialPlacement { placing_road, .. } => {
+                if placing_road {
+                    candidates.extend(paths(&edges));
+                } else {
+                    candidates.extend(vertices.into_iter().map(Action::BuildSettlement));
+                }
+            }
+            Phase::Turn {
+                turn_phase: TurnPhase::Discard(_),
+                ..
+            } => {
+                let hand = &self.players[player].resources;
+                candidates.extend(
+                    selections(hand, hand.total() / 2)
+                        .into_iter()
+                        .map(Action::Discard),
+                );
+            }
+            Phase::Turn {
+                turn_phase: TurnPhase::GoldChoice { .. },
+                ..
+            } => {
+                candidates.extend(Resource::ALL.map(Action::TakeResource));
+            }
+            Phase::Turn {
+                turn_phase: TurnPhase::TradeOffer(_),
+                ..
+            } => {
+                candidates.push(Action::AcceptTrade);
+                candidates.push(Action::RejectTrade);
+            }
+            Phase::Turn { .. } if self.phase.is_thief() => {
+                let mut hexes: Vec<Axial> = self.board.hexes.keys().cloned().collect();
+                hexes.sort();
+                for pos in hexes {
+                    let victims = self.robber_victims(pos);
+                    if victims.is_empty() {
+                        candidates.push(Action::MoveRobber { pos, victim: None });
+                    }
+                    for victim in victims {
+                        candidates.push(Action::MoveRobber {
+                            pos,
+                            victim: Some(victim),
+                        });
+                    }
+                    let victims = self.pirate_victims(pos);
+                    if victims.is_empty() {
+                        candidates.push(Action::MovePirate { pos, victim: None });
+                    }
+                    for victim in victims {
+                        candidates.push(Action::MovePirate {
+                            pos,
+                            victim: Some(victim),
+                        });
+                    }
+                }
+            }
+            Phase::Turn {
+                turn_phase,
+                development_phase,
+                ..
+            } => {
+                candidates.extend([
+                    Action::RollDice,
+                    Action::EndTurn,
+                    Action::BuyDevelopmentCard,
+                    Action::PlayKnight,
+                    Action::PlayRoadBuilding,
+                    Action::PlayYearOfPlenty,
+                ]);
+                for r in Resource::ALL {
+                    candidates.push(Action::PlayMonopoly(r));
+                    candidates.push(Action::TakeResource(r));
+                    for get in Resource::ALL {
+                        candidates.push(Action::BankTrade { give: r, get });
+                    }
+                }
+                // Building is only ever allowed once the dice are rolled.
+                if turn_phase == TurnPhase::Free || turn_phase.is_special_build() {
+                    if !development_phase.is_active() {
+                        for &pos in vertices.iter() {
+                            candidates.push(Action::BuildSettlement(pos));
+                            candidates.push(Action::BuildCity(pos));
+                        }
+                    }
+                    if let Some(ck) = &self.cities_knights {
+                        for &pos in vertices.iter() {
+                            candidates.push(Action::BuildKnight(pos));
+                            candidates.push(Action::ActivateKnight(pos));
+                            candidates.push(Action::PromoteKnight(pos));
+                        }
+                        candidates.extend(Track::ALL.map(Action::ImproveCity));
+                        for (from, _, _) in self.board.knights(player) {
+                            candidates.push(Action::ChaseRobber(from));
+                            candidates.extend(
+                                self.board
+                                    .knight_moves(player, from)
+                                    .into_iter()
+                                    .map(|to| Action::MoveKnight { from, to }),
+                            );
+                        }
+                        for give in Commodity::ALL {
+                            candidates.extend(
+                                Resource::ALL.map(|get| Action::CommodityTrade { give, get }),
+                            );
+                        }
+                        let mut cards = ck.progress_cards[player].clone();
+                        cards.sort_by_key(|&card| card as usize);
+                        cards.dedup();
+                        for card in cards {
+                            if card.names_resource() {
+                                candidates.extend(Resource::ALL.map(|r| {
+                                    Action::PlayProgressCard {
+                                        card,
+                                        resource: Some(r),
+                                    }
+                                }));
+                            } else {
+                                candidates.push(Action::PlayProgressCard {
+                                    card,
+                                    resource: None,
+                                });
+                            }
+                        }
+                    }
+                    candidates.extend(paths(&edges));
+                    if seafarers && turn_phase == TurnPhase::Free {
+                        for from in edges.iter() {
+                            if self.board.is_open_ship(player, from) {
+                                candidates.extend(edges.iter().map(|to| Action::MoveShip {
+                                    from: from.clone(),
+                                    to: to.clone(),
+                                }));
+                            }
+                        }
+                    }
+                } else if development_phase.is_active() {
+                    candidates.extend(paths(&edges));
+                }
+            }
+        }
+        candidates
+            .into_iter()
+            .filter(|action| self.validate(action).is_ok())
+            .collect()
+    }
+
+    /// The players with a building on `pos` and cards to steal, other than the one moving
+    /// the robber.
+    pub fn robber_victims(&self, pos: Axial) -> Vec<usize> {
+        let thief = self.phase.player();
+        let mut victims: Vec<usize> = self
+            .board
+            .get_adjacent_vertices(pos)
+            .iter()
+            .filter(|v| v.build_type.is_building())
+            .filter_map(|v| v.owner)
+            .filter(|&owner| owner != thief && self.can_be_robbed(owner))
+            .collect();
+        victims.sort();
+        victims.dedup();
+        victims
+    }
+
+    /// Whether `player` has cards to steal and, with the friendly robber, enough points to
+    /// be stolen from.
+    pub(crate) fn can_be_robbed(&self, player: usize) -> bool {
+        self.players[player].hand_size() > 0
+            && self.public_victory_points(player) >= self.rules.friendly_robber
+    }
+
+    pub(crate) fn validate(&self, action: &Action) -> Result<(), RuleError> {
+        let player = self.phase.player();
+        match self.phase {
+            Phase::FinishedGame { .. } => Err(RuleError::GameOver),
+            Phase::InitialPlacement { placing_road, .. } => match action {
+                Action::BuildSettlement(pos) if !placing_road => {
+                    self.board.validate_settlement(player, *pos, false)
+                }
+                Action::BuildRoad(coords) | Action::BuildShip(coords) if placing_road => {
+                    let path_type = match action {
+                        Action::BuildShip(_) => PathType::Ship,
+                        _ => PathType::Road,
+                    };
+                    self.board
+                        .validate_path(player, coords.clone(), path_type, false)?;
+                    if !self
+                        .unconnected_settlements(player)
+                        .any(|v| coords.contains(v))
+                    {
+                        return Err(RuleError::NotAtNewSettlement);
+                    }
+                    Ok(())
+                }
+                _ => Err(RuleError::WrongPhase),
+            },
+            Phase::Turn {
+                turn_phase,
+                development_phase,
+                ..
+            } => self.validate_turn(player, turn_phase, development_phase, action),
+        }
+    }
+
+    fn validate_turn(
+        &self,
+        player: usize,
+        turn_phase: TurnPhase,
+        development_phase: DevelopmentPhase,
+        action: &Action,
+    ) -> Result<(), RuleError> {
+        let hand = &self.players[player].resources;
+        let free = turn_phase == TurnPhase::Free && !development_phase.is_active();
+        let special_build = turn_phase.is_special_build();
+        let require_free = || {
+            if free {
+                Ok(())
+            } else if special_build {
+                Err(RuleError::WrongPhase)
+            } else {
+                Err(RuleError::TurnNotFree)
+            }
+        };
+        // Building, buying cards and ending the turn are also allowed when building after
+        // another player's turn.
+        let require_build = || {
+            if special_build {
+                Ok(())
+            } else {
+                require_free()
+            }
+        };
+        let require_affordable = |cost: &ResourceGroup| {
+            if hand.can_afford(cost) {
+                Ok(())
+            } else {
+                Err(RuleError::InsufficientResources)
+            }
+        };
+        let require_card = |card: DevelopmentCard| {
+            if !turn_phase.unbound() {
+                Err(RuleError::WrongPhase)
+            } else if development_phase != DevelopmentPhase::Ready {
+                Err(RuleError::CardAlreadyPlayed)
+            } else if !self.players[player].development_cards.contains(&card) {
+                Err(RuleError::NoPlayableCard(card))
+            } else {
+                Ok(())
+            }
+        };
+
+        match action {
+            Action::RollDice => {
+                if turn_phase != TurnPhase::PreRoll || development_phase.is_active() {
+                    return Err(RuleError::WrongPhase);
+                }
+                Ok(())
+            }
+            Action::Discard(group) => {
+                if !turn_phase.is_discard() {
+                    return Err(RuleError::WrongPhase);
+                }
+                if !group.is_non_negative() || group.total() != hand.total() / 2 {
+                    return Err(RuleError::WrongDiscard {
+                        expected: hand.total() / 2,
+                    });
+                }
+                require_affordable(group)
+            }
+            Action::MoveRobber { pos, victim } => {
+                if !self.phase.is_thief() {
+                    return Err(RuleError::WrongPhase);
+                }
+                let on_land = self
+                    .board
+                    .hexes
+                    .get(pos)
+                    .is_some_and(|hex| hex.terrain.is_land());
+                if !on_land || *pos == self.board.robber {
+                    return Err(RuleError::InvalidRobberHex);
+                }
+                let victims = self.robber_victims(*pos);
+                match victim {
+                    None if victims.is_empty() => Ok(()),
+                    Some(v) if victims.contains(v) => Ok(()),
+                    _ => Err(RuleError::InvalidVictim { victims }),
+                }
+            }
+            Action::MovePirate { pos, victim } => {
+                if !self.phase.is_thief() {
+                    return Err(RuleError::WrongPhase);
+                }
+                self.validate_pirate(*pos, *victim)
+            }
+            Action::AcceptTrade | Action::RejectTrade => {
+                let Some(trade) = self.trade.as_ref().filter(|_| turn_phase.is_trade_offer())
+                else {
+                    return Err(RuleError::WrongPhase);
+                };
+                if *action == Action::AcceptTrade {
+                    require_affordable(&trade.get)?;
+                }
+                Ok(())
+            }
+            Action::TakeResource(resource) => {
+                if !turn_phase.is_gold_choice()
+                    && !matches!(
+                        development_phase,
+                        DevelopmentPhase::YearOfPlentyActive { .. }
+                    )
+                {
+                    return Err(RuleError::WrongPhase);
+                }
+                if self.bank.get(*resource) < 1 {
+                    return Err(RuleError::BankEmpty(*resource));
+                }
+                Ok(())
+            }
+            Action::BuildRoad(coords) => {
+                if self.paths(player, PathType::Road) >= MAX_ROADS {
+                    return Err(RuleError::NoRoadsLeft);
+                }
+                if !matches!(
+                    development_phase,
+                    DevelopmentPhase::RoadBuildingActive { .. }
+                ) {
+                    require_build()?;
+                    require_affordable(&PathType::Road.cost())?;
+                }
+                self.board
+                    .validate_path(player, coords.clone(), PathType::Road, true)
+            }
+            Action::BuildShip(coords) => {
+                if self.paths(player, PathType::Ship) >= MAX_SHIPS {
+                    return Err(RuleError::NoShipsLeft);
+                }
+                if !matches!(
+                    development_phase,
+                    DevelopmentPhase::RoadBuildingActive { .. }
+                ) {
+                    require_build()?;
+                    require_affordable(&PathType::Ship.cost())?;
+                }
+                self.board
+                    .validate_path(player, coords.clone(), PathType::Ship, true)
+            }
+            Action::MoveShip { from, to } => {
+                require_free()?;
+                self.validate_ship_move(player, from, to)
+            }
+            Action::BuildSettlement(pos) => {
+                require_build()?;
+                if self.pieces(player, BuildType::Settlement) >= MAX_SETTLEMENTS {
+                    return Err(RuleError::NoSettlementsLeft);
+                }
+                require_affordable(&BuildType::Settlement.cost())?;
+                self.board.validate_settlement(player, *pos, true)
+            }
+            Action::BuildCity(pos) => {
+                require_build()?;
+                if self.pieces(player, BuildType::City) >= MAX_CITIES {
+                    return Err(RuleError::NoCitiesLeft);
+                }
+                require_affordable(&BuildType::City.cost())?;
+                self.board.validate_city(player, *pos)
+            }
+            Action::BuildKnight(pos) => {
+                require_build()?;
+                self.validate_build_knight(player, *pos)
+            }
+            Action::ActivateKnight(pos) => {
+                require_build()?;
+                self.validate_activate_knight(player, *pos)
+            }
+            Action::PromoteKnight(pos) => {
+                require_build()?;
+                self.validate_promote_knight(player, *pos)
+            }
+            Action::ImproveCity(track) => {
+                require_build()?;
+                self.validate_improve_city(player, *track)
+            }
+            Action::MoveKnight { from, to } => {
+                require_free()?;
+                self.validate_move_knight(player, *from, *to)
+            }
+            Action::ChaseRobber(pos) => {
+                require_free()?;
+                self.validate_chase_robber(player, *pos)
+            }
+            Action::CommodityTrade { give, get } => {
+                require_free()?;
+                self.validate_commodity_trade(player, *give, *get)
+            }
+            Action::PlayProgressCard { card, resource } => {
+                require_free()?;
+                self.validate_progress_card(player, *card, *resource)
+            }
+            Action::BuyDevelopmentCard if self.is_cities_and_knights() => Err(RuleError::NotInGame),
+            Action::PlayKnight
+            | Action::PlayRoadBuilding
+            | Action::PlayYearOfPlenty
+            | Action::PlayMonopoly(_)
+                if self.is_cities_and_knights() =>
+            {
+                Err(RuleError::NotInGame)
+            }
+            Action::BuyDevelopmentCard => {
+                require_build()?;
+                if self.deck.is_empty() {
+                    return Err(RuleError::DeckEmpty);
+                }
+                require_affordable(&DevelopmentCard::cost())
+            }
+            Action::PlayKnight => require_card(DevelopmentCard::Knight),
+            Action::PlayRoadBuilding => require_card(DevelopmentCard::RoadBuilding),
+            Action::PlayYearOfPlenty => require_card(DevelopmentCard::YearOfPlenty),
+            Action::PlayMonopoly(_) => require_card(DevelopmentCard::Monopoly),
+            Action::BankTrade { give, get } => {
+                require_free()?;
+                if !self.rules.bank_trades {
+                    return Err(RuleError::TradeNotAllowed);
+                }
+                if give == get {
+                    return Err(RuleError::InvalidBankTrade {
+                        give: *give,
+                        get: *get,
+                    });
+                }
+                if hand.get(*give) < self.trade_ratio(player, *give) {
+                    return Err(RuleError::InsufficientResources);
+                }
+                if self.bank.get(*get) < 1 {
+                    return Err(RuleError::BankEmpty(*get));
+                }
+                Ok(())
+            }
+            Action::OfferTrade { to, give, get } => {
+                require_free()?;
+                if !self.rules.player_trades {
+                    return Err(RuleError::TradeNotAllowed);
+                }
+                if *to == player || *to >= self.players.len() {
+                    return Err(RuleError::InvalidTradePartner(*to));
+                }
+                if !give.is_non_negative() || !get.is_non_negative() {
+                    return Err(RuleError::InvalidTrade);
+                }
+                if give.total() == 0 || get.total() == 0 {
+                    return Err(RuleError::InvalidTrade);
+                }
+                require_affordable(give)
+            }
+            Action::EndTurn => require_build(),
+        }
+    }
+
+    /// Applies an action that has already been validated.
+    fn execute(&mut self, action: Action) {
+        let player = self.phase.player();
+        match action {
+            Action::BuildSettlement(pos) => {
+                let initial = matches!(self.phase, Phase::InitialPlacement { .. });
+                if !initial {
+                    self.pay(player, &BuildType::Settlement.cost());
+                }
+                self.board
+                    .place_building(player, pos, BuildType::Settlement, !initial)
+                    .expect("validated settlement");
+                self.players[player].buildings.push(pos);
+                if let Phase::InitialPlacement {
+                    placing_second,
+                    placing_road,
+                    ..
+                } = &mut self.phase
+                {
+                    if *placing_second {
+                        let resources: Vec<Resource> = self
+                            .board
+                            .get_adjacent_hexes(pos)
+                            .iter()
+                            .filter_map(|hex| hex.terrain.resource())
+                            .collect();
+                        for resource in resources {
+                            if self.bank.get(resource) > 0 {
+                                self.bank.add_resource(resource, -1);
+                                self.players[player].resources.add_resource(resource, 1);
+                            }
+                        }
+                    }
+                    *placing_road = true;
+                }
+                self.update_longest_road();
+            }
+            Action::BuildRoad(coords) => self.build_path(player, coords, PathType::Road),
+            Action::BuildShip(coords) => self.build_path(player, coords, PathType::Ship),
+            Action::BuildCity(pos) => {
+                self.pay(player, &BuildType::City.cost());
+                self.board
+                    .place_building(player, pos, BuildType::City, true)
+                    .expect("validated city");
+            }
+            Action::BuildKnight(pos) => self.build_knight(player, pos),
+            Action::ActivateKnight(pos) => self.activate_knight(player, pos),
+            Action::PromoteKnight(pos) => self.promote_knight(player, pos),
+            Action::ImproveCity(track) => self.improve_city(player, track),
+            Action::MoveKnight { from, to } => self.move_knight(player, from, to),
+            Action::ChaseRobber(pos) => self.chase_robber(pos),
+            Action::CommodityTrade { give, get } => self.commodity_trade(player, give, get),
+            Action::PlayProgressCard { card, resource } => {
+                self.play_progress_card(player, card, resource)
+            }
+            Action::RollDice => {
+                let (mut red, mut yellow) = self.roll_pair();
+                while red + yellow == 7 && self.round() < self.rules.no_seven_rounds {
+                    (red, yellow) = self.roll_pair();
+                }
+                let roll = red + yellow;
+                self.last_roll = Some(roll);
+                self.rolls += 1;
+                self.roll_event(red);
+                if roll == 7 {
+                    let next = match self.next_discard(player, true) {
+                        Some(p) => TurnPhase::Discard(p),
+                        None => self.after_discards(),
+                    };
+                    self.set_turn_phase(next);
+                } else {
+                    self.give_resources_for_roll(roll);
+                    let next = self.next_gold_choice(player, true);
+                    self.set_turn_phase(next.unwrap_or(TurnPhase::Free));
+                }
+            }
+            Action::Discard(group) => {
+                self.pay(player, &group);
+                let next = match self.next_discard(player, false) {
+                    Some(p) => TurnPhase::Discard(p),
+                    None => self.after_discards(),
+                };
+                self.set_turn_phase(next);
+            }
+            Action::MoveShip { from, to } => {
+                self.move_ship(player, from, to);
+                self.update_longest_road();
+            }
+            Action::MoveRobber { pos, victim } => {
+                self.board.robber = pos;
+                self.finish_thief(player, victim);
+            }
+            Action::MovePirate { pos, victim } => {
+                self.board.pirate = Some(pos);
+                self.finish_thief(player, victim);
+            }
+            Action::BuyDevelopmentCard => {
+                self.pay(player, &DevelopmentCard::cost());
+                let card = self.deck.draw().expect("validated deck");
+                self.players[player].new_development_cards.push(card);
+            }
+            Action::PlayKnight => {
+                self.remove_card(player, DevelopmentCard::Knight);
+                self.players[player].knights_played += 1;
+                self.update_largest_army(player);
+                self.set_development_phase(DevelopmentPhase::KnightActive);
+            }
+            Action::PlayRoadBuilding => {
+                self.remove_card(player, DevelopmentCard::RoadBuilding);
+                self.set_development_phase(DevelopmentPhase::RoadBuildingActive { two_left: true });
+                if !self.can_build_free_road(player) {
+                    self.set_development_phase(DevelopmentPhase::DevelopmentPlayed);
+                }
+            }
+            Action::PlayYearOfPlenty => {
+                self.remove_card(player, DevelopmentCard::YearOfPlenty);
+                self.set_development_phase(DevelopmentPhase::YearOfPlentyActive { two_left: true });
+                if self.bank.resource_total() == 0 {
+                    self.set_development_phase(DevelopmentPhase::DevelopmentPlayed);
+                }
+            }
+            Action::PlayMonopoly(resource) => {
+                self.remove_card(player, DevelopmentCard::Monopoly);
+                for other in 0..self.players.len() {
+                    if other != player {
+                        let amount = self.players[other].resources.get(resource);
+                        self.players[other]
+                            .resources
+                            .add_resource(resource, -amount);
+                        self.players[player]
+                            .resources
+                            .add_resource(resource, amount);
+                    }
+                }
+                self.set_development_phase(DevelopmentPhase::DevelopmentPlayed);
+            }
+            Action::TakeResource(resource) => {
+                self.bank.add_resource(resource, -1);
+                self.players[player].resources.add_resource(resource, 1);
+                if let Phase::Turn {
+                    turn_phase: TurnPhase::GoldChoice { left, .. },
+                    ..
+                } = self.phase
+                {
+                    let next = if left > 1 && self.bank.resource_total() > 0 {
+                        Some(TurnPhase::GoldChoice {
+                            player,
+                            left: left - 1,
+                        })
+                    } else {
+                        self.next_gold_choice(player, false)
+                    };
+                    self.set_turn_phase(next.unwrap_or(TurnPhase::Free));
+                } else if matches!(
+                    self.phase,
+                    Phase::Turn {
+                        development_phase: DevelopmentPhase::YearOfPlentyActive { two_left: true },
+                        ..
+                    }
+                ) && self.bank.resource_total() > 0
+                {
+                    self.set_development_phase(DevelopmentPhase::YearOfPlentyActive {
+                        two_left: false,
+                    });
+                } else {
+                    self.set_development_phase(DevelopmentPhase::DevelopmentPlayed);
+                }
+            }
+            Action::BankTrade { give, get } => {
+                let ratio = self.trade_ratio(player, give);
+                let given = ResourceGroup::of(give, ratio);
+                let got = ResourceGroup::of(get, 1);
+                self.players[player].resources += got - given;
+                self.bank += given - got;
+            }
+            Action::OfferTrade { to, give, get } => {
+                self.trade = Some(TradeOffer {
+                    from: player,
+                    to,
+                    give,
+                    get,
+                });
+                self.set_turn_phase(TurnPhase::TradeOffer(to));
+            }
+            Action::AcceptTrade => {
+                let trade = self.trade.take().expect("validated trade");
+                self.players[trade.from].resources += trade.get - trade.give;
+                self.players[trade.to].resources += trade.give - trade.get;
+                self.set_turn_phase(TurnPhase::Free);
+            }
+            Action::RejectTrade => {
+                self.trade = None;
+                self.set_turn_phase(TurnPhase::Free);
+            }
+            Action::EndTurn => {
+                let bought = std::mem::take(&mut self.players[player].new_development_cards);
+                self.players[player].development_cards.extend(bought);
+                self.end_turn();
+            }
+        }
+    }
+
+    fn build_path(&mut self, player: usize, coords: PathCoords, path_type: PathType) {
+        let paid = matches!(
+            self.phase,
+            Phase::Turn {
+                development_phase: DevelopmentPhase::Ready | DevelopmentPhase::DevelopmentPlayed,
+                ..
+            }
+        );
+        if paid {
+            self.pay(player, &path_type.cost());
+        }
+        self.board
+            .place_path(&self.players[player], coords.clone(), path_type, false)
+            .expect("validated path");
+        if path_type == PathType::Ship && self.phase.is_turn() {
+            self.ships.built.push(coords.clone());
+        }
+        self.players[player].paths.push(coords);
+        match self.phase {
+            Phase::InitialPlacement { .. } => self.next_placement(),
+            Phase::Turn {
+                development_phase: DevelopmentPhase::RoadBuildingActive { two_left },
+                ..
+            } => self.continue_road_building(two_left),
+            _ => {}
+        }
+        self.update_longest_road();
+    }
+
+    /// Steals from `victim` once the robber or pirate has moved, and carries on with the
+    /// turn or the knight that moved it.
+    fn finish_thief(&mut self, player: usize, victim: Option<usize>) {
+        if let Some(victim) = victim {
+            self.steal(player, victim);
+        }
+        if let Phase::Turn {
+            turn_phase,
+            development_phase,
+            ..
+        } = &mut self.phase
+        {
+            if *development_phase == DevelopmentPhase::KnightActive {
+                *development_phase = DevelopmentPhase::DevelopmentPlayed;
+            } else {
+                *turn_phase = TurnPhase::Free;
+            }
+        }
+    }
+
+    /// Moves on from the player who just ended their turn or their special build. In 5-6
+    /// player games every other player gets to build before the next turn starts.
+    fn end_turn(&mut self) {
+        let Phase::Turn {
+            player: active,
+            turn_phase,
+            ..
+        } = self.phase
+        else {
+            return;
+        };
+        let n = self.players.len();
+        self.ships = ShipTurn::default();
+        if let Some(ck) = self.cities_knights.as_mut() {
+            ck.merchant_fleet = None;
+        }
+        let builder = match turn_phase {
+            TurnPhase::SpecialBuild(builder) => Some((builder + 1) % n),
+            _ if self.is_extension() => Some((active + 1) % n),
+            _ => None,
+        };
+        self.phase = match builder {
+            Some(builder) if builder != active => Phase::Turn {
+                player: active,
+                turn_phase: TurnPhase::SpecialBuild(builder),
+                development_phase: DevelopmentPhase::Ready,
+            },
+            _ => Phase::Turn {
+                player: (active + 1) % n,
+                turn_phase: TurnPhase::PreRoll,
+                development_phase: DevelopmentPhase::Ready,
+            },
+        };
+    }
+
+    /// Rounds completed before the current turn's roll, counting every player's roll.
+    pub fn round(&self) -> u32 {
+        self.rolls / self.players.len() as u32
+    }
+
+    /// Where a 7 goes once everyone has discarded: on to the robber, unless it is still
+    /// asleep in a Cities & Knights game.
+    fn after_discards(&self) -> TurnPhase {
+        if self.robber_asleep() {
+            TurnPhase::Free
+        } else {
+            TurnPhase::MoveThief
+        }
+    }
+
+    /// Settlements of `player` without a road of theirs, which is where an initial road
+    /// has to go.
+    fn unconnected_settlements(&self, player: usize) -> impl Iterator<Item = Axial> + '_ {
+        self.players[player]
+            .buildings
+            .iter()
+            .cloned()
+            .filter(move |&pos| {
+                !self
+                    .board
+                    .get_adjacent_paths(pos)
+                    .iter()
+                    .any(|p| self.board.edges[p].owner == Some(player))
+            })
+    }
+
+    fn next_placement(&mut self) {
+        let last = self.players.len() - 1;
+        self.phase = match self.phase {
+            Phase::InitialPlacement {
+                player,
+                placing_second: false,
+                ..
+            } => Phase::InitialPlacement {
+                player: (player + 1).min(last),
+                placing_second: player == last,
+                placing_road: false,
+            },
+            Phase::InitialPlacement { player: 0, .. } => Phase::START_TURNS,
+            Phase::InitialPlacement { player, .. } => Phase::InitialPlacement {
+                player: player - 1,
+                placing_second: true,
+                placing_road: false,
+            },
+            phase => phase,
+        };
+    }
+
+    fn continue_road_building(&mut self, two_left: bool) {
+        let player = self.phase.player();
+        if two_left && self.can_build_free_road(player) {
+            self.set_development_phase(DevelopmentPhase::RoadBuildingActive { two_left: false });
+        } else {
+            self.set_development_phase(DevelopmentPhase::DevelopmentPlayed);
+        }
+    }
+
+    fn can_build_free_road(&self, player: usize) -> bool {
+        let can_build = |path_type, max| {
+            self.paths(player, path_type) < max
+                && !self
+                    .board
+                    .get_valid_path_spots(player, path_type, true)
+                    .is_empty()
+        };
+        can_build(PathType::Road, MAX_ROADS) || can_build(PathType::Ship, MAX_SHIPS)
+    }
+
+    /// How many roads or ships `player` has built.
+    fn paths(&self, player: usize, path_type: PathType) -> usize {
+        self.players[player]
+            .paths
+            .iter()
+            .filter(|coords| self.board.edges[*coords].path_type == path_type)
+            .count()
+    }
+
+    /// The next player, going round from the one who rolled, who holds more than seven
+    /// cards and still has to discard.
+    fn next_discard(&self, after: usize, inclusive: bool) -> Option<usize> {
+        let Phase::Turn { player: active, .. } = self.phase else {
+            return None;
+        };
+        let n = self.players.len();
+        let position = (after + n - active) % n;
+        let start = if inclusive { position } else { position + 1 };
+        (start..n)
+            .map(|i| (active + i) % n)
+            .find(|&p| self.players[p].hand_size() > self.rules.discard_limit)
+    }
+
+    /// The next player in turn order from `after` who takes resources from a gold field
+    /// for the last roll, while the bank has any to give.
+    fn next_gold_choice(&self, after: usize, inclusive: bool) -> Option<TurnPhase> {
+        let Phase::Turn { player: active, .. } = self.phase else {
+            return None;
+        };
+        let roll = self.last_roll?;
+        if self.bank.resource_total() == 0 {
+            return None;
+        }
+        let owed = self.board.gold_for_roll(roll);
+        let n = self.players.len();
+        let position = (after + n - active) % n;
+        let start = if inclusive { position } else { position + 1 };
+        (start..n).map(|i| (active + i) % n).find_map(|p| {
+            owed.get(&p)
+                .map(|&left| TurnPhase::GoldChoice { player: p, left })
+        })
+    }
+
+    pub(crate) fn steal(&mut self, thief: usize, victim: usize) {
+        let hand = self.players[victim].resources;
+        let mut card = self.rng.gen_range(0..hand.total());
+        for (i, count) in hand.counts().into_iter().enumerate() {
+            if card < count {
+                let mut taken = [0; CARD_KINDS];
+                taken[i] = 1;
+                let taken = ResourceGroup::from_counts(taken);
+                self.players[victim].resources -= taken;
+                self.players[thief].resources += taken;
+                return;
+            }
+            card -= count;
+        }
+    }
+
+    pub(crate) fn pay(&mut self, player: usize, cost: &ResourceGroup) {
+        self.players[player].resources -= *cost;
+        self.bank += *cost;
+    }
+
+    pub(crate) fn pieces(&self, player: usize, build_type: BuildType) -> usize {
+        self.players[player]
+            .buildings
+            .iter()
+            .filter(|pos| self.board.vertices[pos].build_type == build_type)
+            .count()
+    }
+
+    fn remove_card(&mut self, player: usize, card: DevelopmentCard) {
+        let cards = &mut self.players[player].development_cards;
+        if let Some(i) = cards.iter().position(|&c| c == card) {
+            cards.remove(i);
+        }
+    }
+
+    pub(crate) fn set_turn_phase(&mut self, next: TurnPhase) {
+        if let Phase::Turn { turn_phase, .. } = &mut self.phase {
+            *turn_phase = next;
+        }
+    }
+
+    fn set_development_phase(&mut self, next: DevelopmentPhase) {
+        if let Phase::Turn {
+            development_phase, ..
+        } = &mut self.phase
+        {
+            *development_phase = next;
+        }
+    }
+
+    /// Longest road needs at least five roads. Whoever holds it keeps it on a tie, and when
+    /// it is broken nobody gets it while the new longest roads are tied.
+    fn update_longest_road(&mut self) {
+        let lengths: Vec<usize> = (0..self.players.len())
+            .map(|p| self.board.longest_road(p))
+            .collect();
+        let longest = lengths.iter().copied().max().unwrap_or(0);
+        if let Some(holder) = self.player_with_road {
+            if lengths[holder as usize] == longest && longest >= 5 {
+                return;
+            }
+        }
+        let leaders: Vec<usize> = (0..lengths.len())
+            .filter(|&p| lengths[p] == longest)
+            .collect();
+        self.player_with_road = match leaders.as_slice() {
+            [leader] if longest >= 5 => Some(*leader as u32),
+            _ => None,
+        };
+    }
+
+    /// Largest army needs at least three knights and passes to whoever plays more.
+    fn update_largest_army(&mut self, player: usize) {
+        let knights = self.players[player].knights_played;
+        let to_beat = self
+            .player_with_army
+            .map_or(2, |holder| self.players[holder as usize].knights_played);
+        if knights > to_beat {
+            self.player_with_army = Some(player as u32);
+        }
+    }
+
+    fn check_winner(&mut self) {
+        if let Phase::Turn { player, .. } = self.phase {
+            if self.victory_points(player) >= self.rules.victory_points {
+                self.phase = Phase::FinishedGame { winner: player };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::SliceRandom, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{board::Board, dice::Dice, player::Player};
+
+    use super::*;
+
+    /// Plays random legal actions, returning the game once it ends or `steps` run out.
+    fn random_game(seed: u64, steps: usize) -> Game {
+        let mut game = Game::with_seed(Player::init_players(4), Board::new(), seed).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        for _ in 0..steps {
+            let actions = game.legal_actions();
+            let Some(action) = actions.choose(&mut rng) else {
+                break;
+            };
+            game.apply(action.clone()).unwrap();
+        }
+        game
+    }
+
+    fn place_initial(game: &mut Game, settlement: Axial, road: Axial) {
+        game.apply(Action::BuildSettlement(settlement)).unwrap();
+        game.apply(Action::BuildRoad(PathCoords::new(settlement, road)))
+            .unwrap();
+    }
+
+    /// Four players placing in snake order on spots well apart.
+    fn started_game() -> Game {
+        started_game_with(Rules::default())
+    }
+
+    fn started_game_with(rules: Rules) -> Game {
+        let mut game = Game::with_rules(Player::init_players(4), Board::new(), rules, 7).unwrap();
+        let spots = [
+            (Axial::new(0, 1), Axial::new(0, 2)),
+            (Axial::new(3, -1), Axial::new(3, -2)),
+            (Axial::new(-3, 1), Axial::new(-3, 2)),
+            (Axial::new(0, -2), Axial::new(0, -1)),
+            (Axial::new(-2, 3), Axial::new(-2, 2)),
+            (Axial::new(2, 3), Axial::new(1, 3)),
+            (Axial::new(3, -4), Axial::new(3, -5)),
+            (Axial::new(-2, -1), Axial::new(-1, -2)),
+        ];
+        for (settlement, road) in spots {
+            place_initial(&mut game, settlement, road);
+        }
+        game
+    }
+
+    #[test]
+    fn test_initial_placement_snake_order() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 1).unwrap();
+        let order: Vec<usize> = (0..6)
+            .map(|_| {
+                let player = game.current_player();
+                let settlement = game.legal_actions()[0].clone();
+                game.apply(settlement).unwrap();
+                let road = game.legal_actions()[0].clone();
+                game.apply(road).unwrap();
+                player
+            })
+            .collect();
+
+        assert_eq!(order, vec![0, 1, 2, 2, 1, 0]);
+        assert_eq!(game.phase(), Phase::START_TURNS);
+        assert!(game.players().iter().all(|p| p.buildings.len() == 2));
+    }
+
+    #[test]
+    fn test_initial_road_must_touch_new_settlement() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 1).unwrap();
+        game.apply(Action::BuildSettlement(Axial::new(0, 1)))
+            .unwrap();
+
+        let far = PathCoords::new(Axial::new(3, -1), Axial::new(3, -2));
+        assert_eq!(
+            game.apply(Action::BuildRoad(far)),
+            Err(RuleError::NotAtNewSettlement)
+        );
+        assert_eq!(game.log().len(), 1);
+    }
+
+    #[test]
+    fn test_errors_name_the_cause() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 1).unwrap();
+        let settlement = Action::BuildSettlement(Axial::new(0, 1));
+        assert_eq!(
+            game.apply_as(1, settlement.clone()),
+            Err(RuleError::NotYourTurn { current: 0 })
+        );
+        game.apply_as(0, settlement).unwrap();
+        game.apply(Action::BuildRoad(PathCoords::new(
+            Axial::new(0, 1),
+            Axial::new(0, 2),
+        )))
+        .unwrap();
+        place_initial(&mut game, Axial::new(3, -1), Axial::new(3, -2));
+
+        assert_eq!(
+            game.apply(Action::BuildSettlement(Axial::new(0, 1))),
+            Err(RuleError::VertexOccupied(Axial::new(0, 1)))
+        );
+        assert_eq!(
+            game.apply(Action::BuildSettlement(Axial::new(0, 2))),
+            Err(RuleError::NeighbourOccupied(Axial::new(0, 1)))
+        );
+        game.apply(Action::BuildSettlement(Axial::new(-3, 1)))
+            .unwrap();
+        let taken = PathCoords::new(Axial::new(3, -1), Axial::new(3, -2));
+        assert_eq!(
+            game.apply(Action::BuildRoad(taken.clone())),
+            Err(RuleError::EdgeOccupied(taken))
+        );
+    }
+
+    #[test]
+    fn test_second_settlement_collects_resources() {
+        let game = started_game();
+        let last = &game.players()[0];
+        // (-2, -1) borders brick 5, ore 3 and wheat 6
+        assert_eq!(last.resources.get(Resource::Brick), 1);
+        assert_eq!(last.resources.get(Resource::Ore), 1);
+        assert_eq!(last.resources.get(Resource::Wheat), 1);
+    }
+
+    #[test]
+    fn test_turn_requires_roll_before_building() {
+        let mut game = started_game();
+        assert_eq!(game.apply(Action::EndTurn), Err(RuleError::TurnNotFree));
+        assert_eq!(game.legal_actions(), vec![Action::RollDice]);
+
+        game.apply(Action::RollDice).unwrap();
+        assert!(game.last_roll().is_some());
+        assert_eq!(game.apply(Action::RollDice), Err(RuleError::WrongPhase));
+    }
+
+    #[test]
+    fn test_build_road_pays_and_connects() {
+        let mut game = started_game();
+        game.apply(Action::RollDice).unwrap();
+        while game.current_player() != 0 || !game.phase().is_turn() {
+            let action = game.legal_actions()[0].clone();
+            game.apply(action).unwrap();
+        }
+        game.players[0].resources = ResourceGroup::new(0, 0, 0, 1, 1);
+        let disconnected = PathCoords::new(Axial::new(2, 3), Axial::new(3, 2));
+        assert_eq!(
+            game.apply(Action::BuildRoad(disconnected)),
+            Err(RuleError::NotConnected)
+        );
+
+        let road = PathCoords::new(Axial::new(0, 2), Axial::new(1, 2));
+        game.apply(Action::BuildRoad(road.clone())).unwrap();
+        assert_eq!(game.board().edges[&road].owner, Some(0));
+        assert_eq!(game.players()[0].resources, ResourceGroup::empty());
+    }
+
+    #[test]
+    fn test_settlement_and_city_costs() {
+        let mut game = started_game();
+        game.set_dice(Dice::scripted([8]));
+        game.apply(Action::RollDice).unwrap();
+        game.players[0].resources = ResourceGroup::new(0, 0, 0, 1, 1);
+        let spot = Axial::new(1, 2);
+        game.apply(Action::BuildRoad(PathCoords::new(Axial::new(0, 2), spot)))
+            .unwrap();
+
+        game.players[0].resources = ResourceGroup::new(3, 2, 0, 0, 0);
+        assert_eq!(
+            game.apply(Action::BuildSettlement(spot)),
+            Err(RuleError::InsufficientResources)
+        );
+        game.players[0].resources = ResourceGroup::new(1, 1, 1, 1, 1);
+        game.apply(Action::BuildSettlement(spot)).unwrap();
+        assert_eq!(
+            game.players()[0].resources,
+            ResourceGroup::new(1, 0, 0, 0, 0)
+        );
+
+        game.players[0].resources = ResourceGroup::new(3, 2, 1, 0, 0);
+        game.apply(Action::BuildCity(spot)).unwrap();
+        assert_eq!(
+            game.players()[0].resources,
+            ResourceGroup::new(0, 0, 1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_scripted_seven_moves_robber() {
+        let mut game = started_game();
+        game.set_dice(Dice::scripted([7]));
+        game.apply(Action::RollDice).unwrap();
+
+        assert_eq!(game.last_roll(), Some(7));
+        assert!(game.phase().is_thief());
+    }
+
+    #[test]
+    fn test_special_build_after_each_turn() {
+        let mut game = Game::with_seed(Player::init_players(5), Board::extension(), 2).unwrap();
+        while !game.phase().is_turn() {
+            let action = game.legal_actions()[0].clone();
+            game.apply(action).unwrap();
+        }
+        game.set_dice(Dice::scripted([8]));
+        game.apply(Action::RollDice).unwrap();
+        game.apply(Action::EndTurn).unwrap();
+
+        let special_build = |builder| Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::SpecialBuild(builder),
+            development_phase: DevelopmentPhase::Ready,
+        };
+        assert_eq!(game.phase(), special_build(1));
+        game.players[1].resources = ResourceGroup::new(4, 0, 0, 1, 1);
+        let give = Resource::Ore;
+        let get = Resource::Wood;
+        assert_eq!(
+            game.apply(Action::BankTrade { give, get }),
+            Err(RuleError::WrongPhase)
+        );
+        assert_eq!(game.apply(Action::RollDice), Err(RuleError::WrongPhase));
+        let road = game
+            .legal_actions()
+            .into_iter()
+            .find(|a| matches!(a, Action::BuildRoad(_)))
+            .expect("player 1 can afford a road");
+        game.apply(road).unwrap();
+        assert_eq!(game.players()[1].paths.len(), 3);
+
+        for builder in 1..5 {
+            assert_eq!(game.phase(), special_build(builder));
+            game.apply(Action::EndTurn).unwrap();
+        }
+        assert_eq!(
+            game.phase(),
+            Phase::Turn {
+                player: 1,
+                turn_phase: TurnPhase::PreRoll,
+                development_phase: DevelopmentPhase::Ready,
+            }
+        );
+    }
+
+    #[test]
+    fn test_bank_trade_uses_harbor_ratio() {
+        let mut game = started_game();
+        game.phase = Phase::Turn {
+            player: 1,
+            turn_phase: TurnPhase::Free,
+            development_phase: DevelopmentPhase::Ready,
+        };
+        game.players[1].resources = ResourceGroup::new(4, 0, 0, 0, 0);
+        assert_eq!(game.board().trade_ratio(1, Resource::Ore), 4);
+
+        game.apply(Action::BankTrade {
+            give: Resource::Ore,
+            get: Resource::Wood,
+        })
+        .unwrap();
+        assert_eq!(
+            game.players()[1].resources,
+            ResourceGroup::new(0, 0, 0, 0, 1)
+        );
+    }
+
+    #[test]
+    fn test_seven_discards_then_moves_robber() {
+        let mut game = started_game();
+        game.players[2].resources = ResourceGroup::new(4, 4, 1, 0, 0);
+        game.phase = Phase::Turn {
+            player: 0,
+            turn_phase: TurnPhase::Discard(2),
+            development_phase: DevelopmentPhase::Ready,
+        };
+        assert!(game
+            .apply(Action::Discard(ResourceGroup::new(1, 0, 0, 0, 0)))
+            .is_err());
+        game.apply(Action::Discard(ResourceGroup::new(4, 0, 0, 0, 0)))
+            .unwrap();
+        assert_eq!(game.phase().player(), 0);
+        assert!(game.phase().is_thief());
+
+        let pos = Axial::new(2, 2);
+        assert_eq!(game.robber_victims(pos), vec![2]);
+        assert!(game
+            .apply(Action::MoveRobber { pos, victim: None })
+            .is_err());
+        let before = game.players()[0].hand_size();
+        game.apply(Action::MoveRobber {
+            pos,
+            victim: Some(2),
+        })
+        .unwrap();
+        assert_eq!(game.players()[0].hand_size(), before + 1);
+        assert_eq!(game.board().robber, pos);
+    }
+
+    #[test]
+    fn test_no_bank_trades_rule() {
+        let mut game = started_game_with(Rules {
+            bank_trades: false,
+            ..Rules::default()
+        });
+        game.set_dice(Dice::scripted([8]));
+        game.apply(Action::RollDice).unwrap();
+        game.players[0].resources = ResourceGroup::new(4, 0, 0, 0, 0);
+        let trade = Action::BankTrade {
+            give: Resource::Ore,
+            get: Resource::Wood,
+        };
+        assert_eq!(game.apply(trade), Err(RuleError::TradeNotAllowed));
+    }
+
+    #[test]
+    fn test_no_seven_rounds_rule() {
+        let mut game = started_game_with(Rules {
+            no_seven_rounds: 2,
+            ..Rules::default()
+        });
+        let mut rolls = vec![7];
+        rolls.extend([8; 8]);
+        rolls.push(7);
+        game.set_dice(Dice::scripted(rolls));
+        game.apply(Action::RollDice).unwrap();
+        assert_eq!(game.last_roll(), Some(8));
+        game.apply(Action::EndTurn).unwrap();
+        // every player rolls twice before the third round starts
+        for _ in 1..8 {
+            game.apply(Action::RollDice).unwrap();
+            game.apply(Action::EndTurn).unwrap();
+        }
+        assert_eq!(game.round(), 2);
+        // a fork leaves the log behind but still knows the round
+        let mut fork = game.fork();
+        assert!(fork.log().is_empty());
+        fork.apply(Action::RollDice).unwrap();
+        assert_eq!(fork.last_roll(), Some(7));
+        game.apply(Action::RollDice).unwrap();
+        assert_eq!(game.last_roll(), Some(7));
+    }
+
+    #[test]
+    fn test_discard_limit_rule() {
+        let mut game = started_game_with(Rules {
+            discard_limit: 9,
+            ..Rules::default()
+        });
+        game.players[2].resources = ResourceGroup::new(4, 4, 1, 0, 0);
+        game.set_dice(Dice::scripted([7]));
+        game.apply(Action::RollDice).unwrap();
+        // nine cards are within the limit
+        assert!(game.phase().is_thief());
+    }
+
+    #[test]
+    fn test_friendly_robber_rule() {
+        let robbed_at = |rules: Rules| {
+            let mut game = started_game_with(rules);
+            game.players[2].resources = ResourceGroup::new(1, 0, 0, 0, 0);
+            game.set_dice(Dice::scripted([7]));
+            game.apply(Action::RollDice).unwrap();
+            game.robber_victims(Axial::new(2, 2))
+        };
+        assert_eq!(robbed_at(Rules::default()), vec![2]);
+        // two points are too few to be robbed
+        let friendly = Rules {
+            friendly_robber: 3,
+            ..Rules::default()
+        };
+        assert_eq!(robbed_at(friendly), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_victory_points_rule() {
+        // player 0 reaches two points with the last initial settlement
+        let game = started_game_with(Rules {
+            victory_points: 2,
+            ..Rules::default()
+        });
+        assert_eq!(game.winner(), Some(0));
+    }
+
+    #[test]
+    fn test_dice_deck_rule() {
+        let game = started_game_with(Rules {
+            dice_deck: true,
+            ..Rules::default()
+        });
+        assert_eq!(game.dice(), &Dice::deck());
+    }
+
+    #[test]
+    fn test_development_cards_wait_a_turn() {
+        let mut game = started_game();
+        game.apply(Action::RollDice).unwrap();
+        while game.phase().player() != 0 || !game.phase().is_turn() {
+            let action = game.legal_actions()[0].clone();
+            game.apply(action).unwrap();
+        }
+        game.players[0].resources = ResourceGroup::new(1, 1, 1, 0, 0);
+        game.deck = crate::deck::Deck::new();
+        game.apply(Action::BuyDevelopmentCard).unwrap();
+        assert_eq!(game.players()[0].new_development_cards.len(), 1);
+        assert!(game.apply(Action::PlayMonopoly(Resource::Ore)).is_err());
+
+        game.apply(Action::EndTurn).unwrap();
+        assert_eq!(game.players()[0].development_cards.len(), 1);
+    }
+
+    #[test]
+    fn test_random_games_finish() {
+        for seed in 0..2 {
+            let game = random_game(seed, 20_000);
+            let winner = game.winner().expect("game should finish");
+            assert!(game.victory_points(winner) >= VICTORY_POINTS);
+        }
+    }
+
+    #[test]
+    fn test_replay_reconstructs_game() {
+        let game = random_game(11, 2_000);
+        let replayed = Game::replay(
+            Player::init_players(4),
+            Board::new(),
+            Rules::default(),
+            game.seed(),
+            game.log(),
+        )
+        .unwrap();
+
+        assert_eq!(replayed.phase(), game.phase());
+        assert_eq!(replayed.bank(), game.bank());
+        assert_eq!(replayed.board().robber, game.board().robber);
+        for (a, b) in replayed.players().iter().zip(game.players()) {
+            assert_eq!(a.resources, b.resources);
+            assert_eq!(a.buildings, b.buildings);
+            assert_eq!(a.development_cards, b.development_cards);
+        }
+    }
+
+    #[test]
+    fn test_replay_rejects_illegal_log() {
+        let log = vec![Action::RollDice];
+        assert!(Game::replay(
+            Player::init_players(3),
+            Board::new(),
+            Rules::default(),
+            0,
+            &log
+        )
+        .is_err());
+    }
+}
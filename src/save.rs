@@ -0,0 +1,390 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::game::Game;
+
+/// Version written into every save file. Bump this whenever the serialized shape of
+/// `Game` changes and append a migration to `MIGRATIONS` that upgrades the previous one.
+pub const FORMAT_VERSION: u32 = 11;
+
+/// Upgrades the raw json of a save in place by exactly one version.
+pub type Migration = fn(&mut Value) -> Result<()>;
+
+/// `MIGRATIONS[i]` upgrades a save from version `i + 1` to version `i + 2`.
+const MIGRATIONS: &[Migration] = &[
+    add_rules_state,
+    add_dice,
+    resource_counts_as_arrays,
+    hex_terrain,
+    add_seafarers,
+    add_cities_knights,
+    add_house_rules,
+    add_dice_deck_rule,
+    add_merchant_fleet,
+    add_roll_count,
+];
+const _: () = assert!(MIGRATIONS.len() as u32 + 1 == FORMAT_VERSION);
+
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    version: u32,
+    game: &'a Game,
+}
+
+impl Game {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_save_string()?)
+            .with_context(|| format!("Could not write save file {}", path.display()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Game> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Could not read save file {}", path.display()))?;
+        Game::from_save_str(&contents)
+    }
+
+    pub fn to_save_string(&self) -> Result<String> {
+        Ok(serde_json::to_string(&SaveFileRef {
+            version: FORMAT_VERSION,
+            game: self,
+        })?)
+    }
+
+    pub fn from_save_str(contents: &str) -> Result<Game> {
+        let mut save: Value = serde_json::from_str(contents)?;
+        let Some(version) = save.get("version").and_then(Value::as_u64) else {
+            return Err(anyhow!("Save file has no format version"));
+        };
+        let mut game = save
+            .get_mut("game")
+            .map(Value::take)
+            .ok_or_else(|| anyhow!("Save file has no game"))?;
+        migrate(&mut game, version as u32, MIGRATIONS)?;
+        Ok(serde_json::from_value(game)?)
+    }
+}
+
+/// Runs every migration needed to bring a save written at `version` up to the version
+/// produced by the last entry of `migrations`.
+fn migrate(game: &mut Value, version: u32, migrations: &[Migration]) -> Result<()> {
+    let latest = migrations.len() as u32 + 1;
+    if version == 0 || version > latest {
+        return Err(anyhow!(
+            "Unsupported save format version {}, expected 1 to {}",
+            version,
+            latest
+        ));
+    }
+    for (i, migration) in migrations.iter().enumerate().skip(version as usize - 1) {
+        migration(game).with_context(|| format!("Migrating save from version {}", i + 1))?;
+    }
+    Ok(())
+}
+
+/// Version 2 added the rules engine. Older saves predate turns, so they start from the
+/// initial placement with a deck shuffled from seed 0.
+///
+/// Migrations write literal json rather than serializing today's types, so they keep
+/// producing the shape of their own version as those types change.
+fn add_rules_state(game: &mut Value) -> Result<()> {
+    game["deck"] = json!({
+        "cards": [
+            "Knight", "RoadBuilding", "Knight", "RoadBuilding", "Knight", "Knight", "Knight",
+            "Knight", "Knight", "Knight", "Knight", "YearOfPlenty", "VictoryPoint",
+            "VictoryPoint", "Monopoly", "Knight", "VictoryPoint", "Knight", "YearOfPlenty",
+            "Monopoly", "Knight", "Knight", "Knight", "VictoryPoint", "VictoryPoint"
+        ]
+    });
+    game["phase"] = json!({
+        "InitialPlacement": {"player": 0, "placing_second": false, "placing_road": false}
+    });
+    game["trade"] = Value::Null;
+    game["last_roll"] = Value::Null;
+    game["seed"] = json!(0);
+    // ChaCha8Rng seeded with 0, after shuffling the deck
+    game["rng"] = json!({
+        "seed": [
+            236, 242, 115, 249, 129, 181, 205, 69, 135, 240, 70, 115, 6, 173, 108, 173, 208,
+            208, 163, 227, 51, 23, 231, 103, 242, 155, 234, 114, 215, 138, 125, 254
+        ],
+        "stream": 0,
+        "word_pos": 37
+    });
+    game["log"] = json!([]);
+    let players = game
+        .get_mut("players")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| anyhow!("Save file has no players"))?;
+    for player in players {
+        player["development_cards"] = json!([]);
+        player["new_development_cards"] = json!([]);
+        player["knights_played"] = json!(0);
+    }
+    Ok(())
+}
+
+/// Version 3 made the dice replaceable. Every older game rolled with its rng.
+fn add_dice(game: &mut Value) -> Result<()> {
+    game["dice"] = json!("Random");
+    Ok(())
+}
+
+/// Version 4 stores a `ResourceGroup` as an array of counts in the order of
+/// `Resource::ALL` rather than a map from resource names.
+fn resource_counts_as_arrays(game: &mut Value) -> Result<()> {
+    match game {
+        Value::Object(map) if map.len() == 1 && map.contains_key("resources") => {
+            let counts = &map["resources"];
+            if let Value::Object(counts) = counts {
+                let array: Vec<Value> = ["Ore", "Wheat", "Sheep", "Brick", "Wood"]
+                    .iter()
+                    .map(|r| counts.get(*r).cloned().unwrap_or(json!(0)))
+                    .collect();
+                map["resources"] = Value::Array(array);
+            }
+        }
+        Value::Object(map) => {
+            for value in map.values_mut() {
+                resource_counts_as_arrays(value)?;
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                resource_counts_as_arrays(value)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Version 5 replaced the resource of a hex, where `None` stood for the desert, with its
+/// terrain.
+fn hex_terrain(game: &mut Value) -> Result<()> {
+    let hexes = game["board"]
+        .get_mut("hexes")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| anyhow!("Save file has no hexes"))?;
+    for entry in hexes {
+        let hex = entry
+            .get_mut(1)
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| anyhow!("Save file has an invalid hex"))?;
+        let resource = hex.remove("resource_type").unwrap_or_default();
+        let terrain = match resource.as_str() {
+            Some("None") => "Desert",
+            Some("Brick") => "Hills",
+            Some("Wood") => "Forest",
+            Some("Ore") => "Mountains",
+            Some("Wheat") => "Fields",
+            Some("Sheep") => "Pasture",
+            _ => return Err(anyhow!("Save file has a hex of {}", resource)),
+        };
+        hex.insert("terrain".to_string(), json!(terrain));
+    }
+    Ok(())
+}
+
+/// Version 6 added Seafarers. Older boards have no pirate and no ships have moved.
+fn add_seafarers(game: &mut Value) -> Result<()> {
+    game["board"]["pirate"] = Value::Null;
+    game["ships"] = json!({"moved": false, "built": []});
+    Ok(())
+}
+
+/// Version 7 added Cities & Knights, which counts commodities in every `ResourceGroup`.
+/// Older games are never Cities & Knights games and hold no commodities.
+fn add_cities_knights(game: &mut Value) -> Result<()> {
+    add_commodity_counts(game);
+    game["cities_knights"] = Value::Null;
+    Ok(())
+}
+
+fn add_commodity_counts(value: &mut Value) {
+    match value {
+        Value::Object(map) if map.get("resources").is_some_and(Value::is_array) => {
+            map.insert("commodities".to_string(), json!([0, 0, 0]));
+        }
+        Value::Object(map) => {
+            for value in map.values_mut() {
+                add_commodity_counts(value);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                add_commodity_counts(value);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Version 8 added house rules. Older games were played to the usual rules, with the
+/// higher target of Cities & Knights.
+fn add_house_rules(game: &mut Value) -> Result<()> {
+    let victory_points = if game["cities_knights"].is_null() {
+        10
+    } else {
+        13
+    };
+    game["rules"] = json!({
+        "victory_points": victory_points,
+        "friendly_robber": 0,
+        "discard_limit": 7,
+        "no_seven_rounds": 0,
+        "bank_trades": true,
+        "player_trades": true,
+    });
+    Ok(())
+}
+
+/// Version 9 added the dice deck house rule, which older games didn't use.
+fn add_dice_deck_rule(game: &mut Value) -> Result<()> {
+    game["rules"]["dice_deck"] = json!(false);
+    Ok(())
+}
+
+/// Version 10 added the merchant fleet progress card, which no older game had played.
+fn add_merchant_fleet(game: &mut Value) -> Result<()> {
+    if let Some(cities_knights) = game["cities_knights"].as_object_mut() {
+        cities_knights.insert("merchant_fleet".to_string(), Value::Null);
+    }
+    Ok(())
+}
+
+/// Version 11 counted the rolls rather than finding them in the log.
+fn add_roll_count(game: &mut Value) -> Result<()> {
+    let rolls = game["log"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Save file has no log"))?
+        .iter()
+        .filter(|action| action.as_str() == Some("RollDice"))
+        .count();
+    game["rolls"] = json!(rolls);
+    Ok(())
+}
+
+/// Serializes a map as a list of entries so that maps keyed by structs such as `Axial`
+/// can be written to formats that only allow string keys.
+pub(crate) mod entries {
+    use std::{collections::HashMap, hash::Hash};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize + Ord,
+        V: Serialize,
+        S: Serializer,
+    {
+        let mut entries: Vec<(&K, &V)> = map.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let entries: Vec<(K, V)> = Vec::deserialize(deserializer)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        axial::Axial, board::Board, phase::Phase, player::Player, resource::ResourceGroup,
+        rules::Rules, terrain::Terrain, vertex::BuildType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let mut game = Game::new(Player::init_players(3), Board::new(), Rules::default()).unwrap();
+        game.board
+            .place_building(1, Axial::new(0, 1), BuildType::Settlement, false)
+            .unwrap();
+        let path = std::env::temp_dir().join("catanrs_test_save_load_round_trip.json");
+
+        game.save(&path).unwrap();
+        let loaded = Game::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.players().len(), 3);
+        assert_eq!(loaded.board().hexes.len(), 19);
+        assert_eq!(loaded.board().vertices[&Axial::new(0, 1)].owner, Some(1));
+        let as_value =
+            |g: &Game| -> Value { serde_json::from_str(&g.to_save_string().unwrap()).unwrap() };
+        assert_eq!(as_value(&loaded), as_value(&game));
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_version() {
+        let game = Game::new(Player::init_players(3), Board::new(), Rules::default()).unwrap();
+        let mut save: Value = serde_json::from_str(&game.to_save_string().unwrap()).unwrap();
+        save["version"] = Value::from(FORMAT_VERSION + 1);
+
+        assert!(Game::from_save_str(&save.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_load_version_1_save() {
+        // written by the first version of `Game::save`
+        let v1 = include_str!("../tests/fixtures/save_v1.json");
+        let game = Game::from_save_str(v1).unwrap();
+
+        assert_eq!(game.phase(), Phase::START_GAME);
+        assert_eq!(game.deck().len(), 25);
+        assert_eq!(game.players().len(), 3);
+        let player = &game.players()[1];
+        assert_eq!(player.knights_played, 0);
+        assert_eq!(player.resources, ResourceGroup::new(2, 0, 0, 1, 0));
+        assert_eq!(player.buildings, vec![Axial::new(0, 1)]);
+        assert_eq!(game.board().vertices[&Axial::new(0, 1)].owner, Some(1));
+        assert_eq!(*game.bank(), ResourceGroup::new(18, 20, 20, 19, 20));
+        assert_eq!(game.board().hexes.len(), 19);
+        assert_eq!(
+            game.board().hexes[&Axial::new(0, 0)].terrain,
+            Terrain::Desert
+        );
+        assert!(game.log().is_empty());
+        assert_eq!(game.round(), 0);
+    }
+
+    #[test]
+    fn test_migrations_run_from_save_version() {
+        fn add_a(v: &mut Value) -> Result<()> {
+            v["a"] = Value::from(true);
+            Ok(())
+        }
+        fn add_b(v: &mut Value) -> Result<()> {
+            v["b"] = Value::from(true);
+            Ok(())
+        }
+        let migrations: &[Migration] = &[add_a, add_b];
+
+        let mut from_v1 = Value::Object(Default::default());
+        migrate(&mut from_v1, 1, migrations).unwrap();
+        assert_eq!(from_v1["a"], true);
+        assert_eq!(from_v1["b"], true);
+
+        let mut from_v2 = Value::Object(Default::default());
+        migrate(&mut from_v2, 2, migrations).unwrap();
+        assert!(from_v2.get("a").is_none());
+        assert_eq!(from_v2["b"], true);
+
+        let mut current = Value::Object(Default::default());
+        migrate(&mut current, 3, migrations).unwrap();
+        assert_eq!(current, Value::Object(Default::default()));
+        assert!(migrate(&mut current, 4, migrations).is_err());
+    }
+}
@@ -0,0 +1,463 @@
+//! The Seafarers expansion: sea hexes, ships, the pirate and bonus points for settling
+//! islands away from home.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    axial::Axial,
+    board::{Board, OFFSETS},
+    edge::{PathCoords, PathType},
+    error::RuleError,
+    game::Game,
+    hex::Hex,
+    terrain::Terrain,
+};
+
+/// Victory points for settling each island other than the ones a player started on.
+pub const ISLAND_BONUS: u32 = 2;
+
+/// Steps from a hex centre to the centres of its six neighbours.
+const HEX_NEIGHBOURS: [Axial; 6] = [
+    Axial::new(-1, 2),
+    Axial::new(1, -2),
+    Axial::new(-1, -1),
+    Axial::new(1, 1),
+    Axial::new(-2, 1),
+    Axial::new(2, -1),
+];
+
+/// What has happened to ships during the current turn.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShipTurn {
+    /// Only one ship may be moved each turn.
+    pub moved: bool,
+    /// Ships built this turn, which can't be moved until the next.
+    pub built: Vec<PathCoords>,
+}
+
+impl Board {
+    /// Whether the board has sea hexes for ships to sail on.
+    pub fn has_sea(&self) -> bool {
+        self.hexes.values().any(|h| h.terrain == Terrain::Sea)
+    }
+
+    /// The hexes on either side of an edge, leaving out any off the board.
+    pub fn edge_hexes(&self, coords: &PathCoords) -> Vec<&Hex> {
+        let [a, b] = coords.vertices();
+        self.get_adjacent_hexes(a)
+            .into_iter()
+            .filter(|hex| OFFSETS.iter().any(|&offset| hex.pos + offset == b))
+            .collect()
+    }
+
+    /// The six edges around the hex at `pos`.
+    pub fn hex_sides(&self, pos: Axial) -> Vec<PathCoords> {
+        (0..6)
+            .map(|i| PathCoords::new(pos + OFFSETS[i], pos + OFFSETS[(i + 1) % 6]))
+            .filter(|coords| self.edges.contains_key(coords))
+            .collect()
+    }
+
+    /// Whether vertex `v` touches land, so that it can be settled.
+    pub fn is_on_land(&self, v: Axial) -> bool {
+        self.get_adjacent_hexes(v)
+            .iter()
+            .any(|hex| hex.terrain.is_land())
+    }
+
+    /// Roads need land beside them and ships need sea that the pirate isn't on.
+    pub fn can_carry(&self, coords: &PathCoords, path_type: PathType) -> bool {
+        let hexes = self.edge_hexes(coords);
+        match path_type {
+            PathType::Road => hexes.iter().any(|hex| hex.terrain.is_land()),
+            PathType::Ship => {
+                hexes.iter().any(|hex| hex.terrain == Terrain::Sea) && !self.pirate_blocks(coords)
+            }
+            PathType::None => false,
+        }
+    }
+
+    /// Whether the pirate is on a hex beside `coords`.
+    pub fn pirate_blocks(&self, coords: &PathCoords) -> bool {
+        self.pirate
+            .is_some_and(|pirate| self.edge_hexes(coords).iter().any(|hex| hex.pos == pirate))
+    }
+
+    /// Groups of land hexes joined by their sides, each sorted and in order of their
+    /// first hex.
+    pub fn islands(&self) -> Vec<Vec<Axial>> {
+        let mut land: Vec<Axial> = self
+            .hexes
+            .values()
+            .filter(|hex| hex.terrain.is_land())
+            .map(|hex| hex.pos)
+            .collect();
+        land.sort();
+        let mut seen = HashSet::new();
+        let mut islands = Vec::new();
+        for &start in land.iter() {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut island = vec![start];
+            let mut i = 0;
+            while i < island.len() {
+                for step in HEX_NEIGHBOURS {
+                    let next = island[i] + step;
+                    if land.contains(&next) && seen.insert(next) {
+                        island.push(next);
+                    }
+                }
+                i += 1;
+            }
+            island.sort();
+            islands.push(island);
+        }
+        islands
+    }
+
+    /// Whether the ship `player` has on `coords` ends a shipping route, with none of the
+    /// player's buildings or other ships at one of its ends.
+    pub fn is_open_ship(&self, player: usize, coords: &PathCoords) -> bool {
+        let Some(edge) = self.edges.get(coords) else {
+            return false;
+        };
+        if edge.owner != Some(player) || edge.path_type != PathType::Ship {
+            return false;
+        }
+        coords.vertices().into_iter().any(|end| {
+            self.vertices[&end].owner != Some(player)
+                && !self.get_adjacent_paths(end).iter().any(|p| {
+                    p != coords
+                        && self.edges[p].owner == Some(player)
+                        && self.edges[p].path_type == PathType::Ship
+                })
+        })
+    }
+}
+
+impl Game {
+    /// Bonus points `player` has for settling islands other than the ones their initial
+    /// settlements are on.
+    pub fn island_bonus(&self, player: usize) -> u32 {
+        let islands = self.board.islands();
+        if islands.len() < 2 {
+            return 0;
+        }
+        let island_of = |v: &Axial| {
+            let hexes = self.board.get_adjacent_hexes(*v);
+            islands
+                .iter()
+                .position(|island| hexes.iter().any(|hex| island.contains(&hex.pos)))
+        };
+        let buildings = &self.players[player].buildings;
+        let home: HashSet<usize> = buildings.iter().take(2).filter_map(island_of).collect();
+        let settled: HashSet<usize> = buildings.iter().filter_map(island_of).collect();
+        settled.difference(&home).count() as u32 * ISLAND_BONUS
+    }
+
+    /// The players with a ship beside the sea hex at `pos` and cards to steal, other than
+    /// the one moving the pirate.
+    pub fn pirate_victims(&self, pos: Axial) -> Vec<usize> {
+        let thief = self.phase.player();
+        let mut victims: Vec<usize> = self
+            .board
+            .hex_sides(pos)
+            .iter()
+            .map(|coords| &self.board.edges[coords])
+            .filter(|edge| edge.path_type == PathType::Ship)
+            .filter_map(|edge| edge.owner)
+            .filter(|&owner| owner != thief && self.can_be_robbed(owner))
+            .collect();
+        victims.sort();
+        victims.dedup();
+        victims
+    }
+
+    pub(crate) fn validate_pirate(
+        &self,
+        pos: Axial,
+        victim: Option<usize>,
+    ) -> Result<(), RuleError> {
+        let is_sea = self
+            .board
+            .hexes
+            .get(&pos)
+            .is_some_and(|hex| hex.terrain == Terrain::Sea);
+        if !is_sea || self.board.pirate == Some(pos) {
+            return Err(RuleError::InvalidPirateHex);
+        }
+        let victims = self.pirate_victims(pos);
+        match victim {
+            None if victims.is_empty() => Ok(()),
+            Some(v) if victims.contains(&v) => Ok(()),
+            _ => Err(RuleError::InvalidVictim { victims }),
+        }
+    }
+
+    /// Checks that `player` may move their open ship on `from` to `to`. Ships built this
+    /// turn or next to the pirate stay where they are.
+    pub(crate) fn validate_ship_move(
+        &self,
+        player: usize,
+        from: &PathCoords,
+        to: &PathCoords,
+    ) -> Result<(), RuleError> {
+        if self.ships.moved {
+            return Err(RuleError::ShipAlreadyMoved);
+        }
+        if !self.board.is_open_ship(player, from)
+            || self.ships.built.contains(from)
+            || self.board.pirate_blocks(from)
+        {
+            return Err(RuleError::ShipNotMovable(from.clone()));
+        }
+        self.board
+            .validate_path(player, to.clone(), PathType::Ship, false)?;
+        if !self
+            .board
+            .is_path_connected(player, to, PathType::Ship, Some(from))
+        {
+            return Err(RuleError::NotConnected);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn move_ship(&mut self, player: usize, from: PathCoords, to: PathCoords) {
+        let edge = self.board.edges.get_mut(&from).expect("validated ship");
+        edge.owner = None;
+        edge.path_type = PathType::None;
+        self.board
+            .place_path(&self.players[player], to.clone(), PathType::Ship, false)
+            .expect("validated ship move");
+        let paths = &mut self.players[player].paths;
+        paths.retain(|p| *p != from);
+        paths.push(to);
+        self.ships.moved = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        action::Action,
+        dice::Dice,
+        phase::{DevelopmentPhase, Phase, TurnPhase},
+        player::Player,
+        resource::{Resource, ResourceGroup},
+        vertex::BuildType,
+    };
+
+    use super::*;
+
+    /// Three players on the new shores map, with player 0 settled on the coast of the home
+    /// island and a ship out to sea.
+    fn shores_game() -> Game {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new_shores(), 3).unwrap();
+        let settlement = Axial::new(-3, -2);
+        let out_to_sea = PathCoords::new(settlement, Axial::new(-4, -2));
+        game.apply(Action::BuildSettlement(settlement)).unwrap();
+        assert_eq!(
+            game.apply(Action::BuildRoad(out_to_sea.clone())),
+            Err(RuleError::UnsuitableEdge {
+                coords: out_to_sea.clone(),
+                path_type: PathType::Road,
+            })
+        );
+        game.apply(Action::BuildShip(out_to_sea)).unwrap();
+        let spots = [
+            (Axial::new(7, -6), Axial::new(6, -5)),
+            (Axial::new(3, -7), Axial::new(2, -6)),
+            (Axial::new(0, -2), Axial::new(0, -1)),
+            (Axial::new(2, 0), Axial::new(1, 0)),
+            (Axial::new(0, -7), Axial::new(1, -7)),
+        ];
+        for (settlement, road) in spots {
+            game.apply(Action::BuildSettlement(settlement)).unwrap();
+            game.apply(Action::BuildRoad(PathCoords::new(settlement, road)))
+                .unwrap();
+        }
+        game
+    }
+
+    fn set_turn(game: &mut Game, player: usize, turn_phase: TurnPhase) {
+        game.phase = Phase::Turn {
+            player,
+            turn_phase,
+            development_phase: DevelopmentPhase::Ready,
+        };
+    }
+
+    #[test]
+    fn test_new_shores_islands() {
+        let board = Board::new_shores();
+        let islands = board.islands();
+
+        assert_eq!(islands.iter().map(Vec::len).collect::<Vec<_>>(), [2, 3, 18]);
+        let pirate = board.pirate.expect("the map places the pirate");
+        assert_eq!(board.hexes[&pirate].terrain, Terrain::Sea);
+        let reparsed: Board = board.to_string().parse().unwrap();
+        assert_eq!(reparsed.islands(), islands);
+        assert_eq!(reparsed.pirate, board.pirate);
+        assert!(!Board::new().has_sea());
+    }
+
+    #[test]
+    fn test_ships_sail_and_move_once_per_turn() {
+        let mut game = shores_game();
+        assert_eq!(game.players()[0].paths.len(), 2);
+        set_turn(&mut game, 0, TurnPhase::Free);
+        let settlement = Axial::new(-3, -2);
+        let first_ship = PathCoords::new(settlement, Axial::new(-4, -2));
+
+        game.players[0].resources = PathType::Ship.cost();
+        let build = game
+            .legal_actions()
+            .into_iter()
+            .find(|a| matches!(a, Action::BuildShip(c) if c.vertices().contains(&settlement)))
+            .expect("player 0 can afford a ship from their settlement");
+        game.apply(build.clone()).unwrap();
+        let Action::BuildShip(new_ship) = build else {
+            unreachable!()
+        };
+        assert_eq!(game.players()[0].resources, ResourceGroup::empty());
+        assert_eq!(
+            game.apply(Action::MoveShip {
+                from: new_ship.clone(),
+                to: first_ship.clone(),
+            }),
+            Err(RuleError::ShipNotMovable(new_ship))
+        );
+
+        let moves: Vec<Action> = game
+            .legal_actions()
+            .into_iter()
+            .filter(|a| matches!(a, Action::MoveShip { .. }))
+            .collect();
+        assert!(!moves.is_empty());
+        assert!(moves
+            .iter()
+            .all(|a| matches!(a, Action::MoveShip { from, .. } if *from == first_ship)));
+        game.apply(moves[0].clone()).unwrap();
+        let Action::MoveShip { to, .. } = moves[0].clone() else {
+            unreachable!()
+        };
+        assert_eq!(game.board().edges[&first_ship].owner, None);
+        assert_eq!(game.board().edges[&to].path_type, PathType::Ship);
+        assert_eq!(
+            game.apply(Action::MoveShip {
+                from: to,
+                to: first_ship,
+            }),
+            Err(RuleError::ShipAlreadyMoved)
+        );
+    }
+
+    #[test]
+    fn test_pirate_robs_ships() {
+        let mut game = shores_game();
+        set_turn(&mut game, 1, TurnPhase::MoveThief);
+        game.players[0].resources = ResourceGroup::of(Resource::Wheat, 2);
+        game.players[1].resources = ResourceGroup::empty();
+        let ship = PathCoords::new(Axial::new(-3, -2), Axial::new(-4, -2));
+        let sea = game
+            .board()
+            .edge_hexes(&ship)
+            .into_iter()
+            .find(|hex| hex.terrain == Terrain::Sea)
+            .unwrap()
+            .pos;
+
+        assert_eq!(
+            game.apply(Action::MovePirate {
+                pos: Axial::new(-2, -2),
+                victim: None,
+            }),
+            Err(RuleError::InvalidPirateHex)
+        );
+        assert_eq!(
+            game.apply(Action::MovePirate {
+                pos: sea,
+                victim: None,
+            }),
+            Err(RuleError::InvalidVictim { victims: vec![0] })
+        );
+        game.apply(Action::MovePirate {
+            pos: sea,
+            victim: Some(0),
+        })
+        .unwrap();
+
+        assert_eq!(game.board().pirate, Some(sea));
+        assert_eq!(game.players()[1].resources.get(Resource::Wheat), 1);
+        assert!(game.board().pirate_blocks(&ship));
+        assert!(!game.board().can_carry(&ship, PathType::Ship));
+    }
+
+    #[test]
+    fn test_island_bonus() {
+        let mut game = shores_game();
+        assert_eq!(game.island_bonus(0), 0);
+
+        let away = Axial::new(-5, 6);
+        game.board
+            .place_building(0, away, BuildType::Settlement, false)
+            .unwrap();
+        game.players[0].buildings.push(away);
+
+        assert_eq!(game.island_bonus(0), ISLAND_BONUS);
+        assert_eq!(game.island_bonus(1), 0);
+        assert_eq!(game.public_victory_points(0), 3 + ISLAND_BONUS);
+    }
+
+    #[test]
+    fn test_gold_field_choice() {
+        let mut game = shores_game();
+        let settlement = game.players[1].buildings[0];
+        let gold = game
+            .board
+            .hexes
+            .values_mut()
+            .find(|hex| {
+                hex.terrain.is_land() && hex.terrain.has_number() && {
+                    let pos = hex.pos;
+                    OFFSETS.iter().any(|&offset| pos + offset == settlement)
+                }
+            })
+            .expect("the settlement borders a field");
+        gold.terrain = Terrain::Gold;
+        gold.number = 6;
+        let owed = game.board().gold_for_roll(6)[&1];
+        set_turn(&mut game, 0, TurnPhase::PreRoll);
+        game.set_dice(Dice::scripted(vec![6]));
+
+        let before = game.players()[1].resources;
+        game.apply(Action::RollDice).unwrap();
+        assert_eq!(
+            game.phase().player(),
+            1,
+            "the owner of the gold field chooses first"
+        );
+        assert_eq!(
+            game.legal_actions(),
+            Resource::ALL.map(Action::TakeResource).to_vec()
+        );
+        assert_eq!(game.apply(Action::EndTurn), Err(RuleError::TurnNotFree));
+        for _ in 0..owed {
+            game.apply(Action::TakeResource(Resource::Ore)).unwrap();
+        }
+
+        let produced = game.players()[1].resources - before;
+        assert_eq!(produced.get(Resource::Ore), owed);
+        assert!(matches!(
+            game.phase(),
+            Phase::Turn {
+                player: 0,
+                turn_phase: TurnPhase::Free,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,346 @@
+use std::{fmt::Write as _, fs, path::Path};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    action::Action,
+    board::Board,
+    dice::outcomes,
+    game::Game,
+    resource::{Commodity, Resource, ResourceGroup},
+    vertex::BuildType,
+};
+
+/// The cards one roll paid a player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Production {
+    /// The seed of the game, which tells games apart.
+    pub game: u64,
+    /// Counts the rolls of the game, starting from 1.
+    pub turn: u32,
+    pub player: usize,
+    pub roll: i32,
+    pub resources: ResourceGroup,
+}
+
+/// What happened to one player over a game.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerSummary {
+    pub game: u64,
+    pub player: usize,
+    pub winner: bool,
+    /// The turn the player's first city was built in.
+    pub first_city: Option<u32>,
+    /// Cards the robber kept the player from collecting.
+    pub blocked: i32,
+    /// Cards stolen from the player by the robber or the pirate.
+    pub stolen: i32,
+    /// Cards given back after a 7.
+    pub discarded: i32,
+    pub cards_bought: u32,
+    pub knights: u32,
+    pub road_building: u32,
+    pub year_of_plenty: u32,
+    pub monopoly: u32,
+}
+
+/// Statistics collected by replaying finished games.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub production: Vec<Production>,
+    pub players: Vec<PlayerSummary>,
+    /// How often each total from 2 to 12 was rolled, indexed by the total.
+    pub dice: [u32; 13],
+}
+impl Stats {
+    /// Replays `log` from `start`, the game as it was before its first action, and adds
+    /// what happened to the statistics.
+    pub fn add_game(&mut self, start: Game, log: &[Action]) -> Result<()> {
+        let mut game = start;
+        let id = game.seed();
+        let mut players: Vec<PlayerSummary> = (0..game.players().len())
+            .map(|player| PlayerSummary {
+                game: id,
+                player,
+                ..PlayerSummary::default()
+            })
+            .collect();
+        let mut turn = 0;
+        for (i, action) in log.iter().enumerate() {
+            let actor = game.current_player();
+            let hands: Vec<ResourceGroup> = game.players().iter().map(|p| p.resources).collect();
+            let blocked = (*action == Action::RollDice).then(|| blocked_cards(game.board()));
+            game.apply(action.clone())
+                .with_context(|| format!("Replaying action {} {:?} of game {}", i, action, id))?;
+            let summary = &mut players[actor];
+            match action {
+                Action::RollDice => {
+                    turn += 1;
+                    let roll = game.last_roll().expect("the dice were rolled");
+                    self.dice[roll as usize] += 1;
+                    for (player, p) in game.players().iter().enumerate() {
+                        self.production.push(Production {
+                            game: id,
+                            turn,
+                            player,
+                            roll,
+                            resources: p.resources - hands[player],
+                        });
+                    }
+                    let blocked = blocked.filter(|(number, _)| *number == roll);
+                    if let Some((_, blocked)) = blocked {
+                        for (player, cards) in blocked {
+                            players[player].blocked += cards;
+                        }
+                    }
+                }
+                Action::BuildCity(_) => {
+                    summary.first_city.get_or_insert(turn);
+                }
+                Action::Discard(cards) => summary.discarded += cards.total(),
+                Action::MoveRobber {
+                    victim: Some(victim),
+                    ..
+                }
+                | Action::MovePirate {
+                    victim: Some(victim),
+                    ..
+                } => {
+                    let hand = game.players()[*victim].resources;
+                    players[*victim].stolen += hands[*victim].total() - hand.total();
+                }
+                Action::BuyDevelopmentCard => summary.cards_bought += 1,
+                Action::PlayKnight => summary.knights += 1,
+                Action::PlayRoadBuilding => summary.road_building += 1,
+                Action::PlayYearOfPlenty => summary.year_of_plenty += 1,
+                Action::PlayMonopoly(_) => summary.monopoly += 1,
+                _ => {}
+            }
+        }
+        if let Some(winner) = game.winner() {
+            players[winner].winner = true;
+        }
+        self.players.extend(players);
+        Ok(())
+    }
+
+    /// The rolls expected for each total from 2 to 12, given how many were rolled.
+    pub fn expected_dice(&self) -> [f64; 13] {
+        let rolls: u32 = self.dice.iter().sum();
+        std::array::from_fn(|total| rolls as f64 * outcomes(total as i32) as f64 / 36.0)
+    }
+
+    /// One row per player per roll, with a column for every kind of card.
+    pub fn production_csv(&self) -> String {
+        let mut csv = String::from("game,turn,player,roll");
+        for name in card_names() {
+            csv.push(',');
+            csv.push_str(&name);
+        }
+        csv.push('\n');
+        for row in &self.production {
+            write!(csv, "{},{},{},{}", row.game, row.turn, row.player, row.roll).unwrap();
+            for count in row.resources.counts() {
+                write!(csv, ",{}", count).unwrap();
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// One row per player per game.
+    pub fn players_csv(&self) -> String {
+        let mut csv = String::from(
+            "game,player,winner,first_city,blocked,stolen,discarded,\
+             cards_bought,knights,road_building,year_of_plenty,monopoly\n",
+        );
+        for p in &self.players {
+            let first_city = p.first_city.map(|t| t.to_string()).unwrap_or_default();
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                p.game,
+                p.player,
+                p.winner,
+                first_city,
+                p.blocked,
+                p.stolen,
+                p.discarded,
+                p.cards_bought,
+                p.knights,
+                p.road_building,
+                p.year_of_plenty,
+                p.monopoly
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// How often each total was rolled against how often it was expected to be.
+    pub fn dice_csv(&self) -> String {
+        let expected = self.expected_dice();
+        let mut csv = String::from("roll,count,expected\n");
+        for (total, (count, expected)) in self.dice.iter().zip(expected).enumerate().skip(2) {
+            writeln!(csv, "{},{},{:.2}", total, count, expected).unwrap();
+        }
+        csv
+    }
+
+    /// Writes production.csv, players.csv and dice.csv into `dir`.
+    pub fn write_csv(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).with_context(|| format!("Creating {}", dir.display()))?;
+        for (name, contents) in [
+            ("production.csv", self.production_csv()),
+            ("players.csv", self.players_csv()),
+            ("dice.csv", self.dice_csv()),
+        ] {
+            let path = dir.join(name);
+            fs::write(&path, contents).with_context(|| format!("Writing {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of the hex the robber is on and the cards each player would collect from
+/// it if that number were rolled.
+fn blocked_cards(board: &Board) -> (i32, Vec<(usize, i32)>) {
+    let Some(hex) = board.hexes.get(&board.robber) else {
+        return (0, Vec::new());
+    };
+    if hex.terrain.resource().is_none() {
+        return (0, Vec::new());
+    }
+    let cards = board
+        .get_adjacent_vertices(hex.pos)
+        .iter()
+        .filter_map(|v| match (v.owner, v.build_type) {
+            (Some(owner), BuildType::Settlement) => Some((owner, 1)),
+            (Some(owner), BuildType::City) => Some((owner, 2)),
+            _ => None,
+        })
+        .collect();
+    (hex.number, cards)
+}
+
+/// The columns of `ResourceGroup::counts`.
+fn card_names() -> impl Iterator<Item = String> {
+    Resource::ALL
+        .iter()
+        .map(|r| r.to_string())
+        .chain(Commodity::ALL.iter().map(|c| c.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        agent::{play_game, Agent},
+        heuristic::HeuristicAgent,
+        player::Player,
+    };
+
+    use super::*;
+
+    fn new_game(seed: u64) -> Game {
+        Game::with_seed(Player::init_players(3), Board::new(), seed).unwrap()
+    }
+
+    fn played_game(seed: u64) -> Game {
+        let mut game = new_game(seed);
+        let mut agents: Vec<Box<dyn Agent>> = (0..3)
+            .map(|_| Box::new(HeuristicAgent::new()) as Box<dyn Agent>)
+            .collect();
+        play_game(&mut game, &mut agents, 5_000).unwrap();
+        game
+    }
+
+    #[test]
+    fn test_stats_of_a_played_game() {
+        let game = played_game(4);
+        let mut stats = Stats::default();
+        stats.add_game(new_game(4), game.log()).unwrap();
+
+        let rolls = game
+            .log()
+            .iter()
+            .filter(|a| **a == Action::RollDice)
+            .count();
+        assert_eq!(stats.dice.iter().sum::<u32>() as usize, rolls);
+        assert_eq!(stats.production.len(), 3 * rolls);
+        assert_eq!(stats.players.len(), 3);
+        let winner = game.winner().unwrap();
+        assert!(stats.players[winner].winner);
+        let bought: u32 = stats.players.iter().map(|p| p.cards_bought).sum();
+        let buys = game
+            .log()
+            .iter()
+            .filter(|a| **a == Action::BuyDevelopmentCard);
+        assert_eq!(bought as usize, buys.count());
+        assert!(stats.players.iter().any(|p| p.first_city.is_some()));
+        // every seven is followed by robbery or discards, never production
+        assert!(stats
+            .production
+            .iter()
+            .filter(|row| row.roll == 7)
+            .all(|row| row.resources.is_empty()));
+
+        let expected: f64 = stats.expected_dice().iter().sum();
+        assert!((expected - rolls as f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_robber_blocks_production() {
+        let mut board = Board::new();
+        let hex = *board
+            .hexes
+            .values()
+            .find(|h| h.terrain.resource().is_some())
+            .unwrap();
+        let corners: Vec<_> = board
+            .get_adjacent_vertices(hex.pos)
+            .iter()
+            .map(|v| v.pos)
+            .collect();
+        for (corner, owner, build_type) in [
+            (corners[0], 1, BuildType::Settlement),
+            (corners[2], 2, BuildType::City),
+        ] {
+            let v = board.vertices.get_mut(&corner).unwrap();
+            v.owner = Some(owner);
+            v.build_type = build_type;
+        }
+        assert!(blocked_cards(&board).1.is_empty());
+
+        board.robber = hex.pos;
+        let (number, mut blocked) = blocked_cards(&board);
+        blocked.sort();
+        assert_eq!(number, hex.number);
+        assert_eq!(blocked, vec![(1, 1), (2, 2)]);
+        assert_ne!(number, 7);
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut stats = Stats::default();
+        stats.add_game(new_game(4), played_game(4).log()).unwrap();
+        let production = stats.production_csv();
+        let mut lines = production.lines();
+        assert_eq!(
+            lines.next(),
+            Some("game,turn,player,roll,ore,wheat,sheep,brick,wood,paper,cloth,coin")
+        );
+        assert_eq!(lines.count(), stats.production.len());
+        assert!(stats
+            .players_csv()
+            .starts_with("game,player,winner,first_city,"));
+        let dice = stats.dice_csv();
+        assert_eq!(dice.lines().count(), 12);
+        assert!(dice.lines().nth(6).unwrap().starts_with("7,"));
+
+        let dir = std::env::temp_dir().join(format!("catanrs-stats-{}", std::process::id()));
+        stats.write_csv(&dir).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("dice.csv")).unwrap(), dice);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use crate::resource::Resource;
+
+/// What a hex is made of, which decides what it produces.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Terrain {
+    Hills,
+    Forest,
+    Mountains,
+    Fields,
+    Pasture,
+    Desert,
+    Sea,
+    /// Produces any one resource of the owner's choice.
+    Gold,
+}
+impl Terrain {
+    /// The resource card this terrain produces, if it always produces the same one.
+    pub fn resource(self) -> Option<Resource> {
+        match self {
+            Terrain::Hills => Some(Resource::Brick),
+            Terrain::Forest => Some(Resource::Wood),
+            Terrain::Mountains => Some(Resource::Ore),
+            Terrain::Fields => Some(Resource::Wheat),
+            Terrain::Pasture => Some(Resource::Sheep),
+            Terrain::Desert | Terrain::Sea | Terrain::Gold => None,
+        }
+    }
+
+    /// The terrain that produces `resource`.
+    pub fn producing(resource: Resource) -> Terrain {
+        match resource {
+            Resource::Brick => Terrain::Hills,
+            Resource::Wood => Terrain::Forest,
+            Resource::Ore => Terrain::Mountains,
+            Resource::Wheat => Terrain::Fields,
+            Resource::Sheep => Terrain::Pasture,
+        }
+    }
+
+    /// Whether the hex gets a number token.
+    pub fn has_number(self) -> bool {
+        !matches!(self, Terrain::Desert | Terrain::Sea)
+    }
+
+    pub fn is_land(self) -> bool {
+        self != Terrain::Sea
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_producing_round_trips() {
+        for r in Resource::ALL {
+            assert_eq!(Terrain::producing(r).resource(), Some(r));
+        }
+        assert_eq!(Terrain::Desert.resource(), None);
+        assert!(!Terrain::Sea.has_number());
+        assert!(Terrain::Gold.has_number());
+    }
+}
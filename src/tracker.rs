@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+
+use crate::{
+    action::Action,
+    cities_knights::ProgressCard,
+    game::{roll_payouts, Game},
+    resource::{ResourceGroup, CARD_KINDS},
+    view::PlayerView,
+};
+
+/// The possible hands of one player, each with its probability.
+type Hands = HashMap<ResourceGroup, f64>;
+
+/// What one player can work out about everyone's cards from what happens in the open.
+///
+/// It only sees the observer's views and the actions taken. Production, building, trades,
+/// discards and monopolies show exactly which cards change hands; a card taken by the
+/// robber, the pirate, a wedding or a master merchant is only seen moving from the victim
+/// to the thief, unless the observer is one of them. Each player's hand is kept as a
+/// distribution over the hands they might hold. A hidden card taken is assumed to be
+/// independent of what the thief already had, so spending the card later narrows down the
+/// thief's hand but not the victim's.
+#[derive(Debug, Clone)]
+pub struct HandTracker {
+    observer: usize,
+    hands: Vec<Hands>,
+}
+impl HandTracker {
+    /// A tracker for `observer` in a game of `players` where nobody has cards yet.
+    pub fn new(players: usize, observer: usize) -> Self {
+        HandTracker {
+            observer,
+            hands: vec![HashMap::from([(ResourceGroup::empty(), 1.0)]); players],
+        }
+    }
+
+    /// Follows `game` from its start to its current state, showing the tracker only
+    /// `observer`'s views.
+    pub fn follow(start: Game, log: &[Action], observer: usize) -> anyhow::Result<Self> {
+        let mut tracker = HandTracker::new(start.players().len(), observer);
+        let mut game = start;
+        for action in log {
+            let before = game.fork();
+            game.apply(action.clone())?;
+            tracker.observe(&before.view_for(observer), action, &game.view_for(observer));
+        }
+        Ok(tracker)
+    }
+
+    /// Updates the hands for `action`, which took the observer's view from `before` to
+    /// `after`.
+    pub fn observe(&mut self, before: &PlayerView, action: &Action, after: &PlayerView) {
+        let actor = before.phase.player();
+        let lost: Vec<i32> = before
+            .players
+            .iter()
+            .zip(&after.players)
+            .map(|(b, a)| b.resource_cards - a.resource_cards)
+            .collect();
+        let own = match (&before.hand, &after.hand) {
+            (Some(b), Some(a)) => a.resources - b.resources,
+            _ => ResourceGroup::empty(),
+        };
+        let hidden = matches!(
+            action,
+            Action::MoveRobber {
+                victim: Some(_),
+                ..
+            } | Action::MovePirate {
+                victim: Some(_),
+                ..
+            } | Action::PlayProgressCard {
+                card: ProgressCard::Wedding | ProgressCard::MasterMerchant,
+                ..
+            }
+        );
+        if hidden {
+            let victims: Vec<usize> = (0..lost.len())
+                .filter(|&p| p != actor && lost[p] > 0)
+                .collect();
+            for &victim in &victims {
+                if self.observer == victim {
+                    self.change(victim, own);
+                    self.change(actor, own * -1);
+                } else if self.observer == actor && victims.len() == 1 {
+                    self.change(victim, own * -1);
+                    self.change(actor, own);
+                } else {
+                    for _ in 0..lost[victim] {
+                        self.steal_unknown(actor, victim);
+                    }
+                }
+            }
+        } else {
+            for (player, delta) in public_deltas(before, action, after, &lost)
+                .into_iter()
+                .enumerate()
+            {
+                self.change(player, delta);
+            }
+        }
+        // whatever happened, the observer knows their own hand
+        if let Some(hand) = &after.hand {
+            self.hands[self.observer] = HashMap::from([(hand.resources, 1.0)]);
+        }
+    }
+
+    /// Adds `delta` to every hand `player` might hold, which may take cards away. Hands
+    /// that can't pay what was taken are ruled out.
+    pub fn change(&mut self, player: usize, delta: ResourceGroup) {
+        if delta.is_empty() {
+            return;
+        }
+        let hands = &self.hands[player];
+        let mut possible: Hands = HashMap::new();
+        for (hand, &p) in hands {
+            let after = *hand + delta;
+            if after.is_non_negative() {
+                *possible.entry(after).or_default() += p;
+            }
+        }
+        if possible.is_empty() {
+            // only a tracker that missed part of the game gets here; keep what is left
+            for (hand, &p) in hands {
+                let clamped =
+                    ResourceGroup::from_counts((*hand + delta).counts().map(|c| c.max(0)));
+                *possible.entry(clamped).or_default() += p;
+            }
+        }
+        self.hands[player] = normalized(possible);
+    }
+
+    /// `thief` takes a card `victim` holds without anyone else seeing which.
+    pub fn steal_unknown(&mut self, thief: usize, victim: usize) {
+        let mut taken = [0.0; CARD_KINDS];
+        let mut victim_after: Hands = HashMap::new();
+        for (hand, &p) in &self.hands[victim] {
+            let total = hand.total();
+            if total == 0 {
+                *victim_after.entry(*hand).or_default() += p;
+                continue;
+            }
+            for (kind, count) in hand.counts().into_iter().enumerate() {
+                if count > 0 {
+                    let share = p * count as f64 / total as f64;
+                    taken[kind] += share;
+                    *victim_after.entry(*hand - card(kind)).or_default() += share;
+                }
+            }
+        }
+        let taken_total: f64 = taken.iter().sum();
+        if taken_total == 0.0 {
+            return;
+        }
+        let mut thief_after: Hands = HashMap::new();
+        for (hand, &p) in &self.hands[thief] {
+            for (kind, &q) in taken.iter().enumerate() {
+                if q > 0.0 {
+                    *thief_after.entry(*hand + card(kind)).or_default() += p * q / taken_total;
+                }
+            }
+        }
+        self.hands[victim] = normalized(victim_after);
+        self.hands[thief] = normalized(thief_after);
+    }
+
+    /// Every hand `player` might hold with its probability, the likeliest first.
+    pub fn distribution(&self, player: usize) -> Vec<(ResourceGroup, f64)> {
+        let mut hands: Vec<(ResourceGroup, f64)> =
+            self.hands[player].iter().map(|(&h, &p)| (h, p)).collect();
+        hands.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.counts().cmp(&b.0.counts())));
+        hands
+    }
+
+    /// The expected number of each kind of card `player` holds, in the order of
+    /// `ResourceGroup::counts`.
+    pub fn expected(&self, player: usize) -> [f64; CARD_KINDS] {
+        let mut expected = [0.0; CARD_KINDS];
+        for (hand, &p) in &self.hands[player] {
+            for (e, count) in expected.iter_mut().zip(hand.counts()) {
+                *e += p * count as f64;
+            }
+        }
+        expected
+    }
+
+    /// The chance that `player` can afford `cost`.
+    pub fn can_afford(&self, player: usize, cost: &ResourceGroup) -> f64 {
+        self.hands[player]
+            .iter()
+            .filter(|(hand, _)| hand.can_afford(cost))
+            .map(|(_, &p)| p)
+            .sum()
+    }
+}
+
+/// How every hand changed with `action`, which showed everyone the cards involved. `lost`
+/// is how many cards each player gave up.
+fn public_deltas(
+    before: &PlayerView,
+    action: &Action,
+    after: &PlayerView,
+    lost: &[i32],
+) -> Vec<ResourceGroup> {
+    let actor = before.phase.player();
+    let mut deltas = vec![ResourceGroup::empty(); lost.len()];
+    let monopoly = match action {
+        Action::PlayMonopoly(resource) => Some(*resource),
+        Action::PlayProgressCard {
+            card: ProgressCard::ResourceMonopoly,
+            resource,
+        } => *resource,
+        _ => None,
+    };
+    match (action, &before.trade) {
+        (Action::RollDice, _) => {
+            let roll = after.last_roll.expect("the dice were rolled");
+            // the board as it was paid out from, after any pillaging by the barbarians
+            let cities_and_knights = after.barbarians.is_some();
+            for (player, paid) in roll_payouts(after.board, &before.bank, cities_and_knights, roll)
+            {
+                deltas[player] = paid;
+            }
+        }
+        (Action::AcceptTrade, Some(trade)) => {
+            deltas[trade.from] = trade.get - trade.give;
+            deltas[trade.to] = trade.give - trade.get;
+        }
+        _ => match monopoly {
+            Some(resource) => {
+                for (player, &count) in lost.iter().enumerate() {
+                    if player != actor {
+                        let taken = ResourceGroup::of(resource, count);
+                        deltas[player] -= taken;
+                        deltas[actor] += taken;
+                    }
+                }
+            }
+            // everything else trades with the bank
+            None => deltas[actor] = before.bank - after.bank,
+        },
+    }
+    deltas
+}
+
+/// One card of the kind at `kind` in `ResourceGroup::counts`.
+fn card(kind: usize) -> ResourceGroup {
+    let mut counts = [0; CARD_KINDS];
+    counts[kind] = 1;
+    ResourceGroup::from_counts(counts)
+}
+
+fn normalized(mut hands: Hands) -> Hands {
+    let total: f64 = hands.values().sum();
+    if total > 0.0 {
+        for p in hands.values_mut() {
+            *p /= total;
+        }
+    }
+    hands
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        agent::{Agent, RandomAgent},
+        board::Board,
+        phase::{DevelopmentPhase, Phase, TurnPhase},
+        player::Player,
+        resource::Resource,
+        rules::Rules,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_unknown_steal() {
+        let mut tracker = HandTracker::new(3, 0);
+        tracker.change(1, ResourceGroup::new(1, 0, 0, 0, 1));
+        tracker.steal_unknown(2, 1);
+        let ore = ResourceGroup::of(Resource::Ore, 1);
+        let wood = ResourceGroup::of(Resource::Wood, 1);
+        assert_eq!(tracker.distribution(1), vec![(wood, 0.5), (ore, 0.5)]);
+        assert_eq!(tracker.can_afford(2, &ore), 0.5);
+        assert_eq!(tracker.expected(2)[0], 0.5);
+
+        // spending the wood shows the ore was stolen
+        tracker.change(1, wood * -1);
+        assert_eq!(tracker.distribution(1), vec![(ResourceGroup::empty(), 1.0)]);
+        tracker.change(2, ore * -1);
+        assert_eq!(tracker.distribution(2), vec![(ResourceGroup::empty(), 1.0)]);
+    }
+
+    #[test]
+    fn test_others_see_a_card_move() {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 6).unwrap();
+        while !game.phase().is_turn() {
+            let action = game.legal_actions()[0].clone();
+            game.apply(action).unwrap();
+        }
+        game.phase = Phase::Turn {
+            player: 1,
+            turn_phase: TurnPhase::MoveThief,
+            development_phase: DevelopmentPhase::Ready,
+        };
+        let hands = [
+            ResourceGroup::new(0, 1, 0, 0, 0),
+            ResourceGroup::empty(),
+            ResourceGroup::new(1, 0, 0, 0, 1),
+        ];
+        let mut trackers: Vec<HandTracker> = (0..3).map(|p| HandTracker::new(3, p)).collect();
+        for (p, hand) in hands.into_iter().enumerate() {
+            game.players[p].resources = hand;
+            for tracker in &mut trackers {
+                tracker.change(p, hand);
+            }
+        }
+        let steal = game
+            .legal_actions()
+            .into_iter()
+            .find(|a| {
+                matches!(
+                    a,
+                    Action::MoveRobber {
+                        victim: Some(2),
+                        ..
+                    }
+                )
+            })
+            .unwrap();
+        let before = game.fork();
+        game.apply(steal.clone()).unwrap();
+        for (observer, tracker) in trackers.iter_mut().enumerate() {
+            tracker.observe(&before.view_for(observer), &steal, &game.view_for(observer));
+        }
+
+        let ore = ResourceGroup::of(Resource::Ore, 1);
+        let wood = ResourceGroup::of(Resource::Wood, 1);
+        assert_eq!(trackers[0].distribution(1), vec![(wood, 0.5), (ore, 0.5)]);
+        assert_eq!(trackers[0].distribution(2), vec![(wood, 0.5), (ore, 0.5)]);
+        let stolen = game.players()[1].resources;
+        for observer in [1, 2] {
+            let tracker = &trackers[observer];
+            assert_eq!(tracker.distribution(1), vec![(stolen, 1.0)]);
+            assert_eq!(tracker.distribution(2), vec![(hands[2] - stolen, 1.0)]);
+        }
+    }
+
+    /// Plays `steps` random actions, checking after each that every tracker still allows
+    /// the true hands and knows its observer's, and returns how many hidden steals there
+    /// were.
+    fn track_random_game(mut game: Game, steps: usize) -> usize {
+        let mut trackers: Vec<HandTracker> = (0..3).map(|p| HandTracker::new(3, p)).collect();
+        let mut agent = RandomAgent::new(0);
+        let mut steals = 0;
+        for _ in 0..steps {
+            let legal = game.legal_actions();
+            if legal.is_empty() || game.winner().is_some() {
+                break;
+            }
+            let action = agent.choose_action(&game.view_for(game.current_player()), &legal);
+            let before = game.fork();
+            game.apply(action.clone()).unwrap();
+            if matches!(
+                action,
+                Action::MoveRobber {
+                    victim: Some(_),
+                    ..
+                }
+            ) {
+                steals += 1;
+            }
+            for (observer, tracker) in trackers.iter_mut().enumerate() {
+                tracker.observe(
+                    &before.view_for(observer),
+                    &action,
+                    &game.view_for(observer),
+                );
+            }
+            for (observer, tracker) in trackers.iter().enumerate() {
+                for (p, player) in game.players().iter().enumerate() {
+                    let hands = tracker.distribution(p);
+                    assert!(
+                        hands.iter().any(|(h, _)| *h == player.resources),
+                        "{action:?}"
+                    );
+                    if p == observer {
+                        assert_eq!(hands, vec![(player.resources, 1.0)]);
+                    }
+                    let total: f64 = tracker.expected(p).iter().sum();
+                    assert!((total - player.hand_size() as f64).abs() < 1e-9);
+                }
+            }
+        }
+        steals
+    }
+
+    #[test]
+    fn test_true_hands_stay_possible() {
+        let game = Game::with_seed(Player::init_players(3), Board::new(), 6).unwrap();
+        assert!(track_random_game(game, 400) > 0);
+    }
+
+    #[test]
+    fn test_true_hands_stay_possible_with_commodities() {
+        let game = Game::with_cities_and_knights(
+            Player::init_players(3),
+            Board::new(),
+            Rules::cities_and_knights(),
+            6,
+        )
+        .unwrap();
+        track_random_game(game, 400);
+    }
+}
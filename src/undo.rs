@@ -0,0 +1,227 @@
+use crate::{
+    action::Action, board::Board, error::RuleError, game::Game, phase::Phase, player::Player,
+    resource::ResourceGroup, seafarers::ShipTurn,
+};
+
+/// The state an undoable action can change, taken just before it was applied.
+#[derive(Debug, Clone)]
+pub(crate) struct Snapshot {
+    players: Vec<Player>,
+    board: Board,
+    bank: ResourceGroup,
+    phase: Phase,
+    player_with_road: Option<u32>,
+    ships: ShipTurn,
+}
+
+impl Game {
+    /// Actions that reveal nothing hidden and leave the rng untouched, so taking them back
+    /// can't leak information or change what happens next.
+    pub fn is_undoable(action: &Action) -> bool {
+        matches!(
+            action,
+            Action::BuildSettlement(_)
+                | Action::BuildCity(_)
+                | Action::BuildRoad(_)
+                | Action::BuildShip(_)
+                | Action::MoveShip { .. }
+                | Action::BankTrade { .. }
+        )
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Takes back the last action of the current turn, returning it. Rolls, steals, card
+    /// draws and anything else that revealed information can't be taken back, and neither
+    /// can anything applied before them or the action that won the game.
+    pub fn undo(&mut self) -> Result<Action, RuleError> {
+        let Some(snapshot) = self.undo.pop() else {
+            return Err(match self.log.last() {
+                Some(action) => RuleError::CantUndo(action.clone()),
+                None => RuleError::NothingToUndo,
+            });
+        };
+        let action = self.log.pop().expect("every snapshot has a logged action");
+        self.players = snapshot.players;
+        self.board = snapshot.board;
+        self.bank = snapshot.bank;
+        self.phase = snapshot.phase;
+        self.player_with_road = snapshot.player_with_road;
+        self.ships = snapshot.ships;
+        self.redo.push(action.clone());
+        Ok(action)
+    }
+
+    /// Applies the last undone action again, returning it.
+    pub fn redo(&mut self) -> Result<Action, RuleError> {
+        let action = self.redo.last().cloned().ok_or(RuleError::NothingToRedo)?;
+        self.validate(&action)?;
+        self.redo.pop();
+        self.commit(action.clone());
+        Ok(action)
+    }
+
+    /// Keeps what undoing `action` needs, or forgets everything that could be undone when
+    /// `action` can't be.
+    pub(crate) fn record_undo(&mut self, action: &Action) {
+        if Game::is_undoable(action) && self.phase.is_turn() {
+            self.undo.push(Snapshot {
+                players: self.players.clone(),
+                board: self.board.clone(),
+                bank: self.bank,
+                phase: self.phase,
+                player_with_road: self.player_with_road,
+                ships: self.ships.clone(),
+            });
+        } else {
+            self.undo.clear();
+            self.redo.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        axial::Axial,
+        dice::Dice,
+        edge::PathCoords,
+        phase::{DevelopmentPhase, TurnPhase},
+        resource::Resource,
+    };
+
+    use super::*;
+
+    /// Player 0 on their first turn after rolling, holding enough for two roads.
+    fn rolled_game() -> Game {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 3).unwrap();
+        for (settlement, road) in [
+            (Axial::new(0, 1), Axial::new(0, 2)),
+            (Axial::new(3, -1), Axial::new(3, -2)),
+            (Axial::new(-3, 1), Axial::new(-3, 2)),
+            (Axial::new(0, -2), Axial::new(0, -1)),
+            (Axial::new(-2, 3), Axial::new(-2, 2)),
+            (Axial::new(-2, -1), Axial::new(-1, -2)),
+        ] {
+            game.apply(Action::BuildSettlement(settlement)).unwrap();
+            game.apply(Action::BuildRoad(PathCoords::new(settlement, road)))
+                .unwrap();
+        }
+        game.set_dice(Dice::scripted([12]));
+        game.apply(Action::RollDice).unwrap();
+        game.players[0].resources = ResourceGroup::new(4, 0, 0, 2, 2);
+        game
+    }
+
+    #[test]
+    fn test_undo_and_redo_road() {
+        let mut game = rolled_game();
+        let road = PathCoords::new(Axial::new(0, 2), Axial::new(1, 2));
+        game.apply(Action::BuildRoad(road.clone())).unwrap();
+        assert!(game.can_undo());
+
+        assert_eq!(game.undo().unwrap(), Action::BuildRoad(road.clone()));
+        assert_eq!(game.board().edges[&road].owner, None);
+        assert_eq!(
+            game.players()[0].resources,
+            ResourceGroup::new(4, 0, 0, 2, 2)
+        );
+        assert_eq!(game.log().last(), Some(&Action::RollDice));
+
+        assert_eq!(game.redo().unwrap(), Action::BuildRoad(road.clone()));
+        assert_eq!(game.board().edges[&road].owner, Some(0));
+        assert_eq!(
+            game.players()[0].resources,
+            ResourceGroup::new(4, 0, 0, 1, 1)
+        );
+        assert!(!game.can_redo());
+    }
+
+    #[test]
+    fn test_undo_several_actions() {
+        let mut game = rolled_game();
+        game.apply(Action::BankTrade {
+            give: Resource::Ore,
+            get: Resource::Wheat,
+        })
+        .unwrap();
+        game.apply(Action::BuildRoad(PathCoords::new(
+            Axial::new(0, 2),
+            Axial::new(1, 2),
+        )))
+        .unwrap();
+
+        game.undo().unwrap();
+        game.undo().unwrap();
+        assert_eq!(
+            game.players()[0].resources,
+            ResourceGroup::new(4, 0, 0, 2, 2)
+        );
+        assert!(game.undo().is_err());
+        assert_eq!(
+            game.phase(),
+            Phase::Turn {
+                player: 0,
+                turn_phase: TurnPhase::Free,
+                development_phase: DevelopmentPhase::Ready,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cant_undo_past_roll_or_draw() {
+        let mut game = rolled_game();
+        assert!(game.undo().is_err());
+
+        game.players[0].resources = ResourceGroup::new(1, 1, 1, 1, 1);
+        game.apply(Action::BuildRoad(PathCoords::new(
+            Axial::new(0, 2),
+            Axial::new(1, 2),
+        )))
+        .unwrap();
+        game.apply(Action::BuyDevelopmentCard).unwrap();
+        assert!(!game.can_undo());
+        assert!(game.undo().is_err());
+    }
+
+    #[test]
+    fn test_new_action_clears_redo() {
+        let mut game = rolled_game();
+        game.apply(Action::BuildRoad(PathCoords::new(
+            Axial::new(0, 2),
+            Axial::new(1, 2),
+        )))
+        .unwrap();
+        game.undo().unwrap();
+        game.apply(Action::BankTrade {
+            give: Resource::Ore,
+            get: Resource::Sheep,
+        })
+        .unwrap();
+
+        assert!(!game.can_redo());
+        assert!(game.redo().is_err());
+    }
+
+    #[test]
+    fn test_cant_undo_winning_build() {
+        let mut game = rolled_game();
+        game.rules.victory_points = 3;
+        game.players[0].resources = ResourceGroup::new(3, 2, 0, 0, 0);
+        game.apply(Action::BuildCity(Axial::new(0, 1))).unwrap();
+        assert_eq!(game.winner(), Some(0));
+
+        assert!(!game.can_undo());
+        assert_eq!(
+            game.undo(),
+            Err(RuleError::CantUndo(Action::BuildCity(Axial::new(0, 1))))
+        );
+        assert_eq!(game.winner(), Some(0));
+    }
+}
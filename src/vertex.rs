@@ -1,12 +1,10 @@
-use std::any;
+use serde::{Deserialize, Serialize};
 
-use anyhow::{anyhow, Result};
-
-use crate::{board::Board, resource::ResourceGroup};
+use crate::resource::ResourceGroup;
 
 use super::axial::Axial;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Vertex {
     pub pos: Axial,
     pub build_type: BuildType,
@@ -21,18 +19,30 @@ impl Vertex {
         }
     }
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BuildType {
     City,
     Settlement,
+    /// Cities & Knights knight, from basic at level 1 to mighty at level 3. Only active
+    /// knights defend against the barbarians.
+    Knight {
+        level: u8,
+        active: bool,
+    },
     None,
 }
 impl BuildType {
     pub fn cost(&self) -> ResourceGroup {
         match self {
-            BuildType::City => ResourceGroup::new(0, 1, 1, 1, 1),
-            BuildType::Settlement => ResourceGroup::new(3, 2, 0, 0, 0),
+            BuildType::City => ResourceGroup::new(3, 2, 0, 0, 0),
+            BuildType::Settlement => ResourceGroup::new(0, 1, 1, 1, 1),
+            BuildType::Knight { .. } => ResourceGroup::new(1, 0, 1, 0, 0),
             BuildType::None => ResourceGroup::empty(),
         }
     }
+
+    /// Settlements and cities, which produce and score, unlike knights.
+    pub fn is_building(&self) -> bool {
+        matches!(self, BuildType::City | BuildType::Settlement)
+    }
 }
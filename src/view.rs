@@ -0,0 +1,203 @@
+use serde::Serialize;
+
+use crate::{
+    axial::Axial,
+    board::Board,
+    cities_knights::ProgressCard,
+    deck::DevelopmentCard,
+    edge::PathCoords,
+    game::{Game, TradeOffer},
+    phase::Phase,
+    resource::ResourceGroup,
+};
+
+/// What every player can see about a player.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublicPlayer {
+    pub id: usize,
+    pub buildings: Vec<Axial>,
+    pub paths: Vec<PathCoords>,
+    pub resource_cards: i32,
+    /// Unplayed development cards, including ones bought this turn.
+    pub development_cards: usize,
+    pub knights_played: u32,
+    pub longest_road: usize,
+    /// Points from buildings, longest road and largest army. Victory point cards stay
+    /// hidden until they win the game.
+    pub victory_points: u32,
+    /// Cities & Knights: the level on each improvement track, in the order of
+    /// `Track::ALL`, and the number of unplayed progress cards.
+    pub improvements: [u8; 3],
+    pub progress_cards: usize,
+}
+
+/// The hidden part of a player's state, only shown to that player.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hand {
+    pub resources: ResourceGroup,
+    pub development_cards: Vec<DevelopmentCard>,
+    pub new_development_cards: Vec<DevelopmentCard>,
+    pub progress_cards: Vec<ProgressCard>,
+    /// Including victory point cards.
+    pub victory_points: u32,
+}
+
+/// The game as one player, or a spectator, is allowed to see it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerView<'a> {
+    /// The player looking, or `None` for a spectator.
+    pub player: Option<usize>,
+    pub board: &'a Board,
+    pub bank: ResourceGroup,
+    pub development_cards_left: usize,
+    pub phase: Phase,
+    pub last_roll: Option<i32>,
+    pub trade: Option<TradeOffer>,
+    pub player_with_road: Option<u32>,
+    pub player_with_army: Option<u32>,
+    /// How far the barbarian ship has sailed, in a Cities & Knights game.
+    pub barbarians: Option<u8>,
+    pub players: Vec<PublicPlayer>,
+    /// The looking player's own hand, `None` for a spectator.
+    pub hand: Option<Hand>,
+}
+impl PlayerView<'_> {
+    pub fn public(&self, player: usize) -> &PublicPlayer {
+        &self.players[player]
+    }
+}
+
+impl Game {
+    pub fn view_for(&self, player: usize) -> PlayerView<'_> {
+        let p = &self.players[player];
+        let mut view = self.spectator_view();
+        view.player = Some(player);
+        view.hand = Some(Hand {
+            resources: p.resources,
+            development_cards: p.development_cards.clone(),
+            new_development_cards: p.new_development_cards.clone(),
+            progress_cards: self
+                .cities_knights
+                .as_ref()
+                .map(|ck| ck.progress_cards[player].clone())
+                .unwrap_or_default(),
+            victory_points: self.victory_points(player),
+        });
+        view
+    }
+
+    /// The game with every hand hidden.
+    pub fn spectator_view(&self) -> PlayerView<'_> {
+        let ck = self.cities_knights.as_ref();
+        let players = self
+            .players
+            .iter()
+            .map(|p| PublicPlayer {
+                id: p.id,
+                buildings: p.buildings.clone(),
+                paths: p.paths.clone(),
+                resource_cards: p.hand_size(),
+                development_cards: p.development_cards.len() + p.new_development_cards.len(),
+                knights_played: p.knights_played,
+                longest_road: self.board.longest_road(p.id),
+                victory_points: self.public_victory_points(p.id),
+                improvements: ck.map_or([0; 3], |ck| ck.improvements[p.id]),
+                progress_cards: ck.map_or(0, |ck| ck.progress_cards[p.id].len()),
+            })
+            .collect();
+        PlayerView {
+            player: None,
+            board: &self.board,
+            bank: self.bank,
+            development_cards_left: self.deck.len(),
+            phase: self.phase,
+            last_roll: self.last_roll,
+            trade: self.trade.clone(),
+            player_with_road: self.player_with_road,
+            player_with_army: self.player_with_army,
+            barbarians: ck.map(|ck| ck.barbarians),
+            players,
+            hand: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{player::Player, resource::Resource, rules::Rules};
+
+    use super::*;
+
+    fn game_with_hands() -> Game {
+        let mut game = Game::with_seed(Player::init_players(3), Board::new(), 0).unwrap();
+        game.players[0].resources = ResourceGroup::new(1, 2, 0, 0, 0);
+        game.players[1].resources = ResourceGroup::new(0, 0, 3, 1, 0);
+        game.players[1].development_cards = vec![DevelopmentCard::VictoryPoint];
+        game.players[1].knights_played = 2;
+        game
+    }
+
+    #[test]
+    fn test_view_hides_other_hands() {
+        let game = game_with_hands();
+        let view = game.view_for(0);
+
+        let hand = view.hand.as_ref().unwrap();
+        assert_eq!(hand.resources, ResourceGroup::new(1, 2, 0, 0, 0));
+        assert_eq!(view.public(1).resource_cards, 4);
+        assert_eq!(view.public(1).development_cards, 1);
+        assert_eq!(view.public(1).knights_played, 2);
+        assert_eq!(view.public(1).victory_points, 0);
+
+        let json = serde_json::to_string(&view).unwrap();
+        assert!(!json.contains("VictoryPoint"));
+    }
+
+    #[test]
+    fn test_own_view_counts_hidden_points() {
+        let game = game_with_hands();
+        let view = game.view_for(1);
+
+        let hand = view.hand.as_ref().unwrap();
+        assert_eq!(hand.development_cards, vec![DevelopmentCard::VictoryPoint]);
+        assert_eq!(hand.victory_points, 1);
+        assert_eq!(hand.resources.get(Resource::Sheep), 3);
+    }
+
+    #[test]
+    fn test_spectator_sees_no_hand() {
+        let game = game_with_hands();
+        let view = game.spectator_view();
+
+        assert_eq!(view.player, None);
+        assert!(view.hand.is_none());
+        assert_eq!(view.players.len(), 3);
+        assert_eq!(view.development_cards_left, 25);
+    }
+
+    #[test]
+    fn test_cities_and_knights_view() {
+        let mut game = Game::with_cities_and_knights(
+            Player::init_players(3),
+            Board::new(),
+            Rules::cities_and_knights(),
+            0,
+        )
+        .unwrap();
+        let ck = game.cities_knights.as_mut().unwrap();
+        ck.improvements[1] = [2, 0, 1];
+        ck.barbarians = 4;
+        ck.progress_cards[1] = vec![ProgressCard::Warlord];
+
+        let view = game.view_for(0);
+        assert_eq!(view.barbarians, Some(4));
+        assert_eq!(view.public(1).improvements, [2, 0, 1]);
+        assert_eq!(view.public(1).progress_cards, 1);
+        assert!(view.hand.unwrap().progress_cards.is_empty());
+        assert_eq!(
+            game.view_for(1).hand.unwrap().progress_cards,
+            vec![ProgressCard::Warlord]
+        );
+        assert_eq!(game_with_hands().spectator_view().barbarians, None);
+    }
+}